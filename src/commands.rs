@@ -3,9 +3,13 @@ use colored::*;
 use std::collections::HashMap;
 use std::path::PathBuf;
 
-use crate::database::{Contribution, Database, Repository};
+use crate::database::{Contribution, Database, LineTotals, Repository};
+use crate::exclude::ExclusionRules;
 use crate::git;
-use crate::markdown;
+use crate::linking::{link_commits, LinkRules};
+use crate::render::{OutputFormat, RenderInput, Renderer};
+use crate::template::TemplateRenderer;
+use crate::utils::{short_hash, DateRange};
 
 pub fn init_command(
     repo_url: String,
@@ -22,6 +26,7 @@ pub fn init_command(
         organization: org.clone(),
         name: name.clone(),
         description: description.clone(),
+        local_path: None,
     };
 
     db.add_repository(&repo)?;
@@ -34,13 +39,20 @@ pub fn init_command(
         Config::new()
     };
     
-    // Add repository to config
+    // Add repository to config, keeping any aliases and checkout path already configured
+    let (aliases, path) = config
+        .repositories
+        .remove(&repo_url)
+        .map(|r| (r.aliases, r.path))
+        .unwrap_or_default();
     config.repositories.insert(
         repo_url.clone(),
         RepositoryConfig {
             organization: org,
             name,
             description,
+            aliases,
+            path,
         },
     );
     
@@ -52,6 +64,222 @@ pub fn init_command(
     Ok(())
 }
 
+pub fn discover_command(dir: PathBuf, yes: bool) -> Result<()> {
+    use crate::config::{Config, RepositoryConfig};
+    use crate::utils::{get_config_path, normalize_repo_url, repo_owner_and_name};
+
+    let root = dir
+        .canonicalize()
+        .with_context(|| format!("Directory not found: {:?}", dir))?;
+    let db = Database::open()?;
+    let config_path = get_config_path()?;
+    let mut config = if config_path.exists() {
+        Config::from_toml(&config_path)?
+    } else {
+        Config::new()
+    };
+    let registered = db.get_all_repositories()?;
+
+    // Registered repositories only get their checkout path recorded
+    let mut found: Vec<(Repository, bool)> = Vec::new();
+    for path in crate::discover::find_repositories(&root) {
+        let local_path = path.display().to_string();
+        let Some(remote) = git::origin_url(&path).ok().flatten() else {
+            println!("{} {} has no origin remote; skipped", "⚠".yellow(), local_path);
+            continue;
+        };
+
+        if let Some(url) = resolve_registered_url(&registered, &config, &remote) {
+            let mut repo = registered.iter().find(|r| r.url == url).cloned().expect("resolved from this list");
+            repo.local_path = Some(local_path);
+            found.push((repo, false));
+            continue;
+        }
+
+        let Some((organization, name)) = repo_owner_and_name(&remote) else {
+            println!("{} Cannot infer organization and name from '{}' ({}); skipped", "⚠".yellow(), remote, local_path);
+            continue;
+        };
+        let url = normalize_repo_url(&remote);
+        if found.iter().any(|(repo, _)| repo.url == url) {
+            println!("{} {} is another checkout of {}; skipped", "⚠".yellow(), local_path, url);
+            continue;
+        }
+        found.push((
+            Repository {
+                url,
+                organization,
+                name,
+                description: None,
+                local_path: Some(local_path),
+            },
+            true,
+        ));
+    }
+
+    if found.is_empty() {
+        println!("No git repositories with a remote found under {}", root.display());
+        return Ok(());
+    }
+
+    println!("\n{} Repositories under {}", "🔎".blue(), root.display());
+    println!("{}", "=".repeat(80));
+    for (repo, new) in &found {
+        let status = if *new { "new".green() } else { "registered".dimmed() };
+        println!("  [{}] {}/{} {}", status, repo.organization, repo.name.bold(), repo.url);
+        println!("      {}", repo.local_path.as_deref().unwrap_or(""));
+    }
+
+    if !yes && !confirm(&format!("Register {} repositories and record their paths?", found.len()))? {
+        println!("Nothing registered");
+        return Ok(());
+    }
+
+    let mut added = 0;
+    for (repo, new) in &found {
+        db.add_repository(repo)?;
+        let entry = config.repositories.entry(repo.url.clone()).or_insert_with(|| RepositoryConfig {
+            organization: repo.organization.clone(),
+            name: repo.name.clone(),
+            description: repo.description.clone(),
+            aliases: Vec::new(),
+            path: None,
+        });
+        entry.path = repo.local_path.clone();
+        if *new {
+            added += 1;
+        }
+    }
+    config.to_toml(&config_path)?;
+
+    println!("{} Registered {} new repositories; recorded paths for {} already registered",
+             "✓".green(), added, found.len() - added);
+    Ok(())
+}
+
+/// Ask a yes/no question on the terminal; anything but "y" or "yes" is a no
+fn confirm(question: &str) -> Result<bool> {
+    use std::io::Write;
+
+    print!("\n{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Find key and related commits that a rebase or force-push removed, propose
+/// the commits they were rewritten into, and rewrite the lists once confirmed
+pub fn repair_command(repo_url: String, repo_path: Option<PathBuf>, yes: bool) -> Result<()> {
+    use crate::repair::{find_rewrite, Remap};
+
+    let db = Database::open()?;
+    let repository = db.get_repository(&repo_url)?
+        .with_context(|| format!("Repository not found: {}", repo_url))?;
+    let repo_path = repo_path
+        .or_else(|| repository.local_path.map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    let reachable = git::reachable_commits(&repo_path)?;
+    let rewrites = git::reflog_rewrites(&repo_path)?;
+    let stored = db.get_repository_commits(&repo_url)?;
+    let candidates: Vec<_> = stored.iter().filter(|c| reachable.contains(&c.hash)).collect();
+
+    let mut remaps = Vec::new();
+    let mut unresolved = Vec::new();
+    for contrib in db.get_contributions(&repo_url)? {
+        let Some(id) = contrib.id else { continue };
+        for (role, hashes) in [("key", &contrib.key_commits), ("related", &contrib.related_commits)] {
+            for hash in hashes {
+                if reachable.iter().any(|h| h.starts_with(hash.as_str())) {
+                    continue;
+                }
+                // What is known about the missing commit: stored details, or the
+                // commit itself while the reflog still keeps it
+                let old = match stored.iter().find(|c| c.hash.starts_with(hash.as_str())) {
+                    Some(commit) => Some(commit.clone()),
+                    None => git::find_commit(&repo_path, &repo_url, hash)?,
+                };
+                match find_rewrite(hash, old.as_ref(), &candidates, &rewrites) {
+                    Some((new, reason)) => remaps.push(Remap {
+                        contribution_id: id,
+                        contribution: contrib.name.clone(),
+                        role,
+                        old: hash.clone(),
+                        new,
+                        reason,
+                    }),
+                    None => unresolved.push((contrib.name.clone(), role, hash.clone())),
+                }
+            }
+        }
+    }
+
+    if remaps.is_empty() && unresolved.is_empty() {
+        println!("{} Every key and related commit exists in {:?}", "✓".green(), repo_path);
+        return Ok(());
+    }
+
+    // Rewritten commits can only be proposed once `update` has stored them
+    let unstored = reachable.len() - candidates.len();
+    if unstored > 0 {
+        println!("{} {} commits in {:?} are not stored yet and cannot be proposed; run 'contrack update' first",
+                 "⚠".yellow(), unstored, repo_path);
+    }
+
+    println!("\n{} Missing commits in {}", "🔧".blue(), repo_url);
+    println!("{}", "=".repeat(80));
+    for remap in &remaps {
+        let new = candidates.iter().find(|c| c.hash == remap.new);
+        println!("  {} ({} commit) {} → {} {}", remap.contribution.bold(), remap.role, remap.old.red(),
+                 remap.replacement().green(), new.and_then(|c| c.message.lines().next()).unwrap_or(""));
+        println!("      {}", remap.reason.as_str().dimmed());
+    }
+    for (name, role, hash) in &unresolved {
+        println!("  {} ({} commit) {} → {}", name.bold(), role, hash.red(), "no rewritten equivalent found".yellow());
+    }
+
+    if remaps.is_empty() {
+        return Ok(());
+    }
+    if !yes && !confirm(&format!("Rewrite {} commit hashes?", remaps.len()))? {
+        println!("Nothing changed");
+        return Ok(());
+    }
+
+    let mut contributions: Vec<i64> = remaps.iter().map(|r| r.contribution_id).collect();
+    contributions.dedup();
+    for id in &contributions {
+        let mut contrib = db
+            .get_contributions(&repo_url)?
+            .into_iter()
+            .find(|c| c.id == Some(*id))
+            .context("Contribution disappeared while repairing")?;
+        for remap in remaps.iter().filter(|r| r.contribution_id == *id) {
+            let list = match remap.role {
+                "key" => &mut contrib.key_commits,
+                _ => &mut contrib.related_commits,
+            };
+            replace_commit(list, &remap.old, remap.replacement());
+        }
+        db.update_contribution(&contrib)?;
+        db.unlink_commits(*id)?;
+    }
+    link_commits(&db, &repo_url, &LinkRules::load()?)?;
+
+    println!("{} Remapped {} commits across {} contributions", "✓".green(), remaps.len(), contributions.len());
+    Ok(())
+}
+
+/// Replace `old` in a hash list, dropping it instead if `new` is already listed
+fn replace_commit(list: &mut Vec<String>, old: &str, new: &str) {
+    if list.iter().any(|h| h == new) {
+        list.retain(|h| h != old);
+    } else if let Some(entry) = list.iter_mut().find(|h| *h == old) {
+        *entry = new.to_string();
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn add_command(
     repo_url: String,
@@ -65,19 +293,9 @@ pub fn add_command(
 ) -> Result<()> {
     let db = Database::open()?;
 
-    let key_commits_vec: Vec<String> = key_commits
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect();
-
+    let key_commits_vec = parse_commit_list(&key_commits);
     let related_commits_vec: Vec<String> = related_commits
-        .map(|s| {
-            s.split(',')
-                .map(|s| s.trim().to_string())
-                .filter(|s| !s.is_empty())
-                .collect()
-        })
+        .map(|s| parse_commit_list(&s))
         .unwrap_or_default();
 
     let contrib = Contribution {
@@ -99,63 +317,621 @@ pub fn add_command(
     Ok(())
 }
 
-pub fn update_command(repo_path: Option<PathBuf>) -> Result<()> {
+/// Split a comma-separated list of commit hashes
+fn parse_commit_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Field changes requested by `contrack edit`; `None` leaves a field unchanged
+#[derive(Debug, Default)]
+pub struct ContributionEdit {
+    pub new_name: Option<String>,
+    pub overview: Option<String>,
+    pub description: Option<String>,
+    pub category: Option<String>,
+    pub priority: Option<u8>,
+    pub add_key_commits: Option<String>,
+    pub remove_key_commits: Option<String>,
+    pub add_related_commits: Option<String>,
+    pub remove_related_commits: Option<String>,
+}
+
+/// What happens to commits linked to a contribution when it is removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LinkedCommits {
+    /// Keep the commits as unassigned commits
+    Detach,
+    /// Delete the commits along with the contribution
+    Cascade,
+}
+
+/// Add hashes to a commit list, skipping ones already present
+fn add_commits(list: &mut Vec<String>, hashes: &str) {
+    for hash in parse_commit_list(hashes) {
+        if !list.contains(&hash) {
+            list.push(hash);
+        }
+    }
+}
+
+/// Remove hashes from a commit list; a hash also removes entries it is a prefix of.
+/// Returns the hashes that matched nothing.
+fn remove_commits(list: &mut Vec<String>, hashes: &str) -> Vec<String> {
+    let mut missing = Vec::new();
+    for hash in parse_commit_list(hashes) {
+        let before = list.len();
+        list.retain(|c| !c.starts_with(&hash));
+        if list.len() == before {
+            missing.push(hash);
+        }
+    }
+    missing
+}
+
+pub fn edit_command(repo_url: String, name: String, edit: ContributionEdit) -> Result<()> {
     let db = Database::open()?;
-    let repo_path = repo_path.unwrap_or_else(|| PathBuf::from("."));
+    let mut contrib = load_contribution(&db, &repo_url, &name)?;
+    let id = contrib.id.context("Contribution has no id")?;
 
-    println!("Extracting commit details from git repository...");
-    let commits = git::extract_commits_from_repo(&repo_path)?;
+    let original_commits = (contrib.key_commits.clone(), contrib.related_commits.clone());
 
-    println!("Found {} commits to process", commits.len());
+    if let Some(new_name) = edit.new_name {
+        contrib.name = new_name;
+    }
+    if let Some(overview) = edit.overview {
+        contrib.overview = overview;
+    }
+    if let Some(description) = edit.description {
+        contrib.description = description;
+    }
+    if let Some(category) = edit.category {
+        contrib.category = category;
+    }
+    if let Some(priority) = edit.priority {
+        contrib.priority = priority;
+    }
 
-    // Get all contributions to match commits
-    let repos = db.get_all_repositories()?;
-    let mut processed = 0;
+    let mut missing = Vec::new();
+    if let Some(hashes) = edit.remove_key_commits {
+        missing.extend(remove_commits(&mut contrib.key_commits, &hashes));
+    }
+    if let Some(hashes) = edit.remove_related_commits {
+        missing.extend(remove_commits(&mut contrib.related_commits, &hashes));
+    }
+    if let Some(hashes) = edit.add_key_commits {
+        add_commits(&mut contrib.key_commits, &hashes);
+    }
+    if let Some(hashes) = edit.add_related_commits {
+        add_commits(&mut contrib.related_commits, &hashes);
+    }
 
-    for commit in &commits {
-        // Try to find matching contribution by checking if commit hash is in key_commits or related_commits
-        let mut contrib_id = None;
-        for repo in &repos {
-            if repo.url != commit.repository_url {
-                continue;
-            }
-            let contribs = db.get_contributions(&repo.url)?;
-            for contrib in contribs {
-                if contrib.key_commits.iter().any(|c| commit.hash.starts_with(c)) ||
-                   contrib.related_commits.iter().any(|c| commit.hash.starts_with(c)) {
-                    if let Some(id) = contrib.id {
-                        contrib_id = Some(id);
-                        break;
-                    }
-                }
-            }
-            if contrib_id.is_some() {
-                break;
+    for hash in &missing {
+        println!("{} Commit '{}' is not listed on '{}'", "⚠".yellow(), hash, name);
+    }
+
+    db.update_contribution(&contrib)?;
+
+    // Re-derive commit links when the hash lists changed
+    if (contrib.key_commits.clone(), contrib.related_commits.clone()) != original_commits {
+        db.unlink_commits(id)?;
+        link_commits(&db, &repo_url, &LinkRules::load()?)?;
+    }
+
+    if contrib.name != name {
+        println!("{} Contribution '{}' renamed to '{}'", "✓".green(), name, contrib.name);
+    } else {
+        println!("{} Contribution '{}' updated successfully!", "✓".green(), name);
+    }
+    Ok(())
+}
+
+pub fn remove_command(repo_url: String, name: String, linked_commits: LinkedCommits) -> Result<()> {
+    let db = Database::open()?;
+    let id = db.get_contribution_id(&repo_url, &name)?
+        .with_context(|| format!("Contribution '{}' not found", name))?;
+
+    let (deleted, detached) = db.delete_contribution(id, linked_commits == LinkedCommits::Cascade)?;
+    // Detached commits go to any other contribution that lists or names them
+    link_commits(&db, &repo_url, &LinkRules::load()?)?;
+
+    println!("{} Contribution '{}' removed", "✓".green(), name);
+    match linked_commits {
+        LinkedCommits::Detach => println!("  {} linked commit(s) detached", detached),
+        LinkedCommits::Cascade => {
+            println!("  {} linked commit(s) deleted", deleted);
+            if detached > 0 {
+                println!("  {} commit(s) kept and relinked: another contribution lists them", detached);
             }
         }
-        
-        let mut commit_with_id = commit.clone();
-        commit_with_id.contribution_id = contrib_id;
+    }
+    Ok(())
+}
+
+/// Technical detail keys named by the `maintain_consistency` agent rule
+pub const SUGGESTED_DETAIL_KEYS: &[&str] = &[
+    "technology_stack",
+    "patterns",
+    "integrations",
+    "storage",
+    "security",
+];
+
+fn load_contribution(db: &Database, repo_url: &str, name: &str) -> Result<Contribution> {
+    db.get_contribution(repo_url, name)?
+        .with_context(|| format!("Contribution '{}' not found", name))
+}
+
+/// Convert a 1-based bullet number into an index into `bullets`
+fn bullet_index(bullets: &[String], number: usize) -> Result<usize> {
+    if number == 0 || number > bullets.len() {
+        return Err(anyhow::anyhow!(
+            "Bullet {} does not exist (contribution has {} bullet(s))",
+            number,
+            bullets.len()
+        ));
+    }
+    Ok(number - 1)
+}
+
+/// Reorder bullets by a comma-separated permutation of 1-based bullet numbers, e.g. "3,1,2"
+fn reorder_bullets(bullets: &[String], order: &str) -> Result<Vec<String>> {
+    let numbers = order
+        .split(',')
+        .map(|s| s.trim().parse::<usize>().with_context(|| format!("Invalid bullet number: '{}'", s.trim())))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut sorted = numbers.clone();
+    sorted.sort_unstable();
+    if sorted != (1..=bullets.len()).collect::<Vec<_>>() {
+        return Err(anyhow::anyhow!(
+            "Order must list each bullet number from 1 to {} exactly once",
+            bullets.len()
+        ));
+    }
+
+    Ok(numbers.into_iter().map(|n| bullets[n - 1].clone()).collect())
+}
+
+fn print_bullets(contrib: &Contribution) {
+    for (i, bullet) in contrib.resume_bullets.iter().enumerate() {
+        println!("  {}. {}", i + 1, bullet);
+    }
+}
+
+pub fn bullets_add_command(repo_url: String, name: String, text: String, position: Option<usize>) -> Result<()> {
+    let db = Database::open()?;
+    let mut contrib = load_contribution(&db, &repo_url, &name)?;
+
+    match position {
+        Some(number) if number == contrib.resume_bullets.len() + 1 => contrib.resume_bullets.push(text),
+        Some(number) => {
+            let index = bullet_index(&contrib.resume_bullets, number)?;
+            contrib.resume_bullets.insert(index, text);
+        }
+        None => contrib.resume_bullets.push(text),
+    }
+
+    db.update_contribution(&contrib)?;
+    println!("{} Resume bullet added to '{}'", "✓".green(), name);
+    print_bullets(&contrib);
+    Ok(())
+}
+
+pub fn bullets_remove_command(repo_url: String, name: String, number: usize) -> Result<()> {
+    let db = Database::open()?;
+    let mut contrib = load_contribution(&db, &repo_url, &name)?;
+
+    let index = bullet_index(&contrib.resume_bullets, number)?;
+    let removed = contrib.resume_bullets.remove(index);
+
+    db.update_contribution(&contrib)?;
+    println!("{} Removed bullet: {}", "✓".green(), removed);
+    print_bullets(&contrib);
+    Ok(())
+}
+
+pub fn bullets_reorder_command(repo_url: String, name: String, order: String) -> Result<()> {
+    let db = Database::open()?;
+    let mut contrib = load_contribution(&db, &repo_url, &name)?;
+
+    contrib.resume_bullets = reorder_bullets(&contrib.resume_bullets, &order)?;
+
+    db.update_contribution(&contrib)?;
+    println!("{} Resume bullets reordered for '{}'", "✓".green(), name);
+    print_bullets(&contrib);
+    Ok(())
+}
+
+pub fn details_set_command(repo_url: String, name: String, key: String, value: String) -> Result<()> {
+    let db = Database::open()?;
+    let mut contrib = load_contribution(&db, &repo_url, &name)?;
+
+    let value: serde_json::Value = serde_json::from_str(&value).with_context(|| {
+        format!(
+            "Value for '{}' is not valid JSON (quote plain strings, e.g. '\"Rust\"')",
+            key
+        )
+    })?;
+
+    if !SUGGESTED_DETAIL_KEYS.contains(&key.as_str()) {
+        println!("{} '{}' is not a standard technical detail key. Suggested keys: {}",
+                 "⚠".yellow(), key, SUGGESTED_DETAIL_KEYS.join(", "));
+    }
+
+    contrib.technical_details.insert(key.clone(), value);
+
+    db.update_contribution(&contrib)?;
+    println!("{} Technical detail '{}' set on '{}'", "✓".green(), key, name);
+    Ok(())
+}
+
+pub fn details_unset_command(repo_url: String, name: String, key: String) -> Result<()> {
+    let db = Database::open()?;
+    let mut contrib = load_contribution(&db, &repo_url, &name)?;
+
+    if contrib.technical_details.remove(&key).is_none() {
+        return Err(anyhow::anyhow!("Technical detail '{}' is not set on '{}'", key, name));
+    }
+
+    db.update_contribution(&contrib)?;
+    println!("{} Technical detail '{}' removed from '{}'", "✓".green(), key, name);
+    Ok(())
+}
+
+/// The registered repository a remote URL belongs to: one whose URL, or one of
+/// whose `aliases` in config.toml, normalizes to the same URL
+fn resolve_registered_url(repositories: &[Repository], config: &crate::config::Config, remote: &str) -> Option<String> {
+    use crate::utils::normalize_repo_url;
+
+    let wanted = normalize_repo_url(remote);
+    repositories
+        .iter()
+        .find(|repo| {
+            normalize_repo_url(&repo.url) == wanted
+                || config.repositories.get(&repo.url).is_some_and(|r| {
+                    r.aliases.iter().any(|alias| normalize_repo_url(alias) == wanted)
+                })
+        })
+        .map(|repo| repo.url.clone())
+}
+
+/// Commits stored per batch during `update`
+const COMMIT_BATCH: usize = 500;
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_command(
+    repo_path: Option<PathBuf>,
+    repo_url: Option<String>,
+    full: bool,
+    selection: git::RefSelection,
+    jobs: usize,
+    blame: bool,
+    range: DateRange,
+    bundle: Option<PathBuf>,
+) -> Result<()> {
+    use crate::config::Config;
+    use crate::utils::normalize_repo_url;
+
+    let db = Database::open()?;
+    // A bundle is read from a temporary repository, removed when `unpacked` drops
+    let unpacked = bundle.as_deref().map(git::unpack_bundle).transpose()?;
+    let repo_path = match &unpacked {
+        Some(unpacked) => unpacked.path.clone(),
+        None => repo_path.unwrap_or_else(|| PathBuf::from(".")),
+    };
+    // Every branch and tag in a bundle was put there to be read
+    let selection = match unpacked {
+        Some(_) => git::RefSelection { all_refs: true, ..selection },
+        None => selection,
+    };
+
+    let snapshot = git::snapshot_repo(&repo_path, &selection)?;
+
+    // Commits are stored under the registered repository URL, never an unregistered one
+    let remote = repo_url.or_else(|| snapshot.remote_url.clone()).with_context(|| {
+        format!("Repository at {:?} has no 'origin' remote; pass --repo-url to say which repository it is", repo_path)
+    })?;
+    let repositories = db.get_all_repositories()?;
+    let config = Config::load()?;
+    let repository_url = resolve_registered_url(&repositories, &config, &remote)
+        .with_context(|| {
+            format!(
+                "No registered repository matches '{}'. Register it with 'contrack init --repo-url {}', \
+                 add the URL to an existing repository's aliases in config.toml, or pass --repo-url",
+                remote,
+                normalize_repo_url(&remote)
+            )
+        })?;
+    println!("Repository: {}", repository_url);
+
+    // Rows stored under the raw origin URL before URLs were normalized belong here
+    let aliases = config.repositories.get(&repository_url).map(|r| r.aliases.as_slice()).unwrap_or_default();
+    for url in db.get_stored_urls()? {
+        let wanted = normalize_repo_url(&url);
+        let same = normalize_repo_url(&repository_url) == wanted
+            || aliases.iter().any(|alias| normalize_repo_url(alias) == wanted);
+        if same && url != repository_url {
+            let moved = db.move_repository_rows(&url, &repository_url)?;
+            println!("Moved {} commits and the contributions stored under {}", moved, url);
+        }
+    }
+
+    let mut options = git::ExtractOptions {
+        tips: snapshot.refs.values().cloned().collect(),
+        exclusions: ExclusionRules::load()?,
+        jobs,
+        range,
+        ..Default::default()
+    };
+
+    let previous = if full { None } else { db.get_sync_state(&repository_url)? };
+    if let Some(state) = &previous {
+        println!("Last synced at {} (HEAD {})", state.synced_at, short_hash(&state.head_oid));
+        options.hide.push(state.head_oid.clone());
+        options.hide.extend(state.refs.values().cloned());
+    }
+    if !full {
+        options.known_commits = db.get_commit_hashes(&repository_url)?;
+    }
+
+    let oids = git::commits_to_extract(&repo_path, &options)?;
+    println!("Found {} new commits to process", oids.len());
+
+    // Diffs are computed in parallel and stored in batches within one transaction
+    let tx = db.transaction()?;
+    let mut batch = Vec::with_capacity(COMMIT_BATCH);
+    let mut processed = 0;
+    git::extract_commits(&repo_path, &repository_url, &oids, &options, |commit| {
+        batch.push(commit);
+        if batch.len() == COMMIT_BATCH {
+            processed += batch.len();
+            db.add_commits(&batch)?;
+            batch.clear();
+            println!("Processed {}/{} commits...", processed, oids.len());
+        }
+        Ok(())
+    })?;
+    processed += batch.len();
+    db.add_commits(&batch)?;
+    tx.commit()?;
+
+    // Commits stored before committer times were recorded get both times and
+    // offsets; those the repository cannot resolve, such as imported patches, are skipped
+    let stale = db.get_commits_without_times(&repository_url)?;
+    if !stale.is_empty() {
+        let times = git::commit_times(&repo_path, &stale)?;
+        if !times.is_empty() {
+            db.set_commit_times(&times)?;
+            println!("Recorded author and committer times for {} earlier commits", times.len());
+        }
+    }
+
+    record_ref_tips(&db, &repository_url, &snapshot)?;
+    if unpacked.is_none() {
+        let checkout = std::fs::canonicalize(&repo_path).unwrap_or_else(|_| repo_path.clone());
+        db.set_local_path_if_unknown(&repository_url, &checkout.display().to_string())?;
+    }
+
+    if blame {
+        println!("Blaming files at HEAD...");
+        let cache = db.get_blame_cache(&repository_url)?;
+        let (files, blamed) = git::blame_head(&repo_path, &options.exclusions, &cache)?;
+        let lines = db.store_blame(&repository_url, &files)?;
+        println!("Blamed {} files ({} unchanged since the last run): {} lines at HEAD",
+                 files.len(), files.len() - blamed, lines);
+    }
+
+    // Attribute commits to the first release tag that contains them. Release
+    // history is walked again only when the release tags have changed.
+    let tags = git::release_tag_tips(&repo_path, &crate::release::load_tag_pattern()?)?;
+    let mut by_name = tags.clone();
+    by_name.sort();
+    let attributed = if full || by_name != db.get_release_tips(&repository_url)? {
+        let released = git::release_tags(&repo_path, &tags)?;
+        db.set_released_in(&repository_url, &tags, &released)?
+    } else {
+        let hashes: Vec<String> = oids.iter().map(|oid| oid.to_string()).collect();
+        db.add_released_in(&repository_url, &git::releases_containing(&repo_path, &tags, &hashes)?)?
+    };
+    if attributed > 0 {
+        println!("Updated release attribution for {} commits", attributed);
+    }
+
+    let report = link_stored_commits(&db, &repository_url)?;
+
+    // A date-limited update leaves history outside the range unread, so the
+    // next update must not treat it as synced
+    if range.is_bounded() {
+        println!("Sync state left unchanged: only commits authored in the given date range were read");
+    } else {
+        db.set_sync_state(&repository_url, &snapshot.head_oid, &snapshot.refs)?;
+    }
+
+    println!("{} Update complete: {} processed, {} linked ({} by hash, {} by trailer, {} by pattern)", 
+             "✓".green(), processed, report.total(), report.hash, report.trailer, report.regex);
+    Ok(())
+}
+
+/// Record the tips of HEAD and the walked refs. Only tips are recorded; which
+/// refs contain a commit is worked out from the checkout when commits are read.
+/// Tips from earlier updates that walked other refs are kept while those refs exist.
+fn record_ref_tips(db: &Database, repository_url: &str, snapshot: &git::RepoSnapshot) -> Result<()> {
+    let tips: Vec<(String, String)> = std::iter::once(("HEAD".to_string(), snapshot.head_oid.clone()))
+        .chain(snapshot.refs.iter().map(|(name, oid)| (name.clone(), oid.clone())))
+        .collect();
+    db.merge_ref_tips(repository_url, &tips, &snapshot.existing_refs)
+}
+
+/// Work shared by `update` and `import-patches` once new commits are stored:
+/// find reverts and repeated changes, then link commits to contributions
+fn link_stored_commits(db: &Database, repository_url: &str) -> Result<crate::linking::LinkReport> {
+    // Reverts are matched across the whole stored history, since a new commit
+    // can revert one stored long ago
+    let reverts = crate::revert::find_reverts(&db.get_patch_infos(repository_url)?);
+    let reverted = db.set_reverts(repository_url, &reverts)?;
+    if reverted > 0 {
+        println!("{} reverted commits earn no credit", reverted);
+    }
+    let equivalent = db.count_equivalent_commits(repository_url)?;
+    if equivalent > 0 {
+        println!("{} commits repeat a change made by another commit (same patch-id) and are counted once", equivalent);
+    }
+
+    // Link new commits, and older ones listed by contributions added since the last sync
+    let report = link_commits(db, repository_url, &LinkRules::load()?)?;
+    for name in &report.unknown_names {
+        println!("{} Commit messages name unknown contribution '{}'", "⚠".yellow(), name);
+    }
+    Ok(report)
+}
+
+/// Store the commits in a `git format-patch` mailbox, for history that is
+/// only available as patches (mailing-list projects, or exports from hosts
+/// that cannot be cloned)
+pub fn import_patches_command(mbox: PathBuf, repo_url: String, mailmap: Option<PathBuf>) -> Result<()> {
+    let db = Database::open()?;
+    let repository = db.get_repository(&repo_url)?
+        .with_context(|| format!("Repository not found: {}", repo_url))?;
+
+    // Names are mapped as `update` maps them: the given file, else the checkout's .mailmap
+    let mailmap = match mailmap {
+        Some(path) => Some(std::fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?),
+        None => match repository.local_path {
+            Some(path) => git::read_mailmap(&PathBuf::from(path)).ok().flatten(),
+            None => None,
+        },
+    };
+    let mailmap = mailmap.map(|text| git2::Mailmap::from_buffer(&text)).transpose()?;
+
+    let text = std::fs::read(&mbox).with_context(|| format!("Cannot read {:?}", mbox))?;
+    let patches = crate::mbox::parse_mbox(&String::from_utf8_lossy(&text))?;
+    let known = db.get_commit_hashes(&repo_url)?;
+    let exclusions = ExclusionRules::load()?;
+    let commits = patches
+        .iter()
+        .filter(|patch| !known.contains(&patch.hash))
+        .map(|patch| git::commit_from_patch(patch, &repo_url, mailmap.as_ref(), &exclusions))
+        .collect::<Result<Vec<_>>>()?;
+    println!("Found {} patches, {} of them new", patches.len(), commits.len());
+
+    let tx = db.transaction()?;
+    for batch in commits.chunks(COMMIT_BATCH) {
+        db.add_commits(batch)?;
+    }
+    tx.commit()?;
+
+    let report = link_stored_commits(&db, &repo_url)?;
+    println!("{} Import complete: {} stored, {} linked ({} by hash, {} by trailer, {} by pattern)",
+             "✓".green(), commits.len(), report.total(), report.hash, report.trailer, report.regex);
+    Ok(())
+}
+
+/// Options for `contrack suggest --accept`; `None` keeps the suggested value
+#[derive(Debug, Default)]
+pub struct AcceptSuggestion {
+    pub number: usize,
+    pub name: Option<String>,
+    pub category: Option<String>,
+    pub priority: Option<u8>,
+}
 
-        db.add_commit(&commit_with_id)?;
-        processed += 1;
+pub fn suggest_command(
+    repo_url: String,
+    repo_path: Option<PathBuf>,
+    window_days: i64,
+    min_commits: usize,
+    accept: Option<AcceptSuggestion>,
+) -> Result<()> {
+    use crate::suggest::{suggest, SuggestOptions};
+
+    let db = Database::open()?;
+    let commits = db.get_unassigned_commits(&repo_url)?;
+    if commits.is_empty() {
+        println!("{} No unassigned commits for {}; run 'contrack update' first", "⚠".yellow(), repo_url);
+        return Ok(());
+    }
+
+    // Merge ancestry needs the repository; without it, grouping uses paths, scopes and time only
+    let merge_groups = match repo_path {
+        Some(path) => git::merge_groups(&path)?,
+        None => Vec::new(),
+    };
+
+    let options = SuggestOptions {
+        window: chrono::Duration::days(window_days),
+        min_commits,
+        ..Default::default()
+    };
+    let suggestions = suggest(&commits, &merge_groups, &options);
+
+    let Some(accept) = accept else {
+        if suggestions.is_empty() {
+            println!("No suggestions: no group of {} or more unassigned commits", min_commits);
+            return Ok(());
+        }
 
-        if processed % 10 == 0 {
-            println!("Processed {} commits...", processed);
+        println!("\n{} Suggested contributions ({} unassigned commits)", "💡".blue(), commits.len());
+        println!("{}", "=".repeat(80));
+        for (i, suggestion) in suggestions.iter().enumerate() {
+            println!("\n{}. {} [{}]", i + 1, suggestion.name.bold(), suggestion.category);
+            println!("  Overview: {}", suggestion.overview);
+            println!("  {}", suggestion.description);
+            let short = |hashes: &[String]| hashes.iter().map(|h| short_hash(h).to_string()).collect::<Vec<_>>().join(", ");
+            println!("  Key Commits: {}", short(&suggestion.key_commits).yellow());
+            if !suggestion.related_commits.is_empty() {
+                println!("  Related Commits: {}", short(&suggestion.related_commits));
+            }
         }
+        println!("\nAccept one with: contrack suggest --repo-url {} --accept <NUMBER> [--name <NAME>]", repo_url);
+        return Ok(());
+    };
+
+    let suggestion = accept
+        .number
+        .checked_sub(1)
+        .and_then(|i| suggestions.get(i))
+        .with_context(|| format!("No suggestion number {} (there are {})", accept.number, suggestions.len()))?;
+
+    let name = accept.name.unwrap_or_else(|| suggestion.name.clone());
+    if db.get_contribution_id(&repo_url, &name)?.is_some() {
+        anyhow::bail!("Contribution '{}' already exists; pass --name to choose another name", name);
     }
 
-    println!("{} Update complete: {} processed", 
-             "✓".green(), processed);
+    let contrib = Contribution {
+        id: None,
+        repository_url: repo_url.clone(),
+        name: name.clone(),
+        overview: suggestion.overview.clone(),
+        description: suggestion.description.clone(),
+        key_commits: suggestion.key_commits.clone(),
+        related_commits: suggestion.related_commits.clone(),
+        technical_details: HashMap::new(),
+        resume_bullets: Vec::new(),
+        category: accept.category.unwrap_or_else(|| suggestion.category.clone()),
+        priority: accept.priority.unwrap_or(5),
+    };
+    db.add_contribution(&contrib)?;
+    let linked = link_commits(&db, &repo_url, &LinkRules::load()?)?.total();
+
+    println!("{} Contribution '{}' added from suggestion {} ({} commits linked)",
+             "✓".green(), name, accept.number, linked);
     Ok(())
 }
 
 pub fn generate_command(
     repo_url: String,
-    output: PathBuf,
+    output: Option<PathBuf>,
     author: Option<String>,
+    format: OutputFormat,
+    template: Option<String>,
+    range: DateRange,
+    include_reverts: bool,
 ) -> Result<()> {
     let db = Database::open()?;
+    let repository = db.get_repository(&repo_url)?;
     let contributions = db.get_contributions(&repo_url)?;
 
     if contributions.is_empty() {
@@ -164,31 +940,126 @@ pub fn generate_command(
         return Ok(());
     }
 
-    // Get commits for each contribution
+    // Get commits for each contribution; with a date range, contributions
+    // without commits authored in it are left out
+    let membership = ref_membership(&db, &repo_url)?;
     let mut contributions_with_commits = Vec::new();
     for contrib in &contributions {
-        let commits = db.get_commits_for_contribution(&repo_url, &contrib.name)?;
-        contributions_with_commits.push((contrib.clone(), commits));
+        let mut commits = db.get_commits_for_contribution_in(&repo_url, &contrib.name, &range)?;
+        if let Some(membership) = &membership {
+            membership.fill(&mut commits);
+        }
+        if range.is_bounded() && commits.is_empty() {
+            continue;
+        }
+        let mut contrib = contrib.clone();
+        // Reverted work and its reverts are left out unless asked for
+        if !include_reverts {
+            let reverted: Vec<String> = commits.iter().filter(|c| c.is_reverted()).map(|c| c.hash.clone()).collect();
+            let kept = |hash: &String| !reverted.iter().any(|r| r.starts_with(hash.as_str()));
+            contrib.key_commits.retain(kept);
+            contrib.related_commits.retain(kept);
+            commits.retain(|c| !c.is_reverted());
+        }
+        contributions_with_commits.push((contrib, commits));
     }
 
-    let markdown = markdown::generate_markdown(
-        &repo_url,
-        &contributions_with_commits,
-        author.as_deref(),
-    )?;
+    // --author matches every name and email of the identity it resolves to
+    let author_filter = author
+        .map(|query| db.resolve_author_filter(&query))
+        .transpose()?;
 
-    std::fs::write(&output, markdown)
+    let renderer: Box<dyn Renderer> = match template {
+        Some(name) => Box::new(TemplateRenderer::load(&name)?),
+        None => format.renderer(),
+    };
+    let document = renderer.render(&RenderInput {
+        repo_url: &repo_url,
+        repository: repository.as_ref(),
+        contributions: &contributions_with_commits,
+        author_filter: author_filter.as_ref(),
+    })?;
+
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("CONTRIBUTIONS.{}", renderer.extension())));
+    std::fs::write(&output, document)
         .with_context(|| format!("Failed to write to {:?}", output))?;
 
-    println!("{} Generated contributions markdown: {:?}", 
+    println!("{} Generated contributions document: {:?}", 
              "✓".green(), output);
-    println!("  {} contributions documented", contributions.len());
+    println!("  {} contributions documented", contributions_with_commits.len());
     Ok(())
 }
 
-pub fn query_contributions(repo_url: String) -> Result<()> {
+#[allow(clippy::too_many_arguments)]
+pub fn portfolio_command(
+    output: PathBuf,
+    org: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+    category: Option<String>,
+    min_priority: Option<u8>,
+    template: Option<String>,
+) -> Result<()> {
+    use crate::config::Config;
+    use crate::portfolio::{self, PortfolioFilter};
+    use crate::utils::{get_config_path, parse_date};
+
+    let filter = PortfolioFilter {
+        organization: org,
+        since: since.as_deref().map(|d| parse_date(d, false)).transpose()?,
+        until: until.as_deref().map(|d| parse_date(d, true)).transpose()?,
+        category,
+        min_priority,
+    };
+
     let db = Database::open()?;
-    let contributions = db.get_contributions(&repo_url)?;
+    let config_path = get_config_path()?;
+    let config = if config_path.exists() {
+        Config::from_toml(&config_path)?
+    } else {
+        Config::new()
+    };
+
+    let organizations = portfolio::collect(&db, &config, &filter)?;
+    if organizations.is_empty() {
+        println!("{} No contributions match the portfolio filters", "⚠".yellow());
+        return Ok(());
+    }
+
+    let renderer = match template {
+        Some(name) => TemplateRenderer::load_portfolio(&name)?,
+        None => TemplateRenderer::portfolio(),
+    };
+    let document = renderer.render_value(portfolio::build_context(&organizations, &filter))?;
+
+    std::fs::write(&output, document)
+        .with_context(|| format!("Failed to write to {:?}", output))?;
+
+    let repositories: usize = organizations.iter().map(|o| o.repositories.len()).sum();
+    let contributions: usize = organizations
+        .iter()
+        .flat_map(|o| &o.repositories)
+        .map(|r| r.contributions.len())
+        .sum();
+    println!("{} Generated portfolio: {:?}", "✓".green(), output);
+    println!("  {} contributions across {} repositories in {} organization(s)",
+             contributions, repositories, organizations.len());
+    Ok(())
+}
+
+pub fn query_contributions(repo_url: String, range: DateRange) -> Result<()> {
+    let db = Database::open()?;
+    let mut contributions = Vec::new();
+    for contrib in db.get_contributions(&repo_url)? {
+        // With a date range, only contributions with commits authored in it are listed
+        let in_range = match range.is_bounded() {
+            true => Some(db.get_commits_for_contribution_in(&repo_url, &contrib.name, &range)?.len()),
+            false => None,
+        };
+        if in_range != Some(0) {
+            contributions.push((contrib, in_range));
+        }
+    }
 
     if contributions.is_empty() {
         println!("No contributions found for repository: {}", repo_url);
@@ -198,11 +1069,14 @@ pub fn query_contributions(repo_url: String) -> Result<()> {
     println!("\n{} Contributions for {}", "📋".blue(), repo_url);
     println!("{}", "=".repeat(80));
 
-    for contrib in contributions {
+    for (contrib, in_range) in contributions {
         println!("\n{} {}", "•".green(), contrib.name.bold());
         println!("  Category: {} | Priority: {}", contrib.category, contrib.priority);
         println!("  Overview: {}", contrib.overview);
         println!("  Key Commits: {}", contrib.key_commits.len());
+        if let Some(count) = in_range {
+            println!("  Commits in range: {}", count);
+        }
     }
 
     Ok(())
@@ -217,6 +1091,25 @@ pub fn query_contribution(repo_url: String, name: String) -> Result<()> {
     println!("{}", "=".repeat(80));
     println!("Repository: {}", contrib.repository_url);
     println!("Category: {} | Priority: {}", contrib.category, contrib.priority);
+    let (reverted, commits): (Vec<_>, Vec<_>) = db
+        .get_commits_for_contribution(&repo_url, &name)?
+        .into_iter()
+        .partition(|c| c.is_reverted());
+    if !commits.is_empty() {
+        let totals = LineTotals::of(&commits);
+        print!("Changes: +{} -{} across {} commits", totals.lines_added.to_string().green(),
+               totals.lines_deleted.to_string().red(), commits.len());
+        if totals.changes < commits.len() {
+            print!(" ({} distinct changes)", totals.changes);
+        }
+        match totals.surviving_lines {
+            Some(lines) => println!(" · {} lines still in production", lines),
+            None => println!(),
+        }
+    }
+    if !reverted.is_empty() {
+        println!("Reverted: {} commits (reverts included) left out of the totals", reverted.len());
+    }
     println!("\nOverview:\n{}", contrib.overview);
     println!("\nDescription:\n{}", contrib.description);
 
@@ -254,9 +1147,18 @@ pub fn query_contribution(repo_url: String, name: String) -> Result<()> {
     Ok(())
 }
 
-pub fn query_commits(repo_url: String, name: String) -> Result<()> {
+/// Which walked refs contain each commit, worked out from the repository's checkout
+fn ref_membership(db: &Database, repo_url: &str) -> Result<Option<git::RefMembership>> {
+    let local_path = db.get_repository(repo_url)?.and_then(|r| r.local_path);
+    Ok(git::RefMembership::for_checkout(local_path.as_deref(), &db.get_ref_tips(repo_url)?))
+}
+
+pub fn query_commits(repo_url: String, name: String, range: DateRange) -> Result<()> {
     let db = Database::open()?;
-    let commits = db.get_commits_for_contribution(&repo_url, &name)?;
+    let mut commits = db.get_commits_for_contribution_in(&repo_url, &name, &range)?;
+    if let Some(membership) = ref_membership(&db, &repo_url)? {
+        membership.fill(&mut commits);
+    }
 
     if commits.is_empty() {
         println!("No commits found for contribution '{}'", name);
@@ -266,19 +1168,109 @@ pub fn query_commits(repo_url: String, name: String) -> Result<()> {
     println!("\n{} Commits for '{}'", "🔍".blue(), name.bold());
     println!("{}", "=".repeat(80));
 
-    for commit in commits {
-        println!("\n{} {}", "•".green(), commit.hash[..8].yellow());
+    for commit in &commits {
+        println!("\n{} {}", "•".green(), short_hash(&commit.hash).yellow());
         println!("  Author: {} <{}>", commit.author, commit.author_email);
-        println!("  Date: {}", commit.date);
+        let co_authors: Vec<String> = commit
+            .authors
+            .iter()
+            .filter(|a| a.role == crate::database::AuthorRole::CoAuthor)
+            .map(|a| format!("{} <{}>", a.name, a.email))
+            .collect();
+        if !co_authors.is_empty() {
+            println!("  Co-authors: {}", co_authors.join(", "));
+        }
+        println!("  Date: {}", commit.author_date());
+        if let Some(committed) = commit.committer_date().filter(|d| *d != commit.author_date()) {
+            println!("  Committed: {}", committed);
+        }
+        if !commit.refs.is_empty() {
+            let refs: Vec<&str> = commit.refs.iter().map(|r| git::short_ref_name(r)).collect();
+            println!("  Refs: {}", refs.join(", "));
+        }
+        if let Some(role) = commit.role {
+            println!("  Listed as: {} commit", role.as_str());
+        }
+        if let Some(source) = &commit.link_source {
+            println!("  Linked by: {}", source);
+        }
+        if let Some(release) = &commit.released_in {
+            println!("  Released in: {}", release);
+        }
+        if let Some(revert) = &commit.reverted_by {
+            println!("  {} by {}", "Reverted".red(), short_hash(revert));
+        }
+        if let Some(target) = &commit.reverts {
+            println!("  {} {}", "Reverts".red(), short_hash(target));
+        }
+        let equivalents: Vec<&str> = commits
+            .iter()
+            .filter(|c| c.hash != commit.hash && c.patch_id.is_some() && c.patch_id == commit.patch_id)
+            .map(|c| short_hash(&c.hash))
+            .collect();
+        if !equivalents.is_empty() {
+            println!("  Same change as: {}", equivalents.join(", "));
+        }
         println!("  Message: {}", commit.message);
         if let (Some(added), Some(deleted)) = (commit.lines_added, commit.lines_deleted) {
-            println!("  Changes: +{} -{}", added.to_string().green(), deleted.to_string().red());
+            match (commit.meaningful_lines_added, commit.meaningful_lines_deleted) {
+                (Some(m_added), Some(m_deleted)) if (m_added, m_deleted) != (added, deleted) => println!(
+                    "  Changes: +{} -{} (raw +{} -{})",
+                    m_added.to_string().green(),
+                    m_deleted.to_string().red(),
+                    added,
+                    deleted
+                ),
+                _ => println!("  Changes: +{} -{}", added.to_string().green(), deleted.to_string().red()),
+            }
+        }
+        if !commit.files.is_empty() {
+            println!("  Files:");
+            for file in &commit.files {
+                print_commit_file(file);
+            }
         }
     }
 
     Ok(())
 }
 
+fn print_commit_file(file: &crate::database::CommitFile) {
+    use crate::database::FileStatus;
+
+    let path = match (file.status, &file.old_path, &file.new_path) {
+        (FileStatus::Renamed | FileStatus::Copied, Some(old), Some(new)) => format!("{} → {}", old, new),
+        _ => file.path().to_string(),
+    };
+    let stats = if file.binary {
+        "binary".to_string()
+    } else {
+        format!("+{} -{}", file.lines_added, file.lines_deleted)
+    };
+    let excluded = if file.excluded { " [excluded]".dimmed().to_string() } else { String::new() };
+    println!("    {} {} ({}){}", file.status.as_str().yellow(), path, stats, excluded);
+}
+
+pub fn query_files(repo_url: String, name: String) -> Result<()> {
+    let db = Database::open()?;
+    let churn = db.get_contribution_file_churn(&repo_url, &name)?;
+
+    if churn.is_empty() {
+        println!("No file changes recorded for contribution '{}'", name);
+        println!("Commits stored before per-file tracking need 'contrack update --full'");
+        return Ok(());
+    }
+
+    println!("\n{} Files changed by '{}'", "📁".blue(), name.bold());
+    println!("{}", "=".repeat(80));
+    for file in churn {
+        println!("  {} +{} -{} ({} commits)", file.path, file.lines_added.to_string().green(),
+                 file.lines_deleted.to_string().red(), file.commits);
+    }
+
+    Ok(())
+}
+
 pub fn query_stats() -> Result<()> {
     let db = Database::open()?;
     let stats = db.get_statistics()?;
@@ -287,7 +1279,8 @@ pub fn query_stats() -> Result<()> {
     println!("{}", "=".repeat(80));
     println!("Repositories: {}", stats.get("repositories").unwrap_or(&0));
     println!("Contributions: {}", stats.get("contributions").unwrap_or(&0));
-    println!("Commits: {}", stats.get("commits").unwrap_or(&0));
+    println!("Commits: {} ({} distinct changes)", stats.get("commits").unwrap_or(&0), stats.get("distinct_changes").unwrap_or(&0));
+    println!("Reverted Commits: {} ({} reverts)", stats.get("reverted_commits").unwrap_or(&0), stats.get("reverts").unwrap_or(&0));
     println!("Agent Rules: {}", stats.get("agent_rules").unwrap_or(&0));
     println!("Prompts: {}", stats.get("prompts").unwrap_or(&0));
 
@@ -313,6 +1306,9 @@ pub fn list_repositories(detailed: bool) -> Result<()> {
         if let Some(desc) = repo.description {
             println!("  Description: {}", desc);
         }
+        if let Some(path) = repo.local_path {
+            println!("  Path: {}", path);
+        }
 
         if detailed {
             let contribs = db.get_contributions(&repo.url)?;
@@ -379,25 +1375,26 @@ pub fn locations_command() -> Result<()> {
     Ok(())
 }
 
-#[cfg(test)]
-mod locations_tests {
-    use super::*;
-
-    #[test]
-    fn test_locations_command() {
-        // Test that the command doesn't panic and returns Ok
-        let result = locations_command();
-        assert!(result.is_ok());
-    }
-}
-
 pub fn config_sync_command() -> Result<()> {
     use crate::utils::get_config_path;
 
     let db = Database::open()?;
-    let config = db.load_config_from_db()?;
+    let mut config = db.load_config_from_db()?;
     let config_path = get_config_path()?;
-    
+
+    // Settings that only live in the file are kept
+    if config_path.exists() {
+        let existing = crate::config::Config::from_toml(&config_path)?;
+        config.linking = existing.linking;
+        config.stats = existing.stats;
+        config.releases = existing.releases;
+        for (url, repo) in existing.repositories {
+            if let Some(synced) = config.repositories.get_mut(&url) {
+                synced.aliases = repo.aliases;
+            }
+        }
+    }
+
     config.to_toml(&config_path)?;
     println!("{} Configuration synced to: {}", "✓".green(), config_path.display());
     Ok(())
@@ -452,7 +1449,13 @@ pub fn config_add_org_command(id: String, name: String, description: Option<Stri
     Ok(())
 }
 
-pub fn config_add_repo_command(url: String, org: String, name: String, description: Option<String>) -> Result<()> {
+pub fn config_add_repo_command(
+    url: String,
+    org: String,
+    name: String,
+    description: Option<String>,
+    aliases: Vec<String>,
+) -> Result<()> {
     use crate::config::{Config, RepositoryConfig};
     use crate::database::Repository;
     use crate::utils::get_config_path;
@@ -464,13 +1467,16 @@ pub fn config_add_repo_command(url: String, org: String, name: String, descripti
         Config::new()
     };
     
-    // Add repository to config
+    // Add repository to config, keeping any recorded checkout path
+    let path = config.repositories.get(&url).and_then(|r| r.path.clone());
     config.repositories.insert(
         url.clone(),
         RepositoryConfig {
             organization: org.clone(),
             name: name.clone(),
             description: description.clone(),
+            aliases,
+            path,
         },
     );
     
@@ -484,6 +1490,7 @@ pub fn config_add_repo_command(url: String, org: String, name: String, descripti
         organization: org,
         name,
         description,
+        local_path: None,
     };
     db.add_repository(&repo)?;
     
@@ -559,6 +1566,115 @@ pub fn loadout_reload_default_command() -> Result<()> {
     Ok(())
 }
 
+pub fn identity_add_command(name: String, emails: Vec<String>, names: Vec<String>) -> Result<()> {
+    let db = Database::open()?;
+    let id = db.add_identity(&name)?;
+    for email in &emails {
+        db.add_identity_alias(id, None, Some(email))?;
+    }
+    for alias in &names {
+        db.add_identity_alias(id, Some(alias), None)?;
+    }
+    println!("{} Identity '{}' saved", "✓".green(), name);
+    Ok(())
+}
+
+pub fn identity_alias_command(identity: String, name: Option<String>, email: Option<String>) -> Result<()> {
+    if name.is_none() && email.is_none() {
+        anyhow::bail!("Provide --name and/or --email for the alias");
+    }
+    let db = Database::open()?;
+    let found = db.find_identity(&identity)?
+        .with_context(|| format!("Identity '{}' not found", identity))?;
+
+    if db.add_identity_alias(found.id, name.as_deref(), email.as_deref())? {
+        println!("{} Alias added to '{}'", "✓".green(), found.name);
+    } else {
+        println!("{} '{}' already has that alias", "⚠".yellow(), found.name);
+    }
+    Ok(())
+}
+
+pub fn identity_list_command() -> Result<()> {
+    let db = Database::open()?;
+    let identities = db.get_identities()?;
+
+    if identities.is_empty() {
+        println!("No identities found. Add one with 'contrack identity add' or 'contrack identity import-mailmap'");
+        return Ok(());
+    }
+
+    println!("\n{} Identities", "👤".blue());
+    println!("{}", "=".repeat(80));
+    for identity in identities {
+        println!("\n{} {}", "•".green(), identity.name.bold());
+        for alias in identity.aliases {
+            match (alias.name, alias.email) {
+                (Some(name), Some(email)) => println!("  {} <{}>", name, email),
+                (Some(name), None) => println!("  {}", name),
+                (None, Some(email)) => println!("  <{}>", email),
+                (None, None) => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn identity_import_mailmap_command(repo_path: Option<PathBuf>, file: Option<PathBuf>) -> Result<()> {
+    use crate::identity::{import_mailmap, parse_mailmap};
+
+    let content = match file {
+        Some(path) => std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {:?}", path))?,
+        None => {
+            let repo_path = repo_path.unwrap_or_else(|| PathBuf::from("."));
+            git::read_mailmap(&repo_path)?
+                .with_context(|| format!("No .mailmap found in {:?}", repo_path))?
+        }
+    };
+
+    let entries = parse_mailmap(&content);
+    let db = Database::open()?;
+    let summary = import_mailmap(&db, &entries)?;
+
+    println!("{} Imported {} mailmap entries: {} new identities, {} new aliases",
+             "✓".green(), entries.len(), summary.identities, summary.aliases);
+    Ok(())
+}
+
+pub fn db_migrate_command(dry_run: bool) -> Result<()> {
+    use crate::migrations::latest_version;
+    use crate::utils::get_database_path;
+
+    let db_path = get_database_path()?;
+    let db = Database::open_without_migrating(&db_path)?;
+    let current = db.schema_version()?;
+    let pending = db.pending_migrations()?;
+
+    println!("Database: {}", db_path.display());
+    println!("Schema version: {} (latest: {})", current, latest_version());
+
+    if pending.is_empty() {
+        println!("{} Database schema is up to date", "✓".green());
+        return Ok(());
+    }
+
+    println!("\nPending migrations:");
+    for migration in &pending {
+        println!("  {} {}", format!("v{}", migration.version).yellow(), migration.description);
+    }
+
+    if dry_run {
+        println!("\n{} Dry run: no changes applied", "⚠".yellow());
+        return Ok(());
+    }
+
+    let applied = db.migrate()?;
+    println!("\n{} Applied {} migration(s); schema is now at version {}", 
+             "✓".green(), applied.len(), db.schema_version()?);
+    Ok(())
+}
+
 pub fn ai_command() -> Result<()> {
     let db = Database::open()?;
     
@@ -581,14 +1697,25 @@ pub fn ai_command() -> Result<()> {
     println!("AVAILABLE COMMANDS:");
     println!("-------------------");
     println!("  contrack init          - Initialize a new repository");
+    println!("  contrack discover      - Register every git repository under a directory");
     println!("  contrack add           - Add a new contribution");
-    println!("  contrack update        - Update commit details from git");
+    println!("  contrack edit          - Edit a contribution in place");
+    println!("  contrack remove        - Remove a contribution");
+    println!("  contrack bullets       - Manage resume bullets (add, remove, reorder)");
+    println!("  contrack details       - Manage technical details (set, unset)");
+    println!("  contrack update        - Update commit details from git (or a bundle with --bundle)");
+    println!("  contrack import-patches - Store commits from a git format-patch mailbox");
+    println!("  contrack repair        - Remap key and related commits rewritten by a rebase");
+    println!("  contrack suggest       - Suggest contributions from unassigned commits");
     println!("  contrack generate      - Generate contributions markdown file");
+    println!("  contrack portfolio     - Generate one portfolio across all repositories");
     println!("  contrack query         - Query the database (contributions, commits, stats)");
     println!("  contrack list          - List repositories");
     println!("  contrack locations     - List all database locations");
     println!("  contrack config        - Manage configuration file");
     println!("  contrack loadout       - Manage prompt and rule loadouts");
+    println!("  contrack identity      - Map author names and emails to one person (mailmap import)");
+    println!("  contrack db            - Manage the database schema (migrations)");
     println!("  contrack ai            - Show this AI configuration prompt\n");
     
     // Agent rules
@@ -641,8 +1768,117 @@ pub fn ai_command() -> Result<()> {
 }
 
 #[cfg(test)]
-mod ai_tests {
+mod tests {
     use super::*;
+    use crate::config::{Config, RepositoryConfig};
+    use tempfile::TempDir;
+
+    fn bullets(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_reorder_bullets() {
+        let reordered = reorder_bullets(&bullets(&["a", "b", "c"]), "3, 1,2").unwrap();
+        assert_eq!(reordered, bullets(&["c", "a", "b"]));
+    }
+
+    #[test]
+    fn test_reorder_bullets_rejects_invalid_orders() {
+        let items = bullets(&["a", "b", "c"]);
+        assert!(reorder_bullets(&items, "1,2").is_err());
+        assert!(reorder_bullets(&items, "1,1,2").is_err());
+        assert!(reorder_bullets(&items, "1,2,4").is_err());
+        assert!(reorder_bullets(&items, "1,x,2").is_err());
+    }
+
+    #[test]
+    fn test_bullet_index_is_one_based() {
+        let items = bullets(&["a", "b"]);
+        assert_eq!(bullet_index(&items, 2).unwrap(), 1);
+        assert!(bullet_index(&items, 0).is_err());
+        assert!(bullet_index(&items, 3).is_err());
+    }
+
+    fn repository(url: &str) -> Repository {
+        Repository {
+            url: url.to_string(),
+            organization: "org".to_string(),
+            name: "repo".to_string(),
+            description: None,
+            local_path: None,
+        }
+    }
+
+    #[test]
+    fn test_plain_update_keeps_tips_of_branches_walked_earlier() {
+        let dir = TempDir::new().unwrap();
+        let repo = git2::Repository::init(dir.path().join("checkout")).unwrap();
+        let signature = git2::Signature::now("Ann", "ann@example.com").unwrap();
+        let tree = repo.find_tree(repo.treebuilder(None).unwrap().write().unwrap()).unwrap();
+        let base = repo.commit(Some("HEAD"), &signature, &signature, "base", &tree, &[]).unwrap();
+        let base = repo.find_commit(base).unwrap();
+        let feature = repo
+            .commit(Some("refs/heads/feature/x"), &signature, &signature, "feature work", &tree, &[&base])
+            .unwrap()
+            .to_string();
+
+        let url = "https://github.com/org/repo";
+        let db = Database::open_at(&dir.path().join("contributions.db")).unwrap();
+        db.add_repository(&repository(url)).unwrap();
+        let path = dir.path().join("checkout");
+        let feature_tip = ("refs/heads/feature/x".to_string(), feature);
+
+        let selection = git::RefSelection {
+            branches: vec!["feature/*".to_string()],
+            ..Default::default()
+        };
+        record_ref_tips(&db, url, &git::snapshot_repo(&path, &selection).unwrap()).unwrap();
+        assert!(db.get_ref_tips(url).unwrap().contains(&feature_tip));
+
+        // A plain update walks only HEAD but the feature branch still exists
+        let plain = git::RefSelection::default();
+        record_ref_tips(&db, url, &git::snapshot_repo(&path, &plain).unwrap()).unwrap();
+        assert!(db.get_ref_tips(url).unwrap().contains(&feature_tip));
+
+        repo.find_reference("refs/heads/feature/x").unwrap().delete().unwrap();
+        record_ref_tips(&db, url, &git::snapshot_repo(&path, &plain).unwrap()).unwrap();
+        let tips = db.get_ref_tips(url).unwrap();
+        assert_eq!(tips.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), vec!["HEAD"]);
+    }
+
+    #[test]
+    fn test_resolve_registered_url() {
+        let repositories = vec![repository("https://github.com/org/repo"), repository("https://github.com/org/app")];
+        let mut config = Config::new();
+        config.repositories.insert(
+            "https://github.com/org/app".to_string(),
+            RepositoryConfig {
+                organization: "org".to_string(),
+                name: "app".to_string(),
+                description: None,
+                aliases: vec!["git@gitlab.example.com:mirror/app.git".to_string()],
+                path: None,
+            },
+        );
+
+        assert_eq!(
+            resolve_registered_url(&repositories, &config, "git@github.com:Org/repo.git").as_deref(),
+            Some("https://github.com/org/repo")
+        );
+        assert_eq!(
+            resolve_registered_url(&repositories, &config, "https://gitlab.example.com/mirror/app/").as_deref(),
+            Some("https://github.com/org/app")
+        );
+        assert_eq!(resolve_registered_url(&repositories, &config, "git@github.com:org/other.git"), None);
+    }
+
+    #[test]
+    fn test_locations_command() {
+        // Test that the command doesn't panic and returns Ok
+        let result = locations_command();
+        assert!(result.is_ok());
+    }
 
     #[test]
     fn test_ai_command() {
@@ -651,4 +1887,3 @@ mod ai_tests {
         assert!(result.is_ok());
     }
 }
-
@@ -14,6 +14,87 @@ pub struct RepositoryConfig {
     pub organization: String,
     pub name: String,
     pub description: Option<String>,
+    /// Other remote URLs for this repository (mirrors, old names), matched by `update`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Local checkout, recorded by `discover`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// How commits link themselves to contributions by name, in addition to the
+/// hash lists on each contribution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkingConfig {
+    /// Trailer key naming a contribution, e.g. `Contribution: Sync Engine`.
+    /// An empty string disables trailer linking.
+    #[serde(default = "default_trailer")]
+    pub trailer: String,
+    /// Regexes matched against commit messages. The `name` capture group (or the
+    /// first group) is the contribution name, e.g. `\[CT-(?P<name>[^\]]+)\]`.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+fn default_trailer() -> String {
+    "Contribution".to_string()
+}
+
+impl Default for LinkingConfig {
+    fn default() -> Self {
+        Self {
+            trailer: default_trailer(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// Which changes count toward "meaningful" line statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsConfig {
+    /// Gitignore-style globs for paths to leave out, e.g. `vendor/` or `*.pb.go`
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Leave out well-known lockfiles such as Cargo.lock and package-lock.json
+    #[serde(default = "default_true")]
+    pub builtin_lockfiles: bool,
+    /// Leave out files marked `linguist-generated` or `linguist-vendored` in .gitattributes
+    #[serde(default = "default_true")]
+    pub gitattributes: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            exclude: Vec::new(),
+            builtin_lockfiles: true,
+            gitattributes: true,
+        }
+    }
+}
+
+/// Which tags mark releases, for "first released in" attribution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleasesConfig {
+    /// Regex matched against tag names, e.g. `^v\d+\.\d+` for `v2.3`
+    #[serde(default = "default_tag_pattern")]
+    pub tag_pattern: String,
+}
+
+fn default_tag_pattern() -> String {
+    r"^v?\d+(\.\d+)*$".to_string()
+}
+
+impl Default for ReleasesConfig {
+    fn default() -> Self {
+        Self {
+            tag_pattern: default_tag_pattern(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +103,12 @@ pub struct Config {
     pub organizations: HashMap<String, Organization>,
     #[serde(default)]
     pub repositories: HashMap<String, RepositoryConfig>,
+    #[serde(default)]
+    pub linking: LinkingConfig,
+    #[serde(default)]
+    pub stats: StatsConfig,
+    #[serde(default)]
+    pub releases: ReleasesConfig,
 }
 
 impl Config {
@@ -29,6 +116,19 @@ impl Config {
         Self {
             organizations: HashMap::new(),
             repositories: HashMap::new(),
+            linking: LinkingConfig::default(),
+            stats: StatsConfig::default(),
+            releases: ReleasesConfig::default(),
+        }
+    }
+
+    /// Load config.toml, or an empty config if it does not exist yet
+    pub fn load() -> Result<Self> {
+        let path = crate::utils::get_config_path()?;
+        if path.exists() {
+            Self::from_toml(&path)
+        } else {
+            Ok(Self::new())
         }
     }
 
@@ -91,6 +191,8 @@ mod tests {
                 organization: "org1".to_string(),
                 name: "repo1".to_string(),
                 description: Some("Test repo".to_string()),
+                aliases: vec!["git@github.com:org1/old-name.git".to_string()],
+                path: None,
             },
         );
 
@@ -106,6 +208,7 @@ mod tests {
         assert_eq!(loaded.repositories.len(), 1);
         assert_eq!(loaded.organizations.get("org1").unwrap().name, "Organization 1");
         assert_eq!(loaded.repositories.get("https://github.com/org1/repo1").unwrap().name, "repo1");
+        assert_eq!(loaded.repositories.get("https://github.com/org1/repo1").unwrap().aliases.len(), 1);
     }
 
     #[test]
@@ -118,6 +221,14 @@ mod tests {
         let loaded = Config::from_toml(&path).unwrap();
         assert!(loaded.organizations.is_empty());
         assert!(loaded.repositories.is_empty());
+        assert_eq!(loaded.linking.trailer, "Contribution");
+    }
+
+    #[test]
+    fn test_linking_config_defaults_when_omitted() {
+        let config: Config = toml::from_str("[linking]\npatterns = ['\\[CT-(?P<name>[^\\]]+)\\]']\n").unwrap();
+        assert_eq!(config.linking.trailer, "Contribution");
+        assert_eq!(config.linking.patterns.len(), 1);
     }
 }
 
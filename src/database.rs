@@ -1,8 +1,14 @@
 use anyhow::{Context, Result};
 use chrono::Utc;
 use rusqlite::{params, Connection};
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
+use crate::utils::DateRange;
+
+use crate::identity::AuthorFilter;
+use crate::migrations::{Migration, MIGRATIONS};
 use crate::utils::get_database_path;
 
 type AgentRule = (String, String, i32, Option<String>);
@@ -12,15 +18,17 @@ pub struct Database {
     conn: Connection,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Repository {
     pub url: String,
     pub organization: String,
     pub name: String,
     pub description: Option<String>,
+    /// Where the repository is checked out, when registered by `discover`
+    pub local_path: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Contribution {
     pub id: Option<i64>,
     pub repository_url: String,
@@ -35,166 +43,550 @@ pub struct Contribution {
     pub priority: u8,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Commit {
     pub hash: String,
     pub repository_url: String,
     pub contribution_id: Option<i64>,
     pub author: String,
     pub author_email: String,
+    /// Author time in UTC, RFC 3339
     pub date: String,
+    /// Author and committer times in epoch seconds, with the UTC offset (in
+    /// minutes) they were recorded in; `None` for commits stored before they were
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_offset: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub committer_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub committer_offset: Option<i32>,
     pub message: String,
     pub files_changed: Vec<String>,
     pub lines_added: Option<i32>,
     pub lines_deleted: Option<i32>,
+    /// Line counts without excluded files (generated, vendored, lockfiles);
+    /// `None` for commits stored before they were recorded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meaningful_lines_added: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meaningful_lines_deleted: Option<i32>,
+    /// How the commit was linked to its contribution: each `LinkSource` that
+    /// applies, joined by ", "
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link_source: Option<String>,
+    /// The earliest release tag that contains the commit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub released_in: Option<String>,
+    /// Lines at HEAD the commit still owns, per the last `update --blame`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub surviving_lines: Option<i64>,
+    /// Patch-ids of the commit's diff against its first parent and of the
+    /// inverse diff; `None` for merges, empty commits and commits stored before
+    /// they were recorded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patch_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inverse_patch_id: Option<String>,
+    /// The commit this one reverts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reverts: Option<String>,
+    /// The revert that undid this commit, when that revert still stands
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reverted_by: Option<String>,
+    /// Whether the contribution it was loaded for lists it (or an equivalent
+    /// commit) as a key or related commit; `None` when only linked by message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<CommitRole>,
+    /// Walked refs that contain the commit, e.g. `HEAD` or `refs/heads/main`;
+    /// filled from the checkout by `git::RefMembership`, not stored
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub refs: Vec<String>,
+    /// Per-file changes; empty for commits stored before they were recorded
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<CommitFile>,
+    /// Everyone named on the commit: its author, `Co-authored-by` trailers and committer
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<CommitAuthor>,
+}
+
+/// How a person is named on a commit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthorRole {
+    /// The git author signature
+    Author,
+    /// A `Co-authored-by` trailer
+    CoAuthor,
+    /// The git committer signature
+    Committer,
+}
+
+impl AuthorRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthorRole::Author => "author",
+            AuthorRole::CoAuthor => "co-author",
+            AuthorRole::Committer => "committer",
+        }
+    }
+
+    fn from_str(role: &str) -> Self {
+        match role {
+            "co-author" => AuthorRole::CoAuthor,
+            "committer" => AuthorRole::Committer,
+            _ => AuthorRole::Author,
+        }
+    }
+
+    /// Authors and co-authors get credit for a commit; committers do not
+    pub fn is_credited(&self) -> bool {
+        !matches!(self, AuthorRole::Committer)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommitAuthor {
+    pub name: String,
+    pub email: String,
+    pub role: AuthorRole,
+}
+
+/// How a file changed in a commit, as in `git diff --name-status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FileStatus {
+    #[serde(rename = "A")]
+    Added,
+    #[serde(rename = "M")]
+    Modified,
+    #[serde(rename = "D")]
+    Deleted,
+    #[serde(rename = "R")]
+    Renamed,
+    #[serde(rename = "C")]
+    Copied,
+}
+
+impl FileStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileStatus::Added => "A",
+            FileStatus::Modified => "M",
+            FileStatus::Deleted => "D",
+            FileStatus::Renamed => "R",
+            FileStatus::Copied => "C",
+        }
+    }
+
+    fn from_code(code: &str) -> Self {
+        match code {
+            "A" => FileStatus::Added,
+            "D" => FileStatus::Deleted,
+            "R" => FileStatus::Renamed,
+            "C" => FileStatus::Copied,
+            _ => FileStatus::Modified,
+        }
+    }
+}
+
+/// One file changed by a commit. Added files have no old path and deleted
+/// files no new path.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitFile {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub status: FileStatus,
+    pub lines_added: i32,
+    pub lines_deleted: i32,
+    pub binary: bool,
+    /// Left out of meaningful line counts (see `ExclusionRules`)
+    pub excluded: bool,
+}
+
+impl CommitFile {
+    /// The path the file has after the commit, or had before it was deleted
+    pub fn path(&self) -> &str {
+        self.new_path.as_deref().or(self.old_path.as_deref()).unwrap_or("")
+    }
+}
+
+/// Author and committer times in epoch seconds, with their UTC offsets in minutes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitTimes {
+    pub author_time: i64,
+    pub author_offset: i32,
+    pub committer_time: i64,
+    pub committer_offset: i32,
+}
+
+/// RFC 3339 time at the given UTC offset in minutes
+fn local_date(time: Option<i64>, offset: Option<i32>) -> Option<String> {
+    let offset = chrono::FixedOffset::east_opt(offset? * 60)?;
+    Some(chrono::DateTime::from_timestamp(time?, 0)?.with_timezone(&offset).to_rfc3339())
+}
+
+/// Meaningful lines added and deleted across a set of commits, and how many
+/// of their lines survive at HEAD (`None` until `update --blame` has run)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineTotals {
+    pub lines_added: i64,
+    pub lines_deleted: i64,
+    pub surviving_lines: Option<i64>,
+    /// Logical changes counted: commits, with equivalent ones counted once
+    pub changes: usize,
+}
+
+impl LineTotals {
+    /// Totals over distinct changes, so a cherry-picked or upstreamed patch
+    /// is only counted once (see `distinct_changes`)
+    pub fn of<'a>(commits: impl IntoIterator<Item = &'a Commit>) -> Self {
+        let mut totals = Self::default();
+        for commit in distinct_changes(commits) {
+            totals.changes += 1;
+            let (added, deleted) = commit.meaningful_lines();
+            totals.lines_added += i64::from(added.unwrap_or(0));
+            totals.lines_deleted += i64::from(deleted.unwrap_or(0));
+            if let Some(surviving) = commit.surviving_lines {
+                *totals.surviving_lines.get_or_insert(0) += surviving;
+            }
+        }
+        totals
+    }
+}
+
+/// Commits with the first of each group of equivalent commits (same patch-id)
+/// kept and the rest dropped; commits without a patch-id are kept once each,
+/// as a commit can belong to several contributions
+pub fn distinct_changes<'a>(commits: impl IntoIterator<Item = &'a Commit>) -> Vec<&'a Commit> {
+    let mut seen = HashSet::new();
+    let mut seen_hashes = HashSet::new();
+    commits
+        .into_iter()
+        .filter(|c| seen_hashes.insert(c.hash.as_str()))
+        .filter(|c| c.patch_id.as_ref().is_none_or(|id| seen.insert(id.as_str())))
+        .collect()
+}
+
+/// Blame results by path and blob id: the lines each commit owns in that file
+pub type BlameCache = HashMap<(String, String), Vec<(String, i64)>>;
+
+/// A file at HEAD and how many of its lines each commit last changed
+#[derive(Debug, Clone)]
+pub struct BlameFile {
+    pub path: String,
+    pub blob_oid: String,
+    pub owners: Vec<(String, i64)>,
+}
+
+/// Lines changed in one path across a contribution's commits
+#[derive(Debug, Clone)]
+pub struct FileChurn {
+    pub path: String,
+    pub commits: i64,
+    pub lines_added: i64,
+    pub lines_deleted: i64,
+}
+
+/// How a revert was recognized
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevertSource {
+    /// A `This reverts commit <hash>` line in the message
+    Message,
+    /// The commit's patch-id equals the inverse patch-id of an earlier commit
+    Patch,
+}
+
+impl RevertSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RevertSource::Message => "message",
+            RevertSource::Patch => "patch",
+        }
+    }
+}
+
+/// A commit that undoes an earlier one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revert {
+    pub revert_hash: String,
+    pub reverted_hash: String,
+    pub source: RevertSource,
+}
+
+/// What revert detection needs to know about a stored commit
+#[derive(Debug, Clone, Default)]
+pub struct PatchInfo {
+    pub hash: String,
+    pub message: String,
+    pub author_time: Option<i64>,
+    pub patch_id: Option<String>,
+    pub inverse_patch_id: Option<String>,
 }
 
+/// Why a commit is linked to a contribution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkSource {
+    /// Listed in the contribution's key_commits or related_commits
+    Hash,
+    /// Named the contribution in a commit message trailer
+    Trailer,
+    /// Named the contribution in a message matching a configured regex
+    Regex,
+}
+
+impl LinkSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkSource::Hash => "hash",
+            LinkSource::Trailer => "trailer",
+            LinkSource::Regex => "regex",
+        }
+    }
+}
+
+/// How a contribution lists a commit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitRole {
+    Key,
+    Related,
+}
+
+impl CommitRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommitRole::Key => "key",
+            CommitRole::Related => "related",
+        }
+    }
+
+    fn from_str(role: &str) -> Self {
+        match role {
+            "key" => CommitRole::Key,
+            _ => CommitRole::Related,
+        }
+    }
+}
+
+impl Commit {
+    /// Author time in the author's own timezone, RFC 3339
+    pub fn author_date(&self) -> String {
+        local_date(self.author_time, self.author_offset).unwrap_or_else(|| self.date.clone())
+    }
+
+    /// Committer time in the committer's own timezone, RFC 3339
+    pub fn committer_date(&self) -> Option<String> {
+        local_date(self.committer_time, self.committer_offset)
+    }
+
+    /// Whether the commit's change was undone: it was reverted by a revert that
+    /// still stands, or it is a revert itself. Either way it earns no credit.
+    pub fn is_reverted(&self) -> bool {
+        self.reverted_by.is_some() || self.reverts.is_some()
+    }
+
+    /// Names and emails of everyone credited with the commit. Commits stored
+    /// before co-authors were recorded credit only the author.
+    pub fn credited(&self) -> Vec<(&str, &str)> {
+        let credited: Vec<(&str, &str)> = self
+            .authors
+            .iter()
+            .filter(|a| a.role.is_credited())
+            .map(|a| (a.name.as_str(), a.email.as_str()))
+            .collect();
+        if credited.is_empty() {
+            vec![(self.author.as_str(), self.author_email.as_str())]
+        } else {
+            credited
+        }
+    }
+
+    /// Lines added and deleted without excluded files, falling back to the raw
+    /// counts for commits stored before exclusions were recorded
+    pub fn meaningful_lines(&self) -> (Option<i32>, Option<i32>) {
+        match (self.meaningful_lines_added, self.meaningful_lines_deleted) {
+            (Some(added), Some(deleted)) => (Some(added), Some(deleted)),
+            _ => (self.lines_added, self.lines_deleted),
+        }
+    }
+}
+
+/// A person who may commit under several names and emails
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub id: i64,
+    pub name: String,
+    pub aliases: Vec<IdentityAlias>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityAlias {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+/// Sync watermark recorded after each `update` of a repository
+#[derive(Debug, Clone)]
+pub struct SyncState {
+    pub head_oid: String,
+    /// Tips of the refs that update walked besides HEAD; refs it did not walk
+    /// are never recorded, or their unread history would be hidden for good
+    pub refs: HashMap<String, String>,
+    pub synced_at: String,
+}
+
+/// Map a row selected as `commit_hash, repository_url, contribution_id, author,
+/// author_email, date, message, files_changed, lines_added, lines_deleted, link_source,
+/// meaningful_lines_added, meaningful_lines_deleted, released_in, surviving_lines,
+/// author_time, author_offset, committer_time, committer_offset, patch_id,
+/// inverse_patch_id, reverted_by, reverts`
+fn commit_from_row(row: &rusqlite::Row) -> rusqlite::Result<Commit> {
+    Ok(Commit {
+        hash: row.get(0)?,
+        repository_url: row.get(1)?,
+        contribution_id: row.get(2)?,
+        author: row.get(3)?,
+        author_email: row.get(4)?,
+        date: row.get(5)?,
+        message: row.get(6)?,
+        files_changed: serde_json::from_str(row.get::<_, String>(7)?.as_str()).unwrap_or_default(),
+        lines_added: row.get(8)?,
+        lines_deleted: row.get(9)?,
+        link_source: row.get(10)?,
+        meaningful_lines_added: row.get(11)?,
+        meaningful_lines_deleted: row.get(12)?,
+        released_in: row.get(13)?,
+        surviving_lines: row.get(14)?,
+        author_time: row.get(15)?,
+        author_offset: row.get(16)?,
+        committer_time: row.get(17)?,
+        committer_offset: row.get(18)?,
+        patch_id: row.get(19)?,
+        inverse_patch_id: row.get(20)?,
+        reverted_by: row.get(21)?,
+        reverts: row.get(22)?,
+        role: None,
+        refs: Vec::new(),
+        files: Vec::new(),
+        authors: Vec::new(),
+    })
+}
+
+/// Replace the key and related commits a contribution lists. A hash listed
+/// both ways is kept as a key commit.
+fn set_listed_commits(conn: &Connection, contribution_id: i64, contrib: &Contribution) -> Result<()> {
+    conn.execute(
+        "DELETE FROM contribution_commits WHERE contribution_id = ?1 AND source = 'hash'",
+        params![contribution_id],
+    )?;
+    let mut stmt = conn.prepare(
+        "INSERT OR IGNORE INTO contribution_commits (contribution_id, commit_hash, source, role, position)
+         VALUES (?1, ?2, 'hash', ?3, ?4)",
+    )?;
+    for (role, hashes) in [(CommitRole::Key, &contrib.key_commits), (CommitRole::Related, &contrib.related_commits)] {
+        for (position, hash) in hashes.iter().enumerate() {
+            stmt.execute(params![contribution_id, hash, role.as_str(), position])?;
+        }
+    }
+    Ok(())
+}
+
+/// `WITH` clauses selecting the commits of the contribution named ?2 in
+/// repository ?1 as `members(commit_hash, role, link_source)`: the commits it
+/// lists (with their equivalents) and the commits whose message names it, each
+/// whatever other contributions it also belongs to. Listed hashes are resolved
+/// once, through a prefix range on the commits' primary key.
+const CONTRIBUTION_MEMBERS: &str = "
+    WITH contribution AS (
+        SELECT id FROM contributions WHERE repository_url = ?1 AND name = ?2
+    ),
+    listed AS MATERIALIZED (
+        -- CROSS JOIN keeps the listed hashes as the outer loop
+        SELECT l.commit_hash, l.patch_id, cc.role
+        FROM contribution
+        CROSS JOIN contribution_commits cc ON cc.contribution_id = contribution.id
+        CROSS JOIN commits l ON l.commit_hash >= cc.commit_hash AND l.commit_hash < cc.commit_hash || 'g'
+        WHERE cc.source = 'hash' AND +l.repository_url = ?1
+    ),
+    candidates AS (
+        SELECT cc.commit_hash, NULL AS role, cc.source
+        FROM contribution
+        CROSS JOIN contribution_commits cc ON cc.contribution_id = contribution.id
+        WHERE cc.source != 'hash'
+        UNION ALL
+        SELECT commit_hash, role, 'hash' FROM listed
+        UNION ALL
+        SELECT e.commit_hash, listed.role, 'hash'
+        FROM listed
+        CROSS JOIN commits e ON e.repository_url = ?1 AND e.patch_id = listed.patch_id
+    ),
+    members AS (
+        -- A commit listed both ways is a key commit
+        SELECT commit_hash,
+               CASE MAX(CASE role WHEN 'key' THEN 2 WHEN 'related' THEN 1 ELSE 0 END)
+                   WHEN 2 THEN 'key' WHEN 1 THEN 'related'
+               END AS role,
+               rtrim(CASE WHEN MAX(source = 'hash') THEN 'hash, ' ELSE '' END
+                     || CASE WHEN MAX(source = 'trailer') THEN 'trailer, ' ELSE '' END
+                     || CASE WHEN MAX(source = 'regex') THEN 'regex' ELSE '' END, ', ') AS link_source
+        FROM candidates
+        GROUP BY commit_hash
+    )";
+
 impl Database {
     pub fn open() -> Result<Self> {
         let db_path = get_database_path()?;
-        let conn = Connection::open(&db_path)
-            .with_context(|| format!("Failed to open database at {:?}", db_path))?;
-        
-        let db = Database { conn };
-        db.initialize_schema()?;
-        Ok(db)
+        Self::open_at(&db_path)
     }
 
-    fn initialize_schema(&self) -> Result<()> {
-        // Repositories table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS repositories (
-                repository_url TEXT PRIMARY KEY,
-                organization TEXT NOT NULL,
-                name TEXT NOT NULL,
-                description TEXT,
-                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
-
-        // Contributions table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS contributions (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                repository_url TEXT NOT NULL,
-                name TEXT NOT NULL,
-                overview TEXT,
-                description TEXT,
-                key_commits TEXT,
-                related_commits TEXT,
-                technical_details TEXT,
-                resume_bullets TEXT,
-                category TEXT,
-                priority INTEGER DEFAULT 0,
-                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (repository_url) REFERENCES repositories(repository_url),
-                UNIQUE(repository_url, name)
-            )",
-            [],
-        )?;
-
-        // Commits table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS commits (
-                commit_hash TEXT PRIMARY KEY,
-                repository_url TEXT NOT NULL,
-                contribution_id INTEGER,
-                author TEXT NOT NULL,
-                author_email TEXT,
-                date TEXT NOT NULL,
-                message TEXT,
-                files_changed TEXT,
-                lines_added INTEGER,
-                lines_deleted INTEGER,
-                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (repository_url) REFERENCES repositories(repository_url),
-                FOREIGN KEY (contribution_id) REFERENCES contributions(id)
-            )",
-            [],
-        )?;
+    /// Open the database at a specific path, applying pending migrations
+    pub fn open_at(db_path: &Path) -> Result<Self> {
+        let db = Self::open_without_migrating(db_path)?;
+        db.migrate()?;
+        db.initialize_defaults()?;
+        Ok(db)
+    }
 
-        // Agent rules table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS agent_rules (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL UNIQUE,
-                instruction TEXT NOT NULL,
-                priority INTEGER DEFAULT 0,
-                category TEXT,
-                examples TEXT,
-                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
+    /// Open the database at a specific path without touching its schema
+    pub fn open_without_migrating(db_path: &Path) -> Result<Self> {
+        let conn = Connection::open(db_path)
+            .with_context(|| format!("Failed to open database at {:?}", db_path))?;
+        Ok(Database { conn })
+    }
 
-        // Prompts table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS prompts (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL UNIQUE,
-                prompt_text TEXT NOT NULL,
-                description TEXT,
-                category TEXT,
-                variables TEXT,
-                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
+    /// Current schema version, as recorded in `PRAGMA user_version`
+    pub fn schema_version(&self) -> Result<i64> {
+        Ok(self.conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+    }
 
-        // Loadouts table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS loadouts (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL UNIQUE,
-                is_default INTEGER DEFAULT 0,
-                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
+    /// Migrations that have not yet been applied to this database
+    pub fn pending_migrations(&self) -> Result<Vec<&'static Migration>> {
+        let current = self.schema_version()?;
+        Ok(MIGRATIONS.iter().filter(|m| m.version > current).collect())
+    }
 
-        // Loadout prompts junction table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS loadout_prompts (
-                loadout_id INTEGER NOT NULL,
-                prompt_id INTEGER NOT NULL,
-                PRIMARY KEY (loadout_id, prompt_id),
-                FOREIGN KEY (loadout_id) REFERENCES loadouts(id) ON DELETE CASCADE,
-                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
-            )",
-            [],
-        )?;
+    /// Apply all pending migrations inside a single transaction.
+    /// Returns the migrations that were applied.
+    pub fn migrate(&self) -> Result<Vec<&'static Migration>> {
+        let pending = self.pending_migrations()?;
+        if pending.is_empty() {
+            return Ok(pending);
+        }
 
-        // Loadout rules junction table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS loadout_rules (
-                loadout_id INTEGER NOT NULL,
-                rule_id INTEGER NOT NULL,
-                PRIMARY KEY (loadout_id, rule_id),
-                FOREIGN KEY (loadout_id) REFERENCES loadouts(id) ON DELETE CASCADE,
-                FOREIGN KEY (rule_id) REFERENCES agent_rules(id) ON DELETE CASCADE
-            )",
-            [],
-        )?;
+        let tx = self.conn.unchecked_transaction()?;
+        for migration in &pending {
+            tx.execute_batch(migration.sql).with_context(|| {
+                format!("Failed to apply migration {} ({})", migration.version, migration.description)
+            })?;
+            tx.pragma_update(None, "user_version", migration.version)?;
+        }
+        tx.commit()?;
 
-        // Create indexes
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_contributions_repo ON contributions(repository_url)",
-            [],
-        )?;
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_commits_repo ON commits(repository_url)",
-            [],
-        )?;
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_commits_contribution ON commits(contribution_id)",
-            [],
-        )?;
+        Ok(pending)
+    }
 
+    fn initialize_defaults(&self) -> Result<()> {
         // Initialize agent rules if they don't exist
         self.initialize_agent_rules()?;
         self.initialize_prompts()?;
@@ -337,56 +729,221 @@ impl Database {
     }
 
     pub fn add_repository(&self, repo: &Repository) -> Result<()> {
+        // A repository registered again keeps its checkout path unless given a new one
         self.conn.execute(
-            "INSERT OR REPLACE INTO repositories (repository_url, organization, name, description, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![repo.url, repo.organization, repo.name, repo.description, Utc::now().to_rfc3339()],
+            "INSERT INTO repositories (repository_url, organization, name, description, local_path, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(repository_url) DO UPDATE SET
+                organization = excluded.organization,
+                name = excluded.name,
+                description = excluded.description,
+                local_path = COALESCE(excluded.local_path, repositories.local_path),
+                updated_at = excluded.updated_at",
+            params![repo.url, repo.organization, repo.name, repo.description, repo.local_path, Utc::now().to_rfc3339()],
         )?;
         Ok(())
     }
 
+    /// Insert a contribution, or update the existing one with the same repository and name.
+    /// An existing contribution keeps its id, so commits linked to it stay linked.
     pub fn add_contribution(&self, contrib: &Contribution) -> Result<i64> {
-        let key_commits_json = serde_json::to_string(&contrib.key_commits)?;
-        let related_commits_json = serde_json::to_string(&contrib.related_commits)?;
         let technical_details_json = serde_json::to_string(&contrib.technical_details)?;
         let resume_bullets_json = serde_json::to_string(&contrib.resume_bullets)?;
 
-        self.conn.execute(
-            "INSERT OR REPLACE INTO contributions 
-            (repository_url, name, overview, description, key_commits, related_commits, 
+        let tx = self.conn.unchecked_transaction()?;
+        let id: i64 = tx.query_row(
+            "INSERT INTO contributions 
+            (repository_url, name, overview, description,
              technical_details, resume_bullets, category, priority, updated_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            ON CONFLICT(repository_url, name) DO UPDATE SET
+                overview = excluded.overview,
+                description = excluded.description,
+                technical_details = excluded.technical_details,
+                resume_bullets = excluded.resume_bullets,
+                category = excluded.category,
+                priority = excluded.priority,
+                updated_at = excluded.updated_at
+            RETURNING id",
             params![
                 contrib.repository_url,
                 contrib.name,
                 contrib.overview,
                 contrib.description,
-                key_commits_json,
-                related_commits_json,
                 technical_details_json,
                 resume_bullets_json,
                 contrib.category,
                 contrib.priority,
                 Utc::now().to_rfc3339()
             ],
+            |row| row.get(0),
         )?;
-
-        let id: i64 = if let Some(existing_id) = contrib.id {
-            existing_id
-        } else {
-            self.conn.last_insert_rowid()
-        };
+        set_listed_commits(&tx, id, contrib)?;
+        tx.commit()?;
 
         Ok(id)
     }
 
+    /// Update an existing contribution in place, identified by its id.
+    /// This is also how a contribution is renamed.
+    pub fn update_contribution(&self, contrib: &Contribution) -> Result<()> {
+        let id = contrib.id
+            .ok_or_else(|| anyhow::anyhow!("Cannot update contribution '{}' without an id", contrib.name))?;
+
+        let technical_details_json = serde_json::to_string(&contrib.technical_details)?;
+        let resume_bullets_json = serde_json::to_string(&contrib.resume_bullets)?;
+
+        let tx = self.conn.unchecked_transaction()?;
+        let result = tx.execute(
+            "UPDATE contributions SET
+                name = ?1, overview = ?2, description = ?3,
+                technical_details = ?4, resume_bullets = ?5, category = ?6, priority = ?7, updated_at = ?8
+             WHERE id = ?9",
+            params![
+                contrib.name,
+                contrib.overview,
+                contrib.description,
+                technical_details_json,
+                resume_bullets_json,
+                contrib.category,
+                contrib.priority,
+                Utc::now().to_rfc3339(),
+                id
+            ],
+        );
+
+        match result {
+            Ok(0) => Err(anyhow::anyhow!("Contribution with id {} not found", id)),
+            Ok(_) => {
+                set_listed_commits(&tx, id, contrib)?;
+                tx.commit()?;
+                Ok(())
+            }
+            Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::ConstraintViolation => {
+                Err(anyhow::anyhow!(
+                    "A contribution named '{}' already exists for {}",
+                    contrib.name,
+                    contrib.repository_url
+                ))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Delete a contribution. Linked commits are deleted when `cascade` is set,
+    /// otherwise they are detached and kept as unassigned commits. A commit that
+    /// another contribution lists, directly or through its patch-id, is always
+    /// detached rather than deleted; run linking again to hand it over.
+    /// Returns the number of commits deleted and the number detached.
+    ///
+    /// Deleted commits lie below the repository's sync watermark, so the
+    /// watermark is dropped and the next `update` reads them again.
+    pub fn delete_contribution(&self, id: i64, cascade: bool) -> Result<(usize, usize)> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        let mut deleted = 0;
+        if cascade {
+            let doomed: Vec<String> = {
+                let mut stmt = tx.prepare(
+                    "SELECT cm.commit_hash FROM commits cm
+                     WHERE cm.contribution_id = ?1
+                       AND NOT EXISTS (
+                           SELECT 1 FROM contribution_commits cc
+                           JOIN commits l ON l.commit_hash >= cc.commit_hash AND l.commit_hash < cc.commit_hash || 'g'
+                           WHERE cc.contribution_id != ?1
+                             AND l.repository_url = cm.repository_url
+                             AND (l.commit_hash = cm.commit_hash OR (cc.source = 'hash' AND l.patch_id = cm.patch_id))
+                       )"
+                )?;
+                let rows = stmt.query_map(params![id], |row| row.get(0))?;
+                rows.collect::<rusqlite::Result<_>>()?
+            };
+            if !doomed.is_empty() {
+                tx.execute(
+                    "DELETE FROM sync_state WHERE repository_url = (SELECT repository_url FROM contributions WHERE id = ?1)",
+                    params![id],
+                )?;
+            }
+            for hash in &doomed {
+                tx.execute("DELETE FROM commit_files WHERE commit_hash = ?1", params![hash])?;
+                tx.execute("DELETE FROM commit_authors WHERE commit_hash = ?1", params![hash])?;
+                tx.execute(
+                    "DELETE FROM commit_reverts WHERE revert_hash = ?1 OR reverted_hash = ?1",
+                    params![hash],
+                )?;
+                deleted += tx.execute("DELETE FROM commits WHERE commit_hash = ?1", params![hash])?;
+            }
+        }
+        let detached = tx.execute(
+            "UPDATE commits SET contribution_id = NULL, link_source = NULL WHERE contribution_id = ?1",
+            params![id],
+        )?;
+        tx.execute("DELETE FROM contribution_commits WHERE contribution_id = ?1", params![id])?;
+        tx.execute("DELETE FROM contributions WHERE id = ?1", params![id])?;
+
+        tx.commit()?;
+        Ok((deleted, detached))
+    }
+
+    /// Detach every commit linked to a contribution and forget the commits
+    /// whose message names it. The hashes it lists are kept.
+    pub fn unlink_commits(&self, contribution_id: i64) -> Result<usize> {
+        self.conn.execute(
+            "DELETE FROM contribution_commits WHERE contribution_id = ?1 AND source != 'hash'",
+            params![contribution_id],
+        )?;
+        Ok(self.conn.execute(
+            "UPDATE commits SET contribution_id = NULL, link_source = NULL WHERE contribution_id = ?1",
+            params![contribution_id],
+        )?)
+    }
+
+    /// Link a commit whose message names a contribution. The commit belongs to
+    /// the contribution whatever else it is linked to, and becomes the commit's
+    /// own link if it has none. Returns whether the link is new.
+    pub fn link_commit(&self, commit_hash: &str, contribution_id: i64, source: LinkSource) -> Result<bool> {
+        let inserted = self.conn.execute(
+            "INSERT OR IGNORE INTO contribution_commits (contribution_id, commit_hash, source)
+             VALUES (?1, ?2, ?3)",
+            params![contribution_id, commit_hash, source.as_str()],
+        )?;
+        self.conn.execute(
+            "UPDATE commits SET contribution_id = ?1, link_source = ?2
+             WHERE commit_hash = ?3 AND contribution_id IS NULL",
+            params![contribution_id, source.as_str(), commit_hash],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    /// Store a commit. Re-storing one updates only what extraction reads, so its
+    /// links, release, revert and surviving lines are kept until recomputed.
     pub fn add_commit(&self, commit: &Commit) -> Result<()> {
         let files_changed_json = serde_json::to_string(&commit.files_changed)?;
 
         self.conn.execute(
-            "INSERT OR REPLACE INTO commits 
+            "INSERT INTO commits
             (commit_hash, repository_url, contribution_id, author, author_email, date, 
-             message, files_changed, lines_added, lines_deleted)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+             message, files_changed, lines_added, lines_deleted, link_source,
+             meaningful_lines_added, meaningful_lines_deleted, released_in, surviving_lines,
+             author_time, author_offset, committer_time, committer_offset, patch_id, inverse_patch_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21)
+            ON CONFLICT(commit_hash) DO UPDATE SET
+                repository_url = excluded.repository_url,
+                author = excluded.author,
+                author_email = excluded.author_email,
+                date = excluded.date,
+                message = excluded.message,
+                files_changed = excluded.files_changed,
+                lines_added = excluded.lines_added,
+                lines_deleted = excluded.lines_deleted,
+                meaningful_lines_added = excluded.meaningful_lines_added,
+                meaningful_lines_deleted = excluded.meaningful_lines_deleted,
+                author_time = excluded.author_time,
+                author_offset = excluded.author_offset,
+                committer_time = excluded.committer_time,
+                committer_offset = excluded.committer_offset,
+                patch_id = excluded.patch_id,
+                inverse_patch_id = excluded.inverse_patch_id",
             params![
                 commit.hash,
                 commit.repository_url,
@@ -397,16 +954,405 @@ impl Database {
                 commit.message,
                 files_changed_json,
                 commit.lines_added,
-                commit.lines_deleted
+                commit.lines_deleted,
+                commit.link_source,
+                commit.meaningful_lines_added,
+                commit.meaningful_lines_deleted,
+                commit.released_in,
+                commit.surviving_lines,
+                commit.author_time,
+                commit.author_offset,
+                commit.committer_time,
+                commit.committer_offset,
+                commit.patch_id,
+                commit.inverse_patch_id
             ],
         )?;
+
+        // A re-extracted commit replaces its files and people, even with none left
+        self.conn.execute("DELETE FROM commit_files WHERE commit_hash = ?1", params![commit.hash])?;
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT INTO commit_files
+             (repository_url, commit_hash, position, old_path, new_path, status,
+              lines_added, lines_deleted, is_binary, is_excluded)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"
+        )?;
+        for (position, file) in commit.files.iter().enumerate() {
+            stmt.execute(params![
+                commit.repository_url,
+                commit.hash,
+                position as i64,
+                file.old_path,
+                file.new_path,
+                file.status.as_str(),
+                file.lines_added,
+                file.lines_deleted,
+                file.binary,
+                file.excluded
+            ])?;
+        }
+
+        self.conn.execute("DELETE FROM commit_authors WHERE commit_hash = ?1", params![commit.hash])?;
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT OR IGNORE INTO commit_authors (repository_url, commit_hash, name, email, role)
+             VALUES (?1, ?2, ?3, ?4, ?5)"
+        )?;
+        for author in &commit.authors {
+            stmt.execute(params![commit.repository_url, commit.hash, author.name, author.email, author.role.as_str()])?;
+        }
+        Ok(())
+    }
+
+    pub fn add_commits(&self, commits: &[Commit]) -> Result<()> {
+        for commit in commits {
+            self.add_commit(commit)?;
+        }
+        Ok(())
+    }
+
+    /// Release tags and their tips as of the last release attribution
+    pub fn get_release_tips(&self, repo_url: &str) -> Result<Vec<(String, String)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT tag_name, tip_hash FROM release_tips WHERE repository_url = ?1 ORDER BY tag_name"
+        )?;
+        let rows = stmt.query_map(params![repo_url], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Record the release each commit first shipped in, given for every
+    /// released commit of the repository, and the release tags it was worked
+    /// out from. Returns the number of commits changed.
+    pub fn set_released_in(
+        &self,
+        repo_url: &str,
+        tags: &[(String, String)],
+        released: &HashMap<String, String>,
+    ) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let released_json = serde_json::to_string(released)?;
+        let mut changed = tx.execute(
+            "UPDATE commits SET released_in = NULL
+             WHERE repository_url = ?1 AND released_in IS NOT NULL
+               AND commit_hash NOT IN (SELECT key FROM json_each(?2))",
+            params![repo_url, released_json],
+        )?;
+        changed += Self::update_released_in(&tx, repo_url, &released_json)?;
+
+        tx.execute("DELETE FROM release_tips WHERE repository_url = ?1", params![repo_url])?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO release_tips (repository_url, tag_name, tip_hash) VALUES (?1, ?2, ?3)"
+            )?;
+            for (name, tip) in tags {
+                stmt.execute(params![repo_url, name, tip])?;
+            }
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    /// Record the release of some commits, leaving the rest as they are.
+    /// Returns the number of commits changed.
+    pub fn add_released_in(&self, repo_url: &str, released: &HashMap<String, String>) -> Result<usize> {
+        Self::update_released_in(&self.conn, repo_url, &serde_json::to_string(released)?)
+    }
+
+    fn update_released_in(conn: &Connection, repo_url: &str, released_json: &str) -> Result<usize> {
+        Ok(conn.execute(
+            "UPDATE commits SET released_in = r.value
+             FROM json_each(?2) r
+             WHERE commits.commit_hash = r.key AND commits.repository_url = ?1
+               AND commits.released_in IS NOT r.value",
+            params![repo_url, released_json],
+        )?)
+    }
+
+    /// Message and patch-ids of every commit in a repository, for revert detection
+    pub fn get_patch_infos(&self, repo_url: &str) -> Result<Vec<PatchInfo>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT commit_hash, message, author_time, patch_id, inverse_patch_id
+             FROM commits WHERE repository_url = ?1"
+        )?;
+        let rows = stmt.query_map(params![repo_url], |row| {
+            Ok(PatchInfo {
+                hash: row.get(0)?,
+                message: row.get(1)?,
+                author_time: row.get(2)?,
+                patch_id: row.get(3)?,
+                inverse_patch_id: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Commits that repeat a change already made by another commit of the
+    /// repository, e.g. cherry-picks to release branches
+    pub fn count_equivalent_commits(&self, repo_url: &str) -> Result<usize> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(patch_id) - COUNT(DISTINCT patch_id) FROM commits WHERE repository_url = ?1",
+            params![repo_url],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Replace a repository's reverts and mark the commits undone by reverts
+    /// that still stand. Returns the number of reverted commits.
+    pub fn set_reverts(&self, repo_url: &str, reverts: &[Revert]) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM commit_reverts WHERE repository_url = ?1", params![repo_url])?;
+        tx.execute("UPDATE commits SET reverted_by = NULL WHERE repository_url = ?1", params![repo_url])?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO commit_reverts (revert_hash, reverted_hash, repository_url, source)
+                 VALUES (?1, ?2, ?3, ?4)"
+            )?;
+            for revert in reverts {
+                stmt.execute(params![revert.revert_hash, revert.reverted_hash, repo_url, revert.source.as_str()])?;
+            }
+        }
+        let undone = crate::revert::standing_reverts(reverts);
+        {
+            let mut stmt = tx.prepare("UPDATE commits SET reverted_by = ?1 WHERE commit_hash = ?2")?;
+            for (reverted, revert) in &undone {
+                stmt.execute(params![revert, reverted])?;
+            }
+        }
+        tx.commit()?;
+        Ok(undone.len())
+    }
+
+    /// Commits stored before their committer time was recorded
+    pub fn get_commits_without_times(&self, repo_url: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT commit_hash FROM commits WHERE repository_url = ?1 AND committer_time IS NULL"
+        )?;
+        let rows = stmt.query_map(params![repo_url], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Record author and committer times read from the repository
+    pub fn set_commit_times(&self, times: &HashMap<String, CommitTimes>) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "UPDATE commits SET author_time = ?1, author_offset = ?2, committer_time = ?3, committer_offset = ?4
+                 WHERE commit_hash = ?5"
+            )?;
+            for (hash, t) in times {
+                stmt.execute(params![t.author_time, t.author_offset, t.committer_time, t.committer_offset, hash])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Blame results from earlier runs, keyed by path and blob id
+    pub fn get_blame_cache(&self, repo_url: &str) -> Result<BlameCache> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path, blob_oid, commit_hash, lines FROM blame_cache WHERE repository_url = ?1"
+        )?;
+        let rows = stmt.query_map(params![repo_url], |row| {
+            Ok(((row.get::<_, String>(0)?, row.get::<_, String>(1)?), (row.get(2)?, row.get(3)?)))
+        })?;
+
+        let mut cache = BlameCache::new();
+        for row in rows {
+            let (key, owner) = row?;
+            cache.entry(key).or_default().push(owner);
+        }
+        Ok(cache)
+    }
+
+    /// Replace the repository's blame cache with the files at HEAD and set each
+    /// commit's surviving lines; commits owning no lines get 0. Returns the
+    /// total number of lines blamed.
+    pub fn store_blame(&self, repo_url: &str, files: &[BlameFile]) -> Result<i64> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM blame_cache WHERE repository_url = ?1", params![repo_url])?;
+
+        let mut totals: HashMap<&str, i64> = HashMap::new();
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO blame_cache (repository_url, path, blob_oid, commit_hash, lines)
+                 VALUES (?1, ?2, ?3, ?4, ?5)"
+            )?;
+            for file in files {
+                for (hash, lines) in &file.owners {
+                    stmt.execute(params![repo_url, file.path, file.blob_oid, hash, lines])?;
+                    *totals.entry(hash.as_str()).or_default() += lines;
+                }
+            }
+        }
+
+        tx.execute("UPDATE commits SET surviving_lines = 0 WHERE repository_url = ?1", params![repo_url])?;
+        {
+            let mut stmt = tx.prepare(
+                "UPDATE commits SET surviving_lines = ?1 WHERE repository_url = ?2 AND commit_hash = ?3"
+            )?;
+            for (hash, lines) in &totals {
+                stmt.execute(params![lines, repo_url, hash])?;
+            }
+        }
+        tx.commit()?;
+
+        Ok(totals.values().sum())
+    }
+
+    /// Start a transaction that covers every call on this database until it is
+    /// committed; dropping it rolls back
+    pub fn transaction(&self) -> Result<rusqlite::Transaction<'_>> {
+        Ok(self.conn.unchecked_transaction()?)
+    }
+
+    /// Get the hashes of all commits already stored for a repository
+    pub fn get_commit_hashes(&self, repo_url: &str) -> Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT commit_hash FROM commits WHERE repository_url = ?1"
+        )?;
+        let rows = stmt.query_map(params![repo_url], |row| row.get::<_, String>(0))?;
+
+        let mut hashes = HashSet::new();
+        for row in rows {
+            hashes.insert(row?);
+        }
+        Ok(hashes)
+    }
+
+    /// Link unassigned commits to the contributions that list them as key or
+    /// related commits, together with their equivalents. A commit listed by
+    /// several contributions is linked to the one with the highest priority;
+    /// the others still include it as a member. Returns the number of commits linked.
+    pub fn link_commits_to_contributions(&self, repo_url: &str) -> Result<usize> {
+        Ok(self.conn.execute(
+            "WITH listed AS MATERIALIZED (
+                 SELECT l.commit_hash, l.patch_id, c.id, c.priority, c.name
+                 FROM contributions c
+                 CROSS JOIN contribution_commits cc ON cc.contribution_id = c.id
+                 CROSS JOIN commits l ON l.commit_hash >= cc.commit_hash AND l.commit_hash < cc.commit_hash || 'g'
+                 WHERE c.repository_url = ?1 AND cc.source = 'hash' AND +l.repository_url = ?1
+             ),
+             candidates AS (
+                 SELECT commit_hash, id, priority, name FROM listed
+                 UNION ALL
+                 SELECT e.commit_hash, listed.id, listed.priority, listed.name
+                 FROM listed
+                 CROSS JOIN commits e ON e.repository_url = ?1 AND e.patch_id = listed.patch_id
+             ),
+             ranked AS (
+                 SELECT commit_hash, id,
+                        ROW_NUMBER() OVER (PARTITION BY commit_hash ORDER BY priority DESC, name) AS rank
+                 FROM candidates
+             )
+             -- The unary + keeps the listed commits as the outer loop
+             UPDATE commits SET contribution_id = ranked.id, link_source = ?2
+             FROM ranked
+             WHERE ranked.rank = 1 AND commits.commit_hash = ranked.commit_hash AND +commits.contribution_id IS NULL",
+            params![repo_url, LinkSource::Hash.as_str()],
+        )?)
+    }
+
+    /// Every repository URL that commits, contributions or sync data are stored under
+    pub fn get_stored_urls(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT repository_url FROM commits
+             UNION SELECT repository_url FROM contributions
+             UNION SELECT repository_url FROM sync_state
+             UNION SELECT repository_url FROM ref_tips
+             UNION SELECT repository_url FROM release_tips
+             UNION SELECT repository_url FROM blame_cache
+             UNION SELECT repository_url FROM commit_reverts"
+        )?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+
+        let mut urls = Vec::new();
+        for row in rows {
+            urls.push(row?);
+        }
+        Ok(urls)
+    }
+
+    /// Move every row stored under `from` to the repository `to`. Where `to`
+    /// already has a row with the same key (a contribution of the same name, a
+    /// watermark, a ref tip), the row under `to` is kept. Returns the number of
+    /// commits moved.
+    pub fn move_repository_rows(&self, from: &str, to: &str) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        tx.execute(
+            "UPDATE OR IGNORE contributions SET repository_url = ?2 WHERE repository_url = ?1",
+            params![from, to],
+        )?;
+        // Contributions whose name `to` already uses are dropped with their links
+        tx.execute(
+            "DELETE FROM contribution_commits WHERE contribution_id IN
+             (SELECT id FROM contributions WHERE repository_url = ?1)",
+            params![from],
+        )?;
+        tx.execute(
+            "UPDATE commits SET contribution_id = NULL, link_source = NULL WHERE contribution_id IN
+             (SELECT id FROM contributions WHERE repository_url = ?1)",
+            params![from],
+        )?;
+        tx.execute("DELETE FROM contributions WHERE repository_url = ?1", params![from])?;
+
+        let moved = tx.execute(
+            "UPDATE commits SET repository_url = ?2 WHERE repository_url = ?1",
+            params![from, to],
+        )?;
+        for table in ["commit_files", "commit_authors", "commit_reverts"] {
+            tx.execute(
+                &format!("UPDATE {} SET repository_url = ?2 WHERE repository_url = ?1", table),
+                params![from, to],
+            )?;
+        }
+        for table in ["sync_state", "ref_tips", "release_tips", "blame_cache"] {
+            tx.execute(
+                &format!("UPDATE OR IGNORE {} SET repository_url = ?2 WHERE repository_url = ?1", table),
+                params![from, to],
+            )?;
+            tx.execute(&format!("DELETE FROM {} WHERE repository_url = ?1", table), params![from])?;
+        }
+
+        tx.commit()?;
+        Ok(moved)
+    }
+
+    pub fn get_sync_state(&self, repo_url: &str) -> Result<Option<SyncState>> {
+        let result = self.conn.query_row(
+            "SELECT head_oid, refs, synced_at FROM sync_state WHERE repository_url = ?1",
+            params![repo_url],
+            |row| {
+                Ok(SyncState {
+                    head_oid: row.get(0)?,
+                    refs: serde_json::from_str(row.get::<_, String>(1)?.as_str()).unwrap_or_default(),
+                    synced_at: row.get(2)?,
+                })
+            },
+        );
+
+        match result {
+            Ok(state) => Ok(Some(state)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Record the watermark: HEAD and the tips of the refs that were walked
+    pub fn set_sync_state(&self, repo_url: &str, head_oid: &str, refs: &HashMap<String, String>) -> Result<()> {
+        let refs_json = serde_json::to_string(refs)?;
+
+        self.conn.execute(
+            "INSERT OR REPLACE INTO sync_state (repository_url, head_oid, refs, synced_at) VALUES (?1, ?2, ?3, ?4)",
+            params![repo_url, head_oid, refs_json, Utc::now().to_rfc3339()],
+        )?;
         Ok(())
     }
 
     pub fn get_contributions(&self, repo_url: &str) -> Result<Vec<Contribution>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, repository_url, name, overview, description, key_commits, 
-             related_commits, technical_details, resume_bullets, category, priority
+            "SELECT id, repository_url, name, overview, description,
+             technical_details, resume_bullets, category, priority
              FROM contributions WHERE repository_url = ?1 ORDER BY priority DESC, name"
         )?;
 
@@ -417,26 +1363,46 @@ impl Database {
                 name: row.get(2)?,
                 overview: row.get(3)?,
                 description: row.get(4)?,
-                key_commits: serde_json::from_str(row.get::<_, String>(5)?.as_str()).unwrap_or_default(),
-                related_commits: serde_json::from_str(row.get::<_, String>(6)?.as_str()).unwrap_or_default(),
-                technical_details: serde_json::from_str(row.get::<_, String>(7)?.as_str()).unwrap_or_default(),
-                resume_bullets: serde_json::from_str(row.get::<_, String>(8)?.as_str()).unwrap_or_default(),
-                category: row.get(9)?,
-                priority: row.get::<_, i32>(10)? as u8,
+                key_commits: Vec::new(),
+                related_commits: Vec::new(),
+                technical_details: serde_json::from_str(row.get::<_, String>(5)?.as_str()).unwrap_or_default(),
+                resume_bullets: serde_json::from_str(row.get::<_, String>(6)?.as_str()).unwrap_or_default(),
+                category: row.get(7)?,
+                priority: row.get::<_, i32>(8)? as u8,
             })
         })?;
 
         let mut contributions = Vec::new();
         for row in rows {
-            contributions.push(row?);
+            let mut contrib = row?;
+            self.load_listed_commits(&mut contrib)?;
+            contributions.push(contrib);
         }
         Ok(contributions)
     }
 
+    /// Fill in the key and related commits a contribution lists, in order
+    fn load_listed_commits(&self, contrib: &mut Contribution) -> Result<()> {
+        let Some(id) = contrib.id else { return Ok(()) };
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT commit_hash, role FROM contribution_commits
+             WHERE contribution_id = ?1 AND source = 'hash' ORDER BY position"
+        )?;
+        let rows = stmt.query_map(params![id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        for row in rows {
+            let (hash, role) = row?;
+            match CommitRole::from_str(&role) {
+                CommitRole::Key => contrib.key_commits.push(hash),
+                CommitRole::Related => contrib.related_commits.push(hash),
+            }
+        }
+        Ok(())
+    }
+
     pub fn get_contribution(&self, repo_url: &str, name: &str) -> Result<Option<Contribution>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, repository_url, name, overview, description, key_commits, 
-             related_commits, technical_details, resume_bullets, category, priority
+            "SELECT id, repository_url, name, overview, description,
+             technical_details, resume_bullets, category, priority
              FROM contributions WHERE repository_url = ?1 AND name = ?2"
         )?;
 
@@ -447,47 +1413,233 @@ impl Database {
                 name: row.get(2)?,
                 overview: row.get(3)?,
                 description: row.get(4)?,
-                key_commits: serde_json::from_str(row.get::<_, String>(5)?.as_str()).unwrap_or_default(),
-                related_commits: serde_json::from_str(row.get::<_, String>(6)?.as_str()).unwrap_or_default(),
-                technical_details: serde_json::from_str(row.get::<_, String>(7)?.as_str()).unwrap_or_default(),
-                resume_bullets: serde_json::from_str(row.get::<_, String>(8)?.as_str()).unwrap_or_default(),
-                category: row.get(9)?,
-                priority: row.get::<_, i32>(10)? as u8,
+                key_commits: Vec::new(),
+                related_commits: Vec::new(),
+                technical_details: serde_json::from_str(row.get::<_, String>(5)?.as_str()).unwrap_or_default(),
+                resume_bullets: serde_json::from_str(row.get::<_, String>(6)?.as_str()).unwrap_or_default(),
+                category: row.get(7)?,
+                priority: row.get::<_, i32>(8)? as u8,
             })
         });
 
         match result {
-            Ok(contrib) => Ok(Some(contrib)),
+            Ok(mut contrib) => {
+                self.load_listed_commits(&mut contrib)?;
+                Ok(Some(contrib))
+            }
             Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
             Err(e) => Err(e.into()),
         }
     }
 
     pub fn get_commits_for_contribution(&self, repo_url: &str, contrib_name: &str) -> Result<Vec<Commit>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT cm.commit_hash, cm.repository_url, cm.contribution_id, cm.author, 
-             cm.author_email, cm.date, cm.message, cm.files_changed, cm.lines_added, cm.lines_deleted
-             FROM commits cm
-             JOIN contributions c ON cm.contribution_id = c.id
-             WHERE c.repository_url = ?1 AND c.name = ?2
-             ORDER BY cm.date DESC"
+        self.get_commits_for_contribution_in(repo_url, contrib_name, &DateRange::default())
+    }
+
+    /// A contribution's commits authored within `range`, newest first
+    pub fn get_commits_for_contribution_in(
+        &self,
+        repo_url: &str,
+        contrib_name: &str,
+        range: &DateRange,
+    ) -> Result<Vec<Commit>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "{}
+             SELECT cm.commit_hash, cm.repository_url, cm.contribution_id, cm.author, 
+             cm.author_email, cm.date, cm.message, cm.files_changed, cm.lines_added, cm.lines_deleted,
+             m.link_source, cm.meaningful_lines_added, cm.meaningful_lines_deleted, cm.released_in,
+             cm.surviving_lines, cm.author_time, cm.author_offset, cm.committer_time, cm.committer_offset,
+             cm.patch_id, cm.inverse_patch_id, cm.reverted_by,
+             (SELECT reverted_hash FROM commit_reverts WHERE revert_hash = cm.commit_hash LIMIT 1),
+             m.role
+             FROM members m
+             JOIN commits cm ON cm.commit_hash = m.commit_hash
+             WHERE (?3 IS NULL OR cm.author_time >= ?3)
+               AND (?4 IS NULL OR cm.author_time <= ?4)
+             ORDER BY cm.date DESC",
+            CONTRIBUTION_MEMBERS
+        ))?;
+
+        let rows = stmt.query_map(params![repo_url, contrib_name, range.since, range.until], |row| {
+            let mut commit = commit_from_row(row)?;
+            commit.role = row.get::<_, Option<String>>(23)?.map(|role| CommitRole::from_str(&role));
+            Ok(commit)
+        })?;
+
+        let mut commits = Vec::new();
+        for row in rows {
+            let mut commit = row?;
+            commit.files = self.get_commit_files(&commit.hash)?;
+            commit.authors = self.get_commit_authors(&commit.hash)?;
+            commits.push(commit);
+        }
+        Ok(commits)
+    }
+
+    /// People named on a commit: the author first, then co-authors, then the committer
+    pub fn get_commit_authors(&self, commit_hash: &str) -> Result<Vec<CommitAuthor>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT name, email, role FROM commit_authors WHERE commit_hash = ?1
+             ORDER BY CASE role WHEN 'author' THEN 0 WHEN 'co-author' THEN 1 ELSE 2 END, name"
         )?;
+        let rows = stmt.query_map(params![commit_hash], |row| {
+            Ok(CommitAuthor {
+                name: row.get(0)?,
+                email: row.get(1)?,
+                role: AuthorRole::from_str(&row.get::<_, String>(2)?),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
 
+    pub fn get_commit_files(&self, commit_hash: &str) -> Result<Vec<CommitFile>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT old_path, new_path, status, lines_added, lines_deleted, is_binary, is_excluded
+             FROM commit_files WHERE commit_hash = ?1
+             ORDER BY COALESCE(new_path, old_path)"
+        )?;
+        let rows = stmt.query_map(params![commit_hash], |row| {
+            Ok(CommitFile {
+                old_path: row.get(0)?,
+                new_path: row.get(1)?,
+                status: FileStatus::from_code(&row.get::<_, String>(2)?),
+                lines_added: row.get(3)?,
+                lines_deleted: row.get(4)?,
+                binary: row.get(5)?,
+                excluded: row.get(6)?,
+            })
+        })?;
+
+        let mut files = Vec::new();
+        for row in rows {
+            files.push(row?);
+        }
+        Ok(files)
+    }
+
+    /// Lines changed per path across a contribution's commits, most changed first.
+    /// Renamed files are reported under their new path.
+    pub fn get_contribution_file_churn(&self, repo_url: &str, contrib_name: &str) -> Result<Vec<FileChurn>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "{}
+             SELECT COALESCE(f.new_path, f.old_path) AS path, COUNT(DISTINCT f.commit_hash),
+                    SUM(f.lines_added), SUM(f.lines_deleted)
+             FROM commit_files f
+             JOIN members m ON m.commit_hash = f.commit_hash
+             GROUP BY path
+             ORDER BY SUM(f.lines_added) + SUM(f.lines_deleted) DESC, path",
+            CONTRIBUTION_MEMBERS
+        ))?;
         let rows = stmt.query_map(params![repo_url, contrib_name], |row| {
-            Ok(Commit {
-                hash: row.get(0)?,
-                repository_url: row.get(1)?,
-                contribution_id: row.get(2)?,
-                author: row.get(3)?,
-                author_email: row.get(4)?,
-                date: row.get(5)?,
-                message: row.get(6)?,
-                files_changed: serde_json::from_str(row.get::<_, String>(7)?.as_str()).unwrap_or_default(),
-                lines_added: row.get(8)?,
-                lines_deleted: row.get(9)?,
+            Ok(FileChurn {
+                path: row.get(0)?,
+                commits: row.get(1)?,
+                lines_added: row.get(2)?,
+                lines_deleted: row.get(3)?,
             })
         })?;
 
+        let mut churn = Vec::new();
+        for row in rows {
+            churn.push(row?);
+        }
+        Ok(churn)
+    }
+
+    /// Tips of the refs the last update walked, `HEAD` included, by ref name
+    pub fn get_ref_tips(&self, repo_url: &str) -> Result<Vec<(String, String)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT ref_name, tip_hash FROM ref_tips WHERE repository_url = ?1 ORDER BY ref_name"
+        )?;
+        let rows = stmt.query_map(params![repo_url], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        let mut tips = Vec::new();
+        for row in rows {
+            tips.push(row?);
+        }
+        Ok(tips)
+    }
+
+    /// Record ref tips by ref name. Tips of refs this update did not walk are
+    /// kept while the ref exists; tips of refs not in `existing` are forgotten.
+    /// `HEAD` is always kept.
+    pub fn merge_ref_tips(&self, repo_url: &str, tips: &[(String, String)], existing: &HashSet<String>) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO ref_tips (repository_url, ref_name, tip_hash) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(repository_url, ref_name) DO UPDATE SET tip_hash = excluded.tip_hash"
+            )?;
+            for (name, tip) in tips {
+                stmt.execute(params![repo_url, name, tip])?;
+            }
+        }
+        let names: Vec<String> = {
+            let mut stmt = tx.prepare("SELECT ref_name FROM ref_tips WHERE repository_url = ?1")?;
+            let rows = stmt.query_map(params![repo_url], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        for name in names {
+            if name != "HEAD" && !existing.contains(&name) {
+                tx.execute(
+                    "DELETE FROM ref_tips WHERE repository_url = ?1 AND ref_name = ?2",
+                    params![repo_url, name],
+                )?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Record where a repository is checked out, unless a path is already known
+    pub fn set_local_path_if_unknown(&self, repo_url: &str, path: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE repositories SET local_path = ?2 WHERE repository_url = ?1 AND local_path IS NULL",
+            params![repo_url, path],
+        )?;
+        Ok(())
+    }
+
+    /// Every commit of a repository, oldest first
+    pub fn get_repository_commits(&self, repo_url: &str) -> Result<Vec<Commit>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT commit_hash, repository_url, contribution_id, author, author_email, date,
+             message, files_changed, lines_added, lines_deleted, link_source,
+             meaningful_lines_added, meaningful_lines_deleted, released_in, surviving_lines,
+             author_time, author_offset, committer_time, committer_offset,
+             patch_id, inverse_patch_id, reverted_by,
+             (SELECT reverted_hash FROM commit_reverts WHERE revert_hash = commits.commit_hash LIMIT 1)
+             FROM commits
+             WHERE repository_url = ?1
+             ORDER BY date ASC"
+        )?;
+        let rows = stmt.query_map(params![repo_url], commit_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Hash and message of every commit in a repository
+    pub fn get_commit_messages(&self, repo_url: &str) -> Result<Vec<(String, String)>> {
+        let mut stmt = self.conn.prepare("SELECT commit_hash, message FROM commits WHERE repository_url = ?1")?;
+        let rows = stmt.query_map(params![repo_url], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Commits not linked to any contribution, oldest first
+    pub fn get_unassigned_commits(&self, repo_url: &str) -> Result<Vec<Commit>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT commit_hash, repository_url, contribution_id, author,
+             author_email, date, message, files_changed, lines_added, lines_deleted, link_source,
+             meaningful_lines_added, meaningful_lines_deleted, released_in, surviving_lines,
+             author_time, author_offset, committer_time, committer_offset,
+             patch_id, inverse_patch_id, reverted_by,
+             (SELECT reverted_hash FROM commit_reverts WHERE revert_hash = commits.commit_hash LIMIT 1)
+             FROM commits
+             WHERE repository_url = ?1 AND contribution_id IS NULL
+             ORDER BY date ASC"
+        )?;
+
+        let rows = stmt.query_map(params![repo_url], commit_from_row)?;
+
         let mut commits = Vec::new();
         for row in rows {
             commits.push(row?);
@@ -495,9 +1647,31 @@ impl Database {
         Ok(commits)
     }
 
+    pub fn get_repository(&self, repo_url: &str) -> Result<Option<Repository>> {
+        let result = self.conn.query_row(
+            "SELECT repository_url, organization, name, description, local_path FROM repositories WHERE repository_url = ?1",
+            params![repo_url],
+            |row| {
+                Ok(Repository {
+                    url: row.get(0)?,
+                    organization: row.get(1)?,
+                    name: row.get(2)?,
+                    description: row.get(3)?,
+                    local_path: row.get(4)?,
+                })
+            },
+        );
+
+        match result {
+            Ok(repo) => Ok(Some(repo)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     pub fn get_all_repositories(&self) -> Result<Vec<Repository>> {
         let mut stmt = self.conn.prepare(
-            "SELECT repository_url, organization, name, description FROM repositories ORDER BY name"
+            "SELECT repository_url, organization, name, description, local_path FROM repositories ORDER BY name"
         )?;
 
         let rows = stmt.query_map([], |row| {
@@ -506,6 +1680,7 @@ impl Database {
                 organization: row.get(1)?,
                 name: row.get(2)?,
                 description: row.get(3)?,
+                local_path: row.get(4)?,
             })
         })?;
 
@@ -516,7 +1691,6 @@ impl Database {
         Ok(repos)
     }
 
-    #[allow(dead_code)]
     pub fn get_contribution_id(&self, repo_url: &str, name: &str) -> Result<Option<i64>> {
         let result: Result<i64, _> = self.conn.query_row(
             "SELECT id FROM contributions WHERE repository_url = ?1 AND name = ?2",
@@ -540,6 +1714,17 @@ impl Database {
             self.conn.query_row("SELECT COUNT(*) FROM contributions", [], |row| row.get(0))?);
         stats.insert("commits".to_string(), 
             self.conn.query_row("SELECT COUNT(*) FROM commits", [], |row| row.get(0))?);
+        // Cherry-picks and rebased copies of a change share its patch-id
+        stats.insert("distinct_changes".to_string(), 
+            self.conn.query_row(
+                "SELECT COUNT(*) FROM (SELECT DISTINCT repository_url, COALESCE(patch_id, commit_hash) FROM commits)",
+                [],
+                |row| row.get(0),
+            )?);
+        stats.insert("reverted_commits".to_string(), 
+            self.conn.query_row("SELECT COUNT(*) FROM commits WHERE reverted_by IS NOT NULL", [], |row| row.get(0))?);
+        stats.insert("reverts".to_string(), 
+            self.conn.query_row("SELECT COUNT(DISTINCT revert_hash) FROM commit_reverts", [], |row| row.get(0))?);
         stats.insert("agent_rules".to_string(), 
             self.conn.query_row("SELECT COUNT(*) FROM agent_rules", [], |row| row.get(0))?);
         stats.insert("prompts".to_string(), 
@@ -548,6 +1733,105 @@ impl Database {
         Ok(stats)
     }
 
+    /// Create an identity, or return the id of the existing one with this name
+    pub fn add_identity(&self, name: &str) -> Result<i64> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO identities (name) VALUES (?1)",
+            params![name],
+        )?;
+        Ok(self.conn.query_row(
+            "SELECT id FROM identities WHERE name = ?1",
+            params![name],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Add a name and/or email to an identity. Returns false if it was already present.
+    pub fn add_identity_alias(&self, identity_id: i64, name: Option<&str>, email: Option<&str>) -> Result<bool> {
+        if name.is_none() && email.is_none() {
+            return Ok(false);
+        }
+        let inserted = self.conn.execute(
+            "INSERT OR IGNORE INTO identity_aliases (identity_id, name, email) VALUES (?1, ?2, ?3)",
+            params![identity_id, name.unwrap_or(""), email.unwrap_or("")],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    pub fn get_identities(&self) -> Result<Vec<Identity>> {
+        let mut stmt = self.conn.prepare("SELECT id, name FROM identities ORDER BY name")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut identities = Vec::new();
+        for row in rows {
+            let (id, name) = row?;
+            identities.push(Identity {
+                id,
+                name,
+                aliases: self.get_identity_aliases(id)?,
+            });
+        }
+        Ok(identities)
+    }
+
+    fn get_identity_aliases(&self, identity_id: i64) -> Result<Vec<IdentityAlias>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT name, email FROM identity_aliases WHERE identity_id = ?1 ORDER BY name, email"
+        )?;
+        let rows = stmt.query_map(params![identity_id], |row| {
+            let name: String = row.get(0)?;
+            let email: String = row.get(1)?;
+            Ok(IdentityAlias {
+                name: (!name.is_empty()).then_some(name),
+                email: (!email.is_empty()).then_some(email),
+            })
+        })?;
+
+        let mut aliases = Vec::new();
+        for row in rows {
+            aliases.push(row?);
+        }
+        Ok(aliases)
+    }
+
+    /// Find the identity whose name, or any alias name or email, equals `query` (case-insensitive)
+    pub fn find_identity(&self, query: &str) -> Result<Option<Identity>> {
+        let result: Result<i64, _> = self.conn.query_row(
+            "SELECT id FROM identities WHERE name = ?1
+             UNION
+             SELECT identity_id FROM identity_aliases WHERE name = ?1 OR email = ?1
+             LIMIT 1",
+            params![query.trim()],
+            |row| row.get(0),
+        );
+
+        let id = match result {
+            Ok(id) => id,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let name: String = self.conn.query_row(
+            "SELECT name FROM identities WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+        Ok(Some(Identity {
+            id,
+            name,
+            aliases: self.get_identity_aliases(id)?,
+        }))
+    }
+
+    /// Resolve an `--author` value through the identities table, falling back
+    /// to an exact name or email match
+    pub fn resolve_author_filter(&self, query: &str) -> Result<AuthorFilter> {
+        Ok(match self.find_identity(query)? {
+            Some(identity) => AuthorFilter::for_identity(&identity),
+            None => AuthorFilter::exact(query),
+        })
+    }
+
     /// Get all unique organizations from repositories
     #[allow(dead_code)]
     pub fn get_all_organizations(&self) -> Result<Vec<(String, Option<String>)>> {
@@ -599,6 +1883,8 @@ impl Database {
                     organization: repo.organization,
                     name: repo.name,
                     description: repo.description,
+                    aliases: Vec::new(),
+                    path: repo.local_path,
                 },
             );
         }
@@ -621,6 +1907,7 @@ impl Database {
                 organization: repo_config.organization.clone(),
                 name: repo_config.name.clone(),
                 description: repo_config.description.clone(),
+                local_path: repo_config.path.clone(),
             };
             self.add_repository(&repo)?;
         }
@@ -850,3 +2137,502 @@ impl Database {
     }
 }
 
+#[cfg(test)]
+mod migration_tests {
+    use super::*;
+    use crate::migrations::latest_version;
+    use tempfile::TempDir;
+
+    /// The schema contrack v0.0.1 created, frozen here so that edits to the
+    /// migrations cannot change what the upgrade tests start from
+    const V0_0_1_SCHEMA: &str = "
+        CREATE TABLE repositories (
+            repository_url TEXT PRIMARY KEY,
+            organization TEXT NOT NULL,
+            name TEXT NOT NULL,
+            description TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE contributions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            repository_url TEXT NOT NULL,
+            name TEXT NOT NULL,
+            overview TEXT,
+            description TEXT,
+            key_commits TEXT,
+            related_commits TEXT,
+            technical_details TEXT,
+            resume_bullets TEXT,
+            category TEXT,
+            priority INTEGER DEFAULT 0,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (repository_url) REFERENCES repositories(repository_url),
+            UNIQUE(repository_url, name)
+        );
+        CREATE TABLE commits (
+            commit_hash TEXT PRIMARY KEY,
+            repository_url TEXT NOT NULL,
+            contribution_id INTEGER,
+            author TEXT NOT NULL,
+            author_email TEXT,
+            date TEXT NOT NULL,
+            message TEXT,
+            files_changed TEXT,
+            lines_added INTEGER,
+            lines_deleted INTEGER,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (repository_url) REFERENCES repositories(repository_url),
+            FOREIGN KEY (contribution_id) REFERENCES contributions(id)
+        );
+        CREATE TABLE agent_rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            instruction TEXT NOT NULL,
+            priority INTEGER DEFAULT 0,
+            category TEXT,
+            examples TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE prompts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            prompt_text TEXT NOT NULL,
+            description TEXT,
+            category TEXT,
+            variables TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE loadouts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            is_default INTEGER DEFAULT 0,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE loadout_prompts (
+            loadout_id INTEGER NOT NULL,
+            prompt_id INTEGER NOT NULL,
+            PRIMARY KEY (loadout_id, prompt_id),
+            FOREIGN KEY (loadout_id) REFERENCES loadouts(id) ON DELETE CASCADE,
+            FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
+        );
+        CREATE TABLE loadout_rules (
+            loadout_id INTEGER NOT NULL,
+            rule_id INTEGER NOT NULL,
+            PRIMARY KEY (loadout_id, rule_id),
+            FOREIGN KEY (loadout_id) REFERENCES loadouts(id) ON DELETE CASCADE,
+            FOREIGN KEY (rule_id) REFERENCES agent_rules(id) ON DELETE CASCADE
+        );
+        CREATE INDEX idx_contributions_repo ON contributions(repository_url);
+        CREATE INDEX idx_commits_repo ON commits(repository_url);
+        CREATE INDEX idx_commits_contribution ON commits(contribution_id);
+    ";
+
+    /// Build a database the way contrack v0.0.1 did: the initial schema
+    /// created directly, with no `user_version` recorded
+    fn create_v0_0_1_database(path: &Path) {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(V0_0_1_SCHEMA).unwrap();
+        conn.execute(
+            "INSERT INTO repositories (repository_url, organization, name) VALUES ('https://github.com/org/repo', 'org', 'repo')",
+            [],
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO contributions (repository_url, name, overview, description, key_commits, related_commits,
+             technical_details, resume_bullets, category, priority)
+             VALUES ('https://github.com/org/repo', 'Feature', 'o', 'd', '[\"abc123\"]', '[\"def456\", \"abc123\"]',
+                     '{}', '[]', 'Feature', 5)",
+            [],
+        ).unwrap();
+    }
+
+    #[test]
+    fn test_migrates_v0_0_1_database() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("contributions.db");
+        create_v0_0_1_database(&path);
+
+        let db = Database::open_at(&path).unwrap();
+        assert_eq!(db.schema_version().unwrap(), latest_version());
+        assert!(db.pending_migrations().unwrap().is_empty());
+
+        // Existing data survives the upgrade
+        let contrib = db.get_contribution("https://github.com/org/repo", "Feature").unwrap().unwrap();
+        assert_eq!(contrib.key_commits, vec!["abc123".to_string()]);
+        assert_eq!(contrib.related_commits, vec!["def456".to_string()]);
+
+        // Tables added by later migrations exist
+        assert!(db.get_sync_state("https://github.com/org/repo").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_pending_migrations_without_applying() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("contributions.db");
+        create_v0_0_1_database(&path);
+
+        let db = Database::open_without_migrating(&path).unwrap();
+        assert_eq!(db.schema_version().unwrap(), 0);
+        let pending: Vec<i64> = db.pending_migrations().unwrap().iter().map(|m| m.version).collect();
+        let all: Vec<i64> = MIGRATIONS.iter().map(|m| m.version).collect();
+        assert_eq!(pending, all);
+
+        // Inspecting pending migrations leaves the schema untouched
+        assert_eq!(db.schema_version().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_migrations_are_ordered_and_idempotent() {
+        let versions: Vec<i64> = MIGRATIONS.iter().map(|m| m.version).collect();
+        let mut sorted = versions.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(versions, sorted);
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("contributions.db");
+        Database::open_at(&path).unwrap();
+        let db = Database::open_at(&path).unwrap();
+        assert_eq!(db.schema_version().unwrap(), latest_version());
+        assert!(db.migrate().unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod contribution_tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const REPO: &str = "https://github.com/org/repo";
+
+    fn open_test_db(dir: &TempDir) -> Database {
+        let db = Database::open_at(&dir.path().join("contributions.db")).unwrap();
+        db.add_repository(&Repository {
+            url: REPO.to_string(),
+            organization: "org".to_string(),
+            name: "repo".to_string(),
+            description: None,
+            local_path: None,
+        }).unwrap();
+        db
+    }
+
+    fn contribution(name: &str, key_commits: &[&str]) -> Contribution {
+        Contribution {
+            id: None,
+            repository_url: REPO.to_string(),
+            name: name.to_string(),
+            overview: "overview".to_string(),
+            description: "description".to_string(),
+            key_commits: key_commits.iter().map(|s| s.to_string()).collect(),
+            related_commits: Vec::new(),
+            technical_details: HashMap::new(),
+            resume_bullets: Vec::new(),
+            category: "Feature".to_string(),
+            priority: 5,
+        }
+    }
+
+    fn commit(hash: &str) -> Commit {
+        Commit {
+            hash: hash.to_string(),
+            repository_url: REPO.to_string(),
+            author: "Ann".to_string(),
+            author_email: "ann@example.com".to_string(),
+            date: "2024-01-01T00:00:00+00:00".to_string(),
+            message: "message".to_string(),
+            lines_added: Some(1),
+            lines_deleted: Some(0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_add_contribution_again_keeps_id_and_links() {
+        let dir = TempDir::new().unwrap();
+        let db = open_test_db(&dir);
+
+        let id = db.add_contribution(&contribution("Feature", &["abc"])).unwrap();
+        db.add_commit(&commit("abc123")).unwrap();
+        assert_eq!(db.link_commits_to_contributions(REPO).unwrap(), 1);
+
+        let mut changed = contribution("Feature", &["abc"]);
+        changed.overview = "new overview".to_string();
+        assert_eq!(db.add_contribution(&changed).unwrap(), id);
+
+        let commits = db.get_commits_for_contribution(REPO, "Feature").unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(db.get_contribution(REPO, "Feature").unwrap().unwrap().overview, "new overview");
+    }
+
+    #[test]
+    fn test_rename_contribution_in_place() {
+        let dir = TempDir::new().unwrap();
+        let db = open_test_db(&dir);
+
+        let id = db.add_contribution(&contribution("Old", &["abc"])).unwrap();
+        db.add_commit(&commit("abc123")).unwrap();
+        db.link_commits_to_contributions(REPO).unwrap();
+
+        let mut contrib = db.get_contribution(REPO, "Old").unwrap().unwrap();
+        contrib.name = "New".to_string();
+        db.update_contribution(&contrib).unwrap();
+
+        assert!(db.get_contribution(REPO, "Old").unwrap().is_none());
+        assert_eq!(db.get_contribution_id(REPO, "New").unwrap(), Some(id));
+        assert_eq!(db.get_commits_for_contribution(REPO, "New").unwrap().len(), 1);
+
+        // Renaming onto an existing name is rejected
+        db.add_contribution(&contribution("Other", &[])).unwrap();
+        contrib.name = "Other".to_string();
+        assert!(db.update_contribution(&contrib).is_err());
+    }
+
+    #[test]
+    fn test_delete_contribution_detach_and_cascade() {
+        let dir = TempDir::new().unwrap();
+        let db = open_test_db(&dir);
+
+        let detached = db.add_contribution(&contribution("Detached", &["abc"])).unwrap();
+        let cascaded = db.add_contribution(&contribution("Cascaded", &["def"])).unwrap();
+        db.add_commit(&commit("abc123")).unwrap();
+        db.add_commit(&commit("def456")).unwrap();
+        db.link_commits_to_contributions(REPO).unwrap();
+        db.set_sync_state(REPO, "def456", &HashMap::new()).unwrap();
+
+        assert_eq!(db.delete_contribution(detached, false).unwrap(), (0, 1));
+        assert!(db.get_sync_state(REPO).unwrap().is_some());
+        assert_eq!(db.delete_contribution(cascaded, true).unwrap(), (1, 0));
+        // The next update reads the deleted commits again
+        assert!(db.get_sync_state(REPO).unwrap().is_none());
+
+        assert!(db.get_contributions(REPO).unwrap().is_empty());
+        let remaining = db.get_commit_hashes(REPO).unwrap();
+        assert!(remaining.contains("abc123"));
+        assert!(!remaining.contains("def456"));
+    }
+
+    #[test]
+    fn test_re_extracted_commit_replaces_its_files_and_authors() {
+        let dir = TempDir::new().unwrap();
+        let db = open_test_db(&dir);
+
+        let mut first = commit("abc123");
+        first.files = vec![CommitFile {
+            old_path: None,
+            new_path: Some("src/lib.rs".to_string()),
+            status: FileStatus::Added,
+            lines_added: 1,
+            lines_deleted: 0,
+            binary: false,
+            excluded: false,
+        }];
+        first.authors = vec![CommitAuthor {
+            name: "Ann".to_string(),
+            email: "ann@example.com".to_string(),
+            role: AuthorRole::Author,
+        }];
+        db.add_commit(&first).unwrap();
+        assert_eq!(db.get_commit_files("abc123").unwrap().len(), 1);
+
+        db.add_commit(&commit("abc123")).unwrap();
+        assert!(db.get_commit_files("abc123").unwrap().is_empty());
+        assert!(db.get_commit_authors("abc123").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_release_attribution_is_replaced_or_added_to() {
+        let dir = TempDir::new().unwrap();
+        let db = open_test_db(&dir);
+        db.add_contribution(&contribution("Feature", &["abc123", "def456", "fed987"])).unwrap();
+        for hash in ["abc123", "def456", "fed987"] {
+            db.add_commit(&commit(hash)).unwrap();
+        }
+        let releases = |db: &Database| -> Vec<(String, Option<String>)> {
+            let mut commits = db.get_commits_for_contribution(REPO, "Feature").unwrap();
+            commits.sort_by(|a, b| a.hash.cmp(&b.hash));
+            commits.into_iter().map(|c| (c.hash, c.released_in)).collect()
+        };
+        let tags = vec![("v1.0".to_string(), "abc123".to_string())];
+        let released = HashMap::from([
+            ("abc123".to_string(), "v1.0".to_string()),
+            ("def456".to_string(), "v1.0".to_string()),
+        ]);
+        assert_eq!(db.set_released_in(REPO, &tags, &released).unwrap(), 2);
+        assert_eq!(db.get_release_tips(REPO).unwrap(), tags);
+
+        // Only the given commits change
+        let added = HashMap::from([("fed987".to_string(), "v1.0".to_string())]);
+        assert_eq!(db.add_released_in(REPO, &added).unwrap(), 1);
+        assert!(releases(&db).iter().all(|(_, release)| release.as_deref() == Some("v1.0")));
+
+        // A full attribution clears commits no longer released
+        assert_eq!(db.set_released_in(REPO, &[], &added).unwrap(), 2);
+        assert!(db.get_release_tips(REPO).unwrap().is_empty());
+        assert_eq!(releases(&db), vec![
+            ("abc123".to_string(), None),
+            ("def456".to_string(), None),
+            ("fed987".to_string(), Some("v1.0".to_string())),
+        ]);
+    }
+
+    #[test]
+    fn test_re_extracted_commit_keeps_computed_columns() {
+        let dir = TempDir::new().unwrap();
+        let db = open_test_db(&dir);
+        let id = db.add_contribution(&contribution("Feature", &[])).unwrap();
+        db.add_commit(&Commit {
+            released_in: Some("v1.0".to_string()),
+            surviving_lines: Some(7),
+            ..commit("abc123")
+        }).unwrap();
+        assert!(db.link_commit("abc123", id, LinkSource::Trailer).unwrap());
+
+        // Extraction leaves these unset; storing again must not clear them
+        db.add_commit(&Commit { message: "reworded".to_string(), ..commit("abc123") }).unwrap();
+        let stored = db.get_commits_for_contribution(REPO, "Feature").unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].message, "reworded");
+        assert_eq!(stored[0].released_in.as_deref(), Some("v1.0"));
+        assert_eq!(stored[0].surviving_lines, Some(7));
+        assert_eq!(stored[0].link_source.as_deref(), Some("trailer"));
+    }
+
+    #[test]
+    fn test_rows_under_raw_origin_url_move_to_registered_repository() {
+        let dir = TempDir::new().unwrap();
+        let db = open_test_db(&dir);
+        let raw = "git@github.com:Org/repo.git";
+        db.add_repository(&Repository {
+            url: raw.to_string(),
+            organization: "org".to_string(),
+            name: "repo".to_string(),
+            description: None,
+            local_path: None,
+        }).unwrap();
+
+        let mut old = commit("abc123");
+        old.repository_url = raw.to_string();
+        db.add_commit(&old).unwrap();
+        db.add_contribution(&Contribution { repository_url: raw.to_string(), ..contribution("Feature", &["abc"]) }).unwrap();
+        db.add_contribution(&Contribution { repository_url: raw.to_string(), ..contribution("Taken", &[]) }).unwrap();
+        db.add_contribution(&contribution("Taken", &[])).unwrap();
+        db.set_sync_state(raw, "abc123", &HashMap::new()).unwrap();
+        assert_eq!(db.get_stored_urls().unwrap(), vec![raw.to_string(), REPO.to_string()]);
+
+        assert_eq!(db.move_repository_rows(raw, REPO).unwrap(), 1);
+        assert_eq!(db.get_stored_urls().unwrap(), vec![REPO.to_string()]);
+        assert!(db.get_commit_hashes(REPO).unwrap().contains("abc123"));
+        assert!(db.get_sync_state(REPO).unwrap().is_some());
+        let names: Vec<String> = db.get_contributions(REPO).unwrap().into_iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["Feature", "Taken"]);
+        assert_eq!(db.get_commits_for_contribution(REPO, "Feature").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_commits_for_contribution_in_date_range() {
+        let dir = TempDir::new().unwrap();
+        let db = open_test_db(&dir);
+
+        db.add_contribution(&contribution("Feature", &["abc", "def"])).unwrap();
+        for (hash, time) in [("abc123", 1_704_067_200), ("def456", 1_709_251_200)] {
+            db.add_commit(&Commit { author_time: Some(time), author_offset: Some(-300), ..commit(hash) }).unwrap();
+        }
+        db.link_commits_to_contributions(REPO).unwrap();
+
+        let range = DateRange::parse(Some("2024-02-01"), None).unwrap();
+        let commits = db.get_commits_for_contribution_in(REPO, "Feature", &range).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].hash, "def456");
+        assert_eq!(commits[0].author_date(), "2024-02-29T19:00:00-05:00");
+        assert_eq!(db.get_commits_for_contribution(REPO, "Feature").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_key_commit_links_its_equivalents_and_totals_count_them_once() {
+        let dir = TempDir::new().unwrap();
+        let db = open_test_db(&dir);
+
+        db.add_contribution(&contribution("Feature", &["abc"])).unwrap();
+        let with_patch = |hash: &str, patch: &str| Commit {
+            patch_id: Some(patch.to_string()),
+            lines_added: Some(10),
+            ..commit(hash)
+        };
+        db.add_commit(&with_patch("abc123", "p1")).unwrap();
+        db.add_commit(&with_patch("fed987", "p1")).unwrap();
+        db.add_commit(&with_patch("def456", "p2")).unwrap();
+        assert_eq!(db.link_commits_to_contributions(REPO).unwrap(), 2);
+        assert_eq!(db.count_equivalent_commits(REPO).unwrap(), 1);
+        let stats = db.get_statistics().unwrap();
+        assert_eq!((stats["commits"], stats["distinct_changes"]), (3, 2));
+
+        let commits = db.get_commits_for_contribution(REPO, "Feature").unwrap();
+        assert_eq!(commits.len(), 2);
+        let totals = LineTotals::of(&commits);
+        assert_eq!((totals.changes, totals.lines_added), (1, 10));
+    }
+
+    #[test]
+    fn test_commit_listed_by_two_contributions_belongs_to_both() {
+        let dir = TempDir::new().unwrap();
+        let db = open_test_db(&dir);
+
+        let sync = Contribution { priority: 8, ..contribution("Sync", &["abc"]) };
+        db.add_contribution(&sync).unwrap();
+        db.add_contribution(&Contribution {
+            related_commits: vec!["abc123".to_string()],
+            ..contribution("Docs", &["def"])
+        })
+        .unwrap();
+        db.add_commit(&Commit {
+            files: vec![CommitFile {
+                old_path: None,
+                new_path: Some("src/sync.rs".to_string()),
+                status: FileStatus::Added,
+                lines_added: 1,
+                lines_deleted: 0,
+                binary: false,
+                excluded: false,
+            }],
+            ..commit("abc123")
+        })
+        .unwrap();
+        db.add_commit(&commit("def456")).unwrap();
+        db.add_commit(&commit("fed987")).unwrap();
+        assert_eq!(db.link_commits_to_contributions(REPO).unwrap(), 2);
+        let docs_id = db.get_contribution_id(REPO, "Docs").unwrap().unwrap();
+        assert!(db.link_commit("fed987", docs_id, LinkSource::Trailer).unwrap());
+
+        let roles = |name: &str| -> Vec<(String, Option<CommitRole>)> {
+            let mut commits: Vec<_> = db
+                .get_commits_for_contribution(REPO, name)
+                .unwrap()
+                .into_iter()
+                .map(|c| (c.hash, c.role))
+                .collect();
+            commits.sort_by(|a, b| a.0.cmp(&b.0));
+            commits
+        };
+        assert_eq!(roles("Sync"), vec![("abc123".to_string(), Some(CommitRole::Key))]);
+        assert_eq!(
+            roles("Docs"),
+            vec![
+                ("abc123".to_string(), Some(CommitRole::Related)),
+                ("def456".to_string(), Some(CommitRole::Key)),
+                ("fed987".to_string(), None),
+            ]
+        );
+        assert_eq!(db.get_contribution_file_churn(REPO, "Docs").unwrap()[0].path, "src/sync.rs");
+
+        // Removing one contribution with its commits keeps the ones the other lists
+        let sync_id = db.get_contribution_id(REPO, "Sync").unwrap().unwrap();
+        assert_eq!(db.delete_contribution(sync_id, true).unwrap(), (0, 1));
+        assert_eq!(db.link_commits_to_contributions(REPO).unwrap(), 1);
+        assert_eq!(roles("Docs").len(), 3);
+        assert_eq!(db.delete_contribution(docs_id, true).unwrap(), (3, 0));
+    }
+}
@@ -0,0 +1,55 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Git checkouts under `root`, found recursively. A directory holding `.git`
+/// (a directory, or a file for worktrees and submodules) is a checkout, and is
+/// not searched further, so repositories vendored inside it are skipped.
+/// Hidden directories, symlinks and unreadable directories are skipped too.
+pub fn find_repositories(root: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        if dir.join(".git").exists() {
+            found.push(dir);
+            continue;
+        }
+
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if is_dir && !hidden {
+                pending.push(entry.path());
+            }
+        }
+    }
+
+    found.sort();
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_nested_and_hidden_repositories_are_skipped() {
+        let dir = TempDir::new().unwrap();
+        for path in ["work/app", "work/app/vendor/lib", "work/tools/cli", ".cache/clone", "notes"] {
+            fs::create_dir_all(dir.path().join(path)).unwrap();
+        }
+        for repo in ["work/app", "work/app/vendor/lib", "work/tools/cli", ".cache/clone"] {
+            git2::Repository::init(dir.path().join(repo)).unwrap();
+        }
+
+        let found: Vec<PathBuf> = find_repositories(dir.path())
+            .into_iter()
+            .map(|p| p.strip_prefix(dir.path()).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(found, vec![PathBuf::from("work/app"), PathBuf::from("work/tools/cli")]);
+    }
+}
@@ -0,0 +1,286 @@
+use anyhow::{Context, Result};
+use git2::{Repository, Tree};
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::config::{Config, StatsConfig};
+
+/// Lockfiles whose churn never reflects meaningful work
+pub const LOCKFILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "poetry.lock",
+    "Pipfile.lock",
+    "uv.lock",
+    "composer.lock",
+    "Gemfile.lock",
+    "go.sum",
+    "mix.lock",
+    "pubspec.lock",
+    "Podfile.lock",
+    "Package.resolved",
+    "packages.lock.json",
+    "flake.lock",
+];
+
+/// `.gitattributes` attributes that mark a file as not hand-written
+const ATTRIBUTES: &[&str] = &["linguist-generated", "linguist-vendored"];
+
+/// Which files are left out of "meaningful" line counts
+#[derive(Debug, Clone)]
+pub struct ExclusionRules {
+    globs: Vec<Regex>,
+    lockfiles: bool,
+    gitattributes: bool,
+}
+
+impl Default for ExclusionRules {
+    fn default() -> Self {
+        Self {
+            globs: Vec::new(),
+            lockfiles: true,
+            gitattributes: true,
+        }
+    }
+}
+
+impl ExclusionRules {
+    pub fn from_config(config: &StatsConfig) -> Result<Self> {
+        let globs = config
+            .exclude
+            .iter()
+            .map(|g| glob_to_regex(g).with_context(|| format!("Invalid exclude glob '{}' in config.toml", g)))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            globs,
+            lockfiles: config.builtin_lockfiles,
+            gitattributes: config.gitattributes,
+        })
+    }
+
+    /// Rules from config.toml, or the defaults when there is no config file
+    pub fn load() -> Result<Self> {
+        Self::from_config(&Config::load()?.stats)
+    }
+
+    /// Whether a path matches an exclude glob or is a known lockfile
+    pub fn matches_path(&self, path: &str) -> bool {
+        if self.lockfiles {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            if LOCKFILES.contains(&name) {
+                return true;
+            }
+        }
+        self.globs.iter().any(|glob| glob.is_match(path))
+    }
+
+    /// Whether a path's changes are left out of meaningful line counts, by
+    /// path or by its `linguist-generated`/`linguist-vendored` attributes in
+    /// the tree being read
+    pub fn is_excluded(&self, attributes: &TreeAttributes, path: &str) -> bool {
+        self.matches_path(path) || (self.gitattributes && attributes.is_marked(path))
+    }
+}
+
+/// One `.gitattributes` line setting or unsetting an attribute in `ATTRIBUTES`
+#[derive(Debug, Clone)]
+struct AttributeRule {
+    pattern: Regex,
+    /// `Some(true)` when set, `Some(false)` when unset, `None` when reset with `!`
+    value: Option<bool>,
+    attribute: usize,
+}
+
+/// Parse the rules in a `.gitattributes` file that concern `ATTRIBUTES`.
+/// Patterns are matched like exclude globs, relative to the file's directory.
+fn parse_attributes(content: &str) -> Vec<AttributeRule> {
+    let mut rules = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let Some(pattern) = fields.next() else { continue };
+        // Negative patterns are not allowed in .gitattributes
+        if pattern.starts_with('!') {
+            continue;
+        }
+        let Ok(pattern) = glob_to_regex(pattern) else { continue };
+        for field in fields {
+            let (name, value) = if let Some(name) = field.strip_prefix('-') {
+                (name, Some(false))
+            } else if let Some(name) = field.strip_prefix('!') {
+                (name, None)
+            } else if let Some((name, value)) = field.split_once('=') {
+                (name, Some(value.eq_ignore_ascii_case("true")))
+            } else {
+                (field, Some(true))
+            };
+            if let Some(attribute) = ATTRIBUTES.iter().position(|a| *a == name) {
+                rules.push(AttributeRule { pattern: pattern.clone(), value, attribute });
+            }
+        }
+    }
+    rules
+}
+
+/// Whether the last rule, from the root's `.gitattributes` down to the
+/// deepest directory's, sets any of `ATTRIBUTES` for `path`. `files` holds each
+/// directory (`""` for the root) with its rules, outermost first.
+fn marked_by(files: &[(&str, &[AttributeRule])], path: &str) -> bool {
+    let mut values = [None; ATTRIBUTES.len()];
+    for (dir, rules) in files {
+        let relative = match *dir {
+            "" => path,
+            dir => match path.strip_prefix(dir).and_then(|p| p.strip_prefix('/')) {
+                Some(relative) => relative,
+                None => continue,
+            },
+        };
+        for rule in rules.iter().filter(|r| r.pattern.is_match(relative)) {
+            values[rule.attribute] = rule.value;
+        }
+    }
+    values.contains(&Some(true))
+}
+
+/// The `.gitattributes` files of one commit's tree, read as paths are looked
+/// up. Attributes come from the tree being diffed, never the working tree, so
+/// history is classified by the rules it had and bare repositories work too.
+pub struct TreeAttributes<'r> {
+    repo: &'r Repository,
+    tree: Tree<'r>,
+    /// Rules per directory, `""` for the root
+    files: RefCell<HashMap<String, Vec<AttributeRule>>>,
+}
+
+impl<'r> TreeAttributes<'r> {
+    pub fn new(repo: &'r Repository, tree: Tree<'r>) -> Self {
+        Self { repo, tree, files: RefCell::new(HashMap::new()) }
+    }
+
+    fn read_rules(&self, dir: &str) -> Vec<AttributeRule> {
+        self.tree
+            .get_path(&Path::new(dir).join(".gitattributes"))
+            .ok()
+            .and_then(|entry| self.repo.find_blob(entry.id()).ok())
+            .map(|blob| parse_attributes(&String::from_utf8_lossy(blob.content())))
+            .unwrap_or_default()
+    }
+
+    /// Whether `path` is marked `linguist-generated` or `linguist-vendored`
+    pub fn is_marked(&self, path: &str) -> bool {
+        let mut dirs = vec![""];
+        dirs.extend(path.match_indices('/').map(|(i, _)| &path[..i]));
+
+        let mut files = self.files.borrow_mut();
+        for dir in &dirs {
+            if !files.contains_key(*dir) {
+                let rules = self.read_rules(dir);
+                files.insert(dir.to_string(), rules);
+            }
+        }
+        let found: Vec<(&str, &[AttributeRule])> = dirs.iter().map(|dir| (*dir, files[*dir].as_slice())).collect();
+        marked_by(&found, path)
+    }
+}
+
+/// Compile a gitignore-style glob. Globs without a slash match a file or
+/// directory name at any depth; `**` matches across directories; a match on a
+/// directory covers everything inside it.
+fn glob_to_regex(glob: &str) -> Result<Regex> {
+    let anchored = glob.starts_with('/');
+    let glob = glob.trim_start_matches('/').trim_end_matches('/');
+    if glob.is_empty() {
+        anyhow::bail!("empty glob");
+    }
+
+    let mut pattern = String::from("^");
+    if !anchored && !glob.contains('/') {
+        pattern.push_str("(?:.*/)?");
+    }
+
+    let mut chars = glob.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            _ => pattern.push_str(&regex::escape(&ch.to_string())),
+        }
+    }
+    pattern.push_str("(?:/.*)?$");
+
+    Ok(Regex::new(&pattern)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(globs: &[&str]) -> ExclusionRules {
+        ExclusionRules::from_config(&StatsConfig {
+            exclude: globs.iter().map(|g| g.to_string()).collect(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_globs_and_lockfiles() {
+        let rules = rules(&["vendor", "*.pb.go", "/gen/**/*.ts", "docs/api/"]);
+
+        assert!(rules.matches_path("Cargo.lock"));
+        assert!(rules.matches_path("web/package-lock.json"));
+        assert!(rules.matches_path("vendor/github.com/x/y.go"));
+        assert!(rules.matches_path("third_party/vendor/lib.c"));
+        assert!(rules.matches_path("api/service.pb.go"));
+        assert!(rules.matches_path("gen/client/deep/types.ts"));
+        assert!(rules.matches_path("gen/types.ts"));
+        assert!(rules.matches_path("docs/api/index.html"));
+
+        assert!(!rules.matches_path("src/main.rs"));
+        assert!(!rules.matches_path("src/vendors.rs"));
+        assert!(!rules.matches_path("src/gen/types.ts"));
+        assert!(!rules.matches_path("docs/api.md"));
+    }
+
+    #[test]
+    fn test_gitattributes_later_and_deeper_rules_win() {
+        let root = parse_attributes("# generated code\n*.gen.rs linguist-generated\nvendor/** linguist-vendored=true\n");
+        let nested = parse_attributes("keep.gen.rs -linguist-generated\nother.rs text\n");
+        let files: Vec<(&str, &[AttributeRule])> = vec![("", &root), ("src", &nested)];
+
+        assert!(marked_by(&files, "schema.gen.rs"));
+        assert!(marked_by(&files, "src/deep/types.gen.rs"));
+        assert!(marked_by(&files, "vendor/lib/x.c"));
+        assert!(!marked_by(&files, "src/keep.gen.rs"));
+        assert!(!marked_by(&files, "src/other.rs"));
+    }
+
+    #[test]
+    fn test_lockfiles_can_be_disabled() {
+        let rules = ExclusionRules::from_config(&StatsConfig {
+            builtin_lockfiles: false,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!rules.matches_path("Cargo.lock"));
+    }
+}
@@ -1,172 +1,568 @@
 use anyhow::{Context, Result};
-use git2::{Repository, Oid};
-use std::path::PathBuf;
+use git2::{Delta, DiffFindOptions, Mailmap, Oid, Patch, Repository};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, OnceLock};
 
-use crate::database::Commit;
+use crate::database::{AuthorRole, BlameCache, BlameFile, Commit, CommitAuthor, CommitFile, CommitTimes, FileStatus};
+use crate::exclude::{ExclusionRules, TreeAttributes};
+use crate::mbox::PatchEmail;
+use crate::utils::DateRange;
+use crate::release::compare_versions;
 
-pub fn extract_commits_from_repo(repo_path: &PathBuf) -> Result<Vec<Commit>> {
-    let repo = Repository::open(repo_path)
-        .with_context(|| format!("Failed to open git repository at {:?}", repo_path))?;
+/// Options controlling which commits `commits_to_extract` walks and how
+/// `extract_commits` reads them
+#[derive(Debug, Default, Clone)]
+pub struct ExtractOptions {
+    /// Ref tips walked in addition to HEAD
+    pub tips: Vec<String>,
+    /// Commits already synced; they and their ancestors are not walked
+    pub hide: Vec<String>,
+    /// Commits already stored in the database; they are walked but not diffed
+    pub known_commits: HashSet<String>,
+    /// Files left out of the meaningful line counts
+    pub exclusions: ExclusionRules,
+    /// Worker threads computing diffs; 0 uses one per CPU
+    pub jobs: usize,
+    /// Only commits authored within this range are extracted
+    pub range: DateRange,
+}
+
+/// Refs walked by `update` in addition to HEAD
+#[derive(Debug, Default, Clone)]
+pub struct RefSelection {
+    /// Local branch globs, e.g. `feature/*`
+    pub branches: Vec<String>,
+    /// Every ref: branches, remote branches, tags and others
+    pub all_refs: bool,
+    /// Every remote-tracking branch
+    pub remote_branches: bool,
+}
 
-    // Get remote URL for repository identification
-    let remote_url = repo
-        .find_remote("origin")
+impl RefSelection {
+    fn globs(&self) -> Vec<String> {
+        if self.all_refs {
+            return vec!["refs/*".to_string()];
+        }
+        let mut globs: Vec<String> = self.branches.iter().map(|b| format!("refs/heads/{}", b)).collect();
+        if self.remote_branches {
+            globs.push("refs/remotes/*".to_string());
+        }
+        globs
+    }
+}
+
+/// Current state of a repository's HEAD and selected refs, used as the sync watermark
+#[derive(Debug, Clone)]
+pub struct RepoSnapshot {
+    /// The `origin` URL as configured, not normalized
+    pub remote_url: Option<String>,
+    pub head_oid: String,
+    /// Selected refs and the commits they point at
+    pub refs: HashMap<String, String>,
+    /// Every ref name in the repository, selected or not
+    pub existing_refs: HashSet<String>,
+}
+
+fn open_repo(repo_path: &PathBuf) -> Result<Repository> {
+    Repository::open(repo_path)
+        .with_context(|| format!("Failed to open git repository at {:?}", repo_path))
+}
+
+/// The `origin` remote's URL as configured, if there is one
+fn remote_url(repo: &Repository) -> Option<String> {
+    repo.find_remote("origin")
         .ok()
         .and_then(|r| r.url().map(|s| s.to_string()))
-        .unwrap_or_else(|| "unknown".to_string());
+}
+
+/// The `origin` remote's URL of the repository at `repo_path`, if it has one
+pub fn origin_url(repo_path: &PathBuf) -> Result<Option<String>> {
+    Ok(remote_url(&open_repo(repo_path)?))
+}
+
+/// Read the remote URL, HEAD and the selected refs that point at commits
+pub fn snapshot_repo(repo_path: &PathBuf, selection: &RefSelection) -> Result<RepoSnapshot> {
+    let repo = open_repo(repo_path)?;
+
+    let head_oid = repo
+        .head()
+        .context("Failed to resolve HEAD")?
+        .peel_to_commit()
+        .context("HEAD does not point at a commit")?
+        .id()
+        .to_string();
+
+    let mut existing_refs = HashSet::new();
+    for reference in repo.references()? {
+        if let Some(name) = reference?.name() {
+            existing_refs.insert(name.to_string());
+        }
+    }
+
+    let mut refs = HashMap::new();
+    for glob in selection.globs() {
+        for reference in repo.references_glob(&glob)? {
+            let reference = reference?;
+            // Symbolic refs such as refs/remotes/origin/HEAD repeat another ref
+            if reference.kind() == Some(git2::ReferenceType::Symbolic) {
+                continue;
+            }
+            let Some(name) = reference.name() else { continue };
+            if let Ok(commit) = reference.peel_to_commit() {
+                refs.insert(name.to_string(), commit.id().to_string());
+            }
+        }
+    }
+
+    Ok(RepoSnapshot {
+        remote_url: remote_url(&repo),
+        head_oid,
+        refs,
+        existing_refs,
+    })
+}
+
+/// Which recorded ref tips contain a commit. Only the tips are stored, so
+/// membership is worked out from a checkout when commits are read.
+pub struct RefMembership {
+    repo: Repository,
+    tips: Vec<(String, Oid)>,
+}
+
+impl RefMembership {
+    /// Open the checkout at `repo_path` with the tips recorded by `update`.
+    /// Tips the checkout no longer has are ignored.
+    pub fn open(repo_path: &PathBuf, tips: &[(String, String)]) -> Result<Self> {
+        let repo = open_repo(repo_path)?;
+        let tips = tips
+            .iter()
+            .filter_map(|(name, tip)| Oid::from_str(tip).ok().map(|oid| (name.clone(), oid)))
+            .filter(|(_, oid)| repo.find_commit(*oid).is_ok())
+            .collect();
+        Ok(Self { repo, tips })
+    }
+
+    /// Membership for a repository checked out at `local_path`. `None` without
+    /// a checkout or recorded tips, or when the checkout can no longer be opened.
+    pub fn for_checkout(local_path: Option<&str>, tips: &[(String, String)]) -> Option<Self> {
+        if tips.is_empty() {
+            return None;
+        }
+        Self::open(&PathBuf::from(local_path?), tips).ok()
+    }
+
+    /// Names of the refs whose tip is the commit or descends from it
+    pub fn refs_containing(&self, hash: &str) -> Vec<String> {
+        let Ok(oid) = Oid::from_str(hash) else { return Vec::new() };
+        self.tips
+            .iter()
+            .filter(|(_, tip)| *tip == oid || self.repo.graph_descendant_of(*tip, oid).unwrap_or(false))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Fill `refs` on each commit
+    pub fn fill(&self, commits: &mut [Commit]) {
+        for commit in commits {
+            commit.refs = self.refs_containing(&commit.hash);
+        }
+    }
+}
+
+/// Ref name without its namespace: `refs/heads/main` is `main`, `refs/remotes/origin/x` is `origin/x`
+pub fn short_ref_name(name: &str) -> &str {
+    ["refs/heads/", "refs/remotes/", "refs/tags/", "refs/"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+}
+
+/// Contents of the repository's `.mailmap`, from the working tree or else from HEAD
+pub fn read_mailmap(repo_path: &PathBuf) -> Result<Option<String>> {
+    let repo = open_repo(repo_path)?;
+
+    if let Some(workdir) = repo.workdir() {
+        let path = workdir.join(".mailmap");
+        if path.is_file() {
+            return Ok(Some(std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {:?}", path))?));
+        }
+    }
+
+    let Ok(tree) = repo.head().and_then(|head| head.peel_to_tree()) else {
+        return Ok(None);
+    };
+    let Ok(entry) = tree.get_path(std::path::Path::new(".mailmap")) else {
+        return Ok(None);
+    };
+    let blob = entry.to_object(&repo)?.peel_to_blob()?;
+    Ok(Some(String::from_utf8_lossy(blob.content()).to_string()))
+}
+
+/// Commits that `extract_commits` should read: everything reachable from HEAD
+/// and the selected tips, minus hidden history and commits already stored.
+/// Only ids are collected, so this stays small even for large histories.
+pub fn commits_to_extract(repo_path: &PathBuf, options: &ExtractOptions) -> Result<Vec<Oid>> {
+    let repo = open_repo(repo_path)?;
 
-    let mut commits = Vec::new();
     let mut revwalk = repo.revwalk()?;
     revwalk.push_head()?;
+    for tip in &options.tips {
+        revwalk.push(Oid::from_str(tip)?)?;
+    }
 
+    // Skip history that was already synced; tips that were rewritten or
+    // garbage collected no longer exist and are ignored
+    for hidden in &options.hide {
+        if let Ok(oid) = Oid::from_str(hidden) {
+            if repo.find_commit(oid).is_ok() {
+                revwalk.hide(oid)?;
+            }
+        }
+    }
+
+    let mut oids = Vec::new();
     for oid in revwalk {
         let oid = oid?;
-        let commit_obj = repo.find_commit(oid)?;
-        
-        let author = commit_obj.author();
-        let author_name = author.name().unwrap_or("Unknown").to_string();
-        let author_email = author.email().unwrap_or("unknown@example.com").to_string();
-
-        let time = commit_obj.time();
-        let date = chrono::DateTime::<chrono::Utc>::from_timestamp(time.seconds(), 0)
-            .unwrap_or_default()
-            .to_rfc3339();
-
-        let message = commit_obj.message().unwrap_or("").to_string();
-        let hash = oid.to_string();
-
-        // Get diff stats
-        let (lines_added, lines_deleted, files_changed) = if let Ok(tree) = commit_obj.tree() {
-            let parent_tree = commit_obj
-                .parent(0)
-                .ok()
-                .and_then(|p| p.tree().ok());
-            
-            let diff = repo.diff_tree_to_tree(
-                parent_tree.as_ref(),
-                Some(&tree),
-                None,
-            )?;
-
-            let mut added = 0;
-            let mut deleted = 0;
-            let mut files = Vec::new();
-
-            diff.foreach(
-                &mut |delta, _| {
-                    if let Some(path) = delta.new_file().path() {
-                        files.push(path.to_string_lossy().to_string());
-                    }
-                    true
-                },
-                None,
-                None,
-                Some(&mut |_delta, _hunk, line| {
-                    let origin = line.origin();
-                    if origin == '+' {
-                        added += 1;
-                    } else if origin == '-' {
-                        deleted += 1;
+        if options.known_commits.contains(&oid.to_string()) {
+            continue;
+        }
+        if options.range.is_bounded() && !options.range.contains(repo.find_commit(oid)?.author().when().seconds()) {
+            continue;
+        }
+        oids.push(oid);
+    }
+    Ok(oids)
+}
+
+/// Read `oids` on worker threads, storing them under `repository_url`, and
+/// pass each commit to `sink` on the calling thread as soon as it is ready.
+/// Commits arrive in no particular order. At most a few commits per worker
+/// are held in memory at once; a sink error stops the workers.
+pub fn extract_commits(
+    repo_path: &PathBuf,
+    repository_url: &str,
+    oids: &[Oid],
+    options: &ExtractOptions,
+    mut sink: impl FnMut(Commit) -> Result<()>,
+) -> Result<()> {
+    let jobs = worker_count(options.jobs, oids.len());
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::sync_channel::<Result<Commit>>(jobs * 4);
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            let sender = sender.clone();
+            let next = &next;
+            scope.spawn(move || {
+                // Repository handles are not shareable, so each worker opens its own
+                let work = || -> Result<()> {
+                    let repo = open_repo(repo_path)?;
+                    // Signatures are canonicalized through the repository's .mailmap, if any
+                    let mailmap = repo.mailmap().ok();
+                    while let Some(oid) = oids.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let commit_obj = repo.find_commit(*oid)?;
+                        let commit = build_commit(&repo, &commit_obj, repository_url, mailmap.as_ref(), &options.exclusions)?;
+                        if sender.send(Ok(commit)).is_err() {
+                            break; // The sink failed and the receiver is gone
+                        }
                     }
-                    true
-                }),
-            )?;
+                    Ok(())
+                };
+                if let Err(e) = work() {
+                    let _ = sender.send(Err(e));
+                }
+            });
+        }
+        drop(sender);
 
-            (Some(added), Some(deleted), files)
-        } else {
-            (None, None, Vec::new())
-        };
+        for commit in receiver {
+            sink(commit?)?;
+        }
+        Ok(())
+    })
+}
 
-        commits.push(Commit {
-            hash,
-            repository_url: remote_url.clone(),
-            contribution_id: None, // Will be set later
-            author: author_name,
-            author_email,
-            date,
-            message,
-            files_changed,
-            lines_added,
-            lines_deleted,
-        });
+/// Threads to diff with: `jobs`, or one per CPU when 0, but never more than
+/// there are commits
+fn worker_count(jobs: usize, commits: usize) -> usize {
+    let jobs = match jobs {
+        0 => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        n => n,
+    };
+    jobs.min(commits).max(1)
+}
+
+/// Tags matching `pattern` and the commits they point at, in release order:
+/// by commit date, then by version
+pub fn release_tag_tips(repo_path: &PathBuf, pattern: &Regex) -> Result<Vec<(String, String)>> {
+    let repo = open_repo(repo_path)?;
+
+    let mut tags = Vec::new();
+    for name in repo.tag_names(None)?.iter().flatten() {
+        if !pattern.is_match(name) {
+            continue;
+        }
+        let target = repo
+            .revparse_single(&format!("refs/tags/{}", name))
+            .and_then(|object| object.peel_to_commit());
+        if let Ok(commit) = target {
+            tags.push((commit.time().seconds(), name.to_string(), commit.id().to_string()));
+        }
+    }
+    tags.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| compare_versions(&a.1, &b.1)));
+    Ok(tags.into_iter().map(|(_, name, oid)| (name, oid)).collect())
+}
+
+/// The earliest release tag containing each commit, as `git tag --contains`
+/// would report it. `tags` are in release order, as from [`release_tag_tips`],
+/// and each commit is attributed to the first one that reaches it.
+pub fn release_tags(repo_path: &PathBuf, tags: &[(String, String)]) -> Result<HashMap<String, String>> {
+    let repo = open_repo(repo_path)?;
+    let oids = tags.iter().map(|(_, oid)| Oid::from_str(oid)).collect::<Result<Vec<_>, _>>()?;
+
+    // Each walk stops at the earlier releases, so every commit is visited once
+    let mut released = HashMap::new();
+    for (index, (name, _)) in tags.iter().enumerate() {
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(oids[index])?;
+        for earlier in &oids[..index] {
+            revwalk.hide(*earlier)?;
+        }
+        for commit in revwalk {
+            released.entry(commit?.to_string()).or_insert_with(|| name.clone());
+        }
+    }
+
+    Ok(released)
+}
+
+/// The earliest of `tags` containing each of `hashes`, without walking the
+/// tags' history. Commits in no tag are left out.
+pub fn releases_containing(
+    repo_path: &PathBuf,
+    tags: &[(String, String)],
+    hashes: &[String],
+) -> Result<HashMap<String, String>> {
+    let repo = open_repo(repo_path)?;
+    let oids = tags.iter().map(|(_, oid)| Oid::from_str(oid)).collect::<Result<Vec<_>, _>>()?;
+
+    let mut released = HashMap::new();
+    for hash in hashes {
+        let commit = Oid::from_str(hash)?;
+        for ((name, _), tip) in tags.iter().zip(&oids) {
+            if *tip == commit || repo.graph_descendant_of(*tip, commit)? {
+                released.insert(hash.clone(), name.clone());
+                break;
+            }
+        }
+    }
+    Ok(released)
+}
+
+/// Blame every file at HEAD, attributing each line to the commit that last
+/// changed it. Binary and excluded files are skipped. Files whose path and blob
+/// are in `cache` reuse the cached result. Returns the files and how many of
+/// them had to be blamed.
+pub fn blame_head(
+    repo_path: &PathBuf,
+    exclusions: &ExclusionRules,
+    cache: &BlameCache,
+) -> Result<(Vec<BlameFile>, usize)> {
+    let repo = open_repo(repo_path)?;
+    let head = repo.head()?.peel_to_commit()?;
+
+    let mut blobs = Vec::new();
+    head.tree()?.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.kind() == Some(git2::ObjectType::Blob) {
+            if let Some(name) = entry.name() {
+                blobs.push((format!("{}{}", dir, name), entry.id()));
+            }
+        }
+        git2::TreeWalkResult::Ok
+    })?;
+
+    let attributes = TreeAttributes::new(&repo, head.tree()?);
+    let mut files = Vec::new();
+    let mut blamed = 0;
+    for (path, blob_oid) in blobs {
+        if exclusions.is_excluded(&attributes, &path) {
+            continue;
+        }
+        let key = (path, blob_oid.to_string());
+        if let Some(owners) = cache.get(&key) {
+            let (path, blob_oid) = key;
+            files.push(BlameFile { path, blob_oid, owners: owners.clone() });
+            continue;
+        }
+        if repo.find_blob(blob_oid)?.is_binary() {
+            continue;
+        }
+
+        let (path, blob_oid) = key;
+        let mut options = git2::BlameOptions::new();
+        options.newest_commit(head.id());
+        let blame = repo
+            .blame_file(std::path::Path::new(&path), Some(&mut options))
+            .with_context(|| format!("Failed to blame {}", path))?;
+
+        let mut owners: HashMap<String, i64> = HashMap::new();
+        for hunk in blame.iter() {
+            *owners.entry(hunk.final_commit_id().to_string()).or_default() += hunk.lines_in_hunk() as i64;
+        }
+        let mut owners: Vec<(String, i64)> = owners.into_iter().collect();
+        owners.sort();
+
+        blamed += 1;
+        files.push(BlameFile { path, blob_oid, owners });
+    }
+
+    Ok((files, blamed))
+}
+
+/// Commits brought in by each merge reachable from HEAD: everything reachable
+/// from the merged parent but not from the mainline parent. Each group is
+/// labelled with the merged branch name when the merge message names one.
+pub fn merge_groups(repo_path: &PathBuf) -> Result<Vec<(Option<String>, Vec<String>)>> {
+    let repo = open_repo(repo_path)?;
+
+    let mut groups = Vec::new();
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_head()?;
+
+    for oid in revwalk {
+        let merge = repo.find_commit(oid?)?;
+        if merge.parent_count() < 2 {
+            continue;
+        }
+
+        let mut branch = repo.revwalk()?;
+        branch.push(merge.parent_id(1)?)?;
+        branch.hide(merge.parent_id(0)?)?;
+
+        let mut hashes = vec![merge.id().to_string()];
+        for oid in branch {
+            hashes.push(oid?.to_string());
+        }
+        groups.push((merged_branch_name(merge.message().unwrap_or("")), hashes));
     }
 
-    Ok(commits)
+    Ok(groups)
+}
+
+/// Branch name from "Merge branch 'x'" or "Merge pull request #1 from owner/x"
+fn merged_branch_name(message: &str) -> Option<String> {
+    let subject = message.lines().next()?;
+    if let Some(rest) = subject.strip_prefix("Merge branch '") {
+        return rest.split('\'').next().map(|s| s.to_string());
+    }
+    if subject.starts_with("Merge pull request") {
+        let from = subject.split(" from ").nth(1)?;
+        let branch = from.split_once('/').map(|(_, b)| b).unwrap_or(from);
+        return Some(branch.trim().to_string());
+    }
+    None
 }
 
 #[allow(dead_code)]
 pub fn get_commit_details(commit_hash: &str, repo_path: &PathBuf) -> Result<Option<Commit>> {
-    let repo = Repository::open(repo_path)
-        .with_context(|| format!("Failed to open git repository at {:?}", repo_path))?;
+    let repo = open_repo(repo_path)?;
 
     let oid = Oid::from_str(commit_hash)
         .with_context(|| format!("Invalid commit hash: {}", commit_hash))?;
 
     let commit_obj = repo.find_commit(oid)?;
+    let remote_url = remote_url(&repo).unwrap_or_else(|| "unknown".to_string());
+    let mailmap = repo.mailmap().ok();
 
-    let remote_url = repo
-        .find_remote("origin")
-        .ok()
-        .and_then(|r| r.url().map(|s| s.to_string()))
-        .unwrap_or_else(|| "unknown".to_string());
+    Ok(Some(build_commit(&repo, &commit_obj, &remote_url, mailmap.as_ref(), &ExclusionRules::load()?)?))
+}
 
-    let author = commit_obj.author();
+fn build_commit(
+    repo: &Repository,
+    commit_obj: &git2::Commit,
+    repository_url: &str,
+    mailmap: Option<&Mailmap>,
+    exclusions: &ExclusionRules,
+) -> Result<Commit> {
+    let author = match mailmap {
+        Some(mailmap) => commit_obj.author_with_mailmap(mailmap)?,
+        None => commit_obj.author(),
+    };
     let author_name = author.name().unwrap_or("Unknown").to_string();
     let author_email = author.email().unwrap_or("unknown@example.com").to_string();
+    let committer = match mailmap {
+        Some(mailmap) => commit_obj.committer_with_mailmap(mailmap)?,
+        None => commit_obj.committer(),
+    };
 
-    let time = commit_obj.time();
-    let date = chrono::DateTime::<chrono::Utc>::from_timestamp(time.seconds(), 0)
+    // Both times keep the offset they were recorded in; `date` is the author time in UTC
+    let (author_time, committer_time) = (author.when(), committer.when());
+    let date = chrono::DateTime::<chrono::Utc>::from_timestamp(author_time.seconds(), 0)
         .unwrap_or_default()
         .to_rfc3339();
 
     let message = commit_obj.message().unwrap_or("").to_string();
+    let hash = commit_obj.id().to_string();
+
+    let mut authors = vec![CommitAuthor {
+        name: author_name.clone(),
+        email: author_email.clone(),
+        role: AuthorRole::Author,
+    }];
+    for (name, email) in co_authors(&message, mailmap) {
+        let known = authors.iter().any(|a| a.email.eq_ignore_ascii_case(&email));
+        if !known {
+            authors.push(CommitAuthor { name, email, role: AuthorRole::CoAuthor });
+        }
+    }
+    authors.push(CommitAuthor {
+        name: committer.name().unwrap_or("Unknown").to_string(),
+        email: committer.email().unwrap_or("").to_string(),
+        role: AuthorRole::Committer,
+    });
 
-    // Get diff stats (simplified)
-    let (lines_added, lines_deleted, files_changed) = if let Ok(tree) = commit_obj.tree() {
+    // Per-file changes, with renames and copies detected
+    let mut patch_ids = None;
+    let (lines_added, lines_deleted, meaningful, files) = if let Ok(tree) = commit_obj.tree() {
         let parent_tree = commit_obj
             .parent(0)
             .ok()
             .and_then(|p| p.tree().ok());
-        
-        let diff = repo.diff_tree_to_tree(
+
+        let mut diff = repo.diff_tree_to_tree(
             parent_tree.as_ref(),
             Some(&tree),
             None,
         )?;
 
-        let mut added = 0;
-        let mut deleted = 0;
-        let mut files = Vec::new();
+        // Patch-ids are taken before rename detection, from the diff and its
+        // inverse, so that a revert's patch-id equals its target's inverse one
+        if commit_obj.parent_count() <= 1 && diff.deltas().len() > 0 {
+            let inverse = repo.diff_tree_to_tree(Some(&tree), parent_tree.as_ref(), None)?;
+            patch_ids = Some((diff.patchid(None)?.to_string(), inverse.patchid(None)?.to_string()));
+        }
+        diff.find_similar(Some(DiffFindOptions::new().renames(true).copies(true)))?;
 
-        diff.foreach(
-            &mut |delta, _| {
-                if let Some(path) = delta.new_file().path() {
-                    files.push(path.to_string_lossy().to_string());
-                }
-                true
-            },
-            None,
-            None,
-            Some(&mut |_delta, _hunk, line| {
-                let origin = line.origin();
-                if origin == '+' {
-                    added += 1;
-                } else if origin == '-' {
-                    deleted += 1;
-                }
-                true
-            }),
-        )?;
-
-        (Some(added), Some(deleted), files)
+        let attributes = TreeAttributes::new(repo, tree.clone());
+        let files = diff_files(&diff, |path| exclusions.is_excluded(&attributes, path))?;
+        let added = files.iter().map(|f| f.lines_added).sum();
+        let deleted = files.iter().map(|f| f.lines_deleted).sum();
+        let kept = files.iter().filter(|f| !f.excluded);
+        let meaningful = (kept.clone().map(|f| f.lines_added).sum(), kept.map(|f| f.lines_deleted).sum());
+        (Some(added), Some(deleted), Some(meaningful), files)
     } else {
-        (None, None, Vec::new())
+        (None, None, None, Vec::new())
     };
+    let files_changed = files.iter().map(|f| f.path().to_string()).collect();
 
-    Ok(Some(Commit {
-        hash: commit_hash.to_string(),
-        repository_url: remote_url,
-        contribution_id: None,
+    Ok(Commit {
+        hash,
+        repository_url: repository_url.to_string(),
+        contribution_id: None, // Will be set later
         author: author_name,
         author_email,
         date,
@@ -174,6 +570,877 @@ pub fn get_commit_details(commit_hash: &str, repo_path: &PathBuf) -> Result<Opti
         files_changed,
         lines_added,
         lines_deleted,
-    }))
+        meaningful_lines_added: meaningful.map(|(added, _)| added),
+        meaningful_lines_deleted: meaningful.map(|(_, deleted)| deleted),
+        link_source: None,
+        released_in: None,
+        surviving_lines: None,
+        author_time: Some(author_time.seconds()),
+        author_offset: Some(author_time.offset_minutes()),
+        committer_time: Some(committer_time.seconds()),
+        committer_offset: Some(committer_time.offset_minutes()),
+        patch_id: patch_ids.as_ref().map(|(forward, _)| forward.clone()),
+        inverse_patch_id: patch_ids.map(|(_, inverse)| inverse),
+        reverts: None,
+        reverted_by: None,
+        role: None,
+        refs: Vec::new(),
+        files,
+        authors,
+    })
+}
+
+/// The same record `extract_commits` makes, from a patch in a `format-patch`
+/// mailbox. Names go through `mailmap` as for extracted commits. Patches carry
+/// no committer, and exclusions cannot consult `.gitattributes`, since there is
+/// no tree to read it from.
+pub fn commit_from_patch(
+    patch: &PatchEmail,
+    repository_url: &str,
+    mailmap: Option<&Mailmap>,
+    exclusions: &ExclusionRules,
+) -> Result<Commit> {
+    let diff = git2::Diff::from_buffer(patch.diff.as_bytes())
+        .with_context(|| format!("Cannot read the diff of patch {}", patch.hash))?;
+    let files = diff_files(&diff, |path| exclusions.matches_path(path))?;
+    let patch_id = (diff.deltas().len() > 0).then(|| diff.patchid(None)).transpose()?;
+    // The inverse patch-id lets a later revert of this patch be recognised
+    let inverse_patch_id = match reverse_patch(&patch.diff).filter(|_| patch_id.is_some()) {
+        Some(reversed) => Some(git2::Diff::from_buffer(reversed.as_bytes())?.patchid(None)?.to_string()),
+        None => None,
+    };
+
+    let (author, author_email) = mailmap
+        .and_then(|mailmap| {
+            let signature = git2::Signature::new(&patch.author, &patch.author_email, &git2::Time::new(0, 0)).ok()?;
+            let resolved = mailmap.resolve_signature(&signature).ok()?;
+            Some((resolved.name()?.to_string(), resolved.email()?.to_string()))
+        })
+        .unwrap_or_else(|| (patch.author.clone(), patch.author_email.clone()));
+
+    let mut authors = vec![CommitAuthor {
+        name: author.clone(),
+        email: author_email.clone(),
+        role: AuthorRole::Author,
+    }];
+    for (name, email) in co_authors(&patch.message, mailmap) {
+        if !authors.iter().any(|a| a.email.eq_ignore_ascii_case(&email)) {
+            authors.push(CommitAuthor { name, email, role: AuthorRole::CoAuthor });
+        }
+    }
+
+    let kept = files.iter().filter(|f| !f.excluded);
+    Ok(Commit {
+        hash: patch.hash.clone(),
+        repository_url: repository_url.to_string(),
+        contribution_id: None,
+        author,
+        author_email,
+        date: patch.date.with_timezone(&chrono::Utc).to_rfc3339(),
+        message: patch.message.clone(),
+        files_changed: files.iter().map(|f| f.path().to_string()).collect(),
+        lines_added: Some(files.iter().map(|f| f.lines_added).sum()),
+        lines_deleted: Some(files.iter().map(|f| f.lines_deleted).sum()),
+        meaningful_lines_added: Some(kept.clone().map(|f| f.lines_added).sum()),
+        meaningful_lines_deleted: Some(kept.map(|f| f.lines_deleted).sum()),
+        link_source: None,
+        released_in: None,
+        surviving_lines: None,
+        author_time: Some(patch.date.timestamp()),
+        author_offset: Some(patch.date.offset().local_minus_utc() / 60),
+        committer_time: None,
+        committer_offset: None,
+        patch_id: patch_id.map(|id| id.to_string()),
+        inverse_patch_id,
+        reverts: None,
+        reverted_by: None,
+        role: None,
+        refs: Vec::new(),
+        files,
+        authors,
+    })
+}
+
+/// A unified diff hunk header, e.g. `@@ -1,3 +1,4 @@ fn main()`
+static HUNK_HEADER: OnceLock<Regex> = OnceLock::new();
+
+/// The patch that undoes a `git diff` patch: sides, modes and hunks swapped,
+/// with removed lines ahead of added ones as git writes them. `None` for
+/// binary patches, which cannot be reversed from their text.
+fn reverse_patch(diff: &str) -> Option<String> {
+    let hunk_header = HUNK_HEADER.get_or_init(|| {
+        Regex::new(r"^@@ -(\d+(?:,(\d+))?) \+(\d+(?:,(\d+))?) @@(.*)$").expect("hunk header pattern is valid")
+    });
+    let count = |value: Option<regex::Match>| value.map_or(Some(1), |m| m.as_str().parse::<usize>().ok());
+    let side = |path: &str, prefix: &str| match path.split_once('/') {
+        Some((_, rest)) if path != "/dev/null" => format!("{}/{}", prefix, rest),
+        _ => path.to_string(),
+    };
+
+    let mut out: Vec<String> = Vec::new();
+    let (mut removed, mut added): (Vec<String>, Vec<String>) = (Vec::new(), Vec::new());
+    // Lines left in the current hunk on the old and new side
+    let (mut old_left, mut new_left) = (0usize, 0usize);
+    // Whether the last changed line went to `removed`, for `\ No newline` markers
+    let mut last_removed = false;
+    let (mut old_file, mut old_mode, mut rename_from) = (None, None, None);
+
+    for line in diff.lines() {
+        if old_left > 0 || new_left > 0 {
+            match line.chars().next() {
+                Some('+') => {
+                    removed.push(format!("-{}", &line[1..]));
+                    new_left = new_left.saturating_sub(1);
+                    last_removed = true;
+                }
+                Some('-') => {
+                    added.push(format!("+{}", &line[1..]));
+                    old_left = old_left.saturating_sub(1);
+                    last_removed = false;
+                }
+                Some('\\') if !removed.is_empty() || !added.is_empty() => {
+                    let list = if last_removed { &mut removed } else { &mut added };
+                    list.push(line.to_string());
+                }
+                _ => {
+                    out.append(&mut removed);
+                    out.append(&mut added);
+                    out.push(line.to_string());
+                    old_left = old_left.saturating_sub(1);
+                    new_left = new_left.saturating_sub(1);
+                }
+            }
+            continue;
+        }
+        out.append(&mut removed);
+        out.append(&mut added);
+
+        if line.starts_with("GIT binary patch") || line.starts_with("Binary files ") {
+            return None;
+        } else if let Some(caps) = hunk_header.captures(line) {
+            old_left = count(caps.get(2))?;
+            new_left = count(caps.get(4))?;
+            out.push(format!("@@ -{} +{} @@{}", &caps[3], &caps[1], &caps[5]));
+        } else if let Some(paths) = line.strip_prefix("diff --git ") {
+            let (old, new) = paths.rsplit_once(" b/")?;
+            out.push(format!("diff --git a/{} b/{}", new, old.strip_prefix("a/")?));
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            old_file = Some(path.to_string());
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            out.push(format!("--- {}", side(path, "a")));
+            out.push(format!("+++ {}", side(&old_file.take()?, "b")));
+        } else if let Some(mode) = line.strip_prefix("new file mode ") {
+            out.push(format!("deleted file mode {}", mode));
+        } else if let Some(mode) = line.strip_prefix("deleted file mode ") {
+            out.push(format!("new file mode {}", mode));
+        } else if let Some(mode) = line.strip_prefix("old mode ") {
+            old_mode = Some(mode.to_string());
+        } else if let Some(mode) = line.strip_prefix("new mode ") {
+            out.push(format!("old mode {}", mode));
+            out.push(format!("new mode {}", old_mode.take()?));
+        } else if let Some(path) = line.strip_prefix("rename from ") {
+            rename_from = Some(path.to_string());
+        } else if let Some(path) = line.strip_prefix("rename to ") {
+            out.push(format!("rename from {}", path));
+            out.push(format!("rename to {}", rename_from.take()?));
+        } else if let Some(rest) = line.strip_prefix("index ") {
+            let (range, mode) = rest.split_once(' ').map_or((rest, None), |(r, m)| (r, Some(m)));
+            let (from, to) = range.split_once("..")?;
+            let mode = mode.map(|m| format!(" {}", m)).unwrap_or_default();
+            out.push(format!("index {}..{}{}", to, from, mode));
+        } else {
+            out.push(line.to_string());
+        }
+    }
+    out.append(&mut removed);
+    out.append(&mut added);
+
+    let mut reversed = out.join("\n");
+    reversed.push('\n');
+    Some(reversed)
+}
+
+/// A `git bundle` unpacked into a temporary bare repository, removed on drop
+#[derive(Debug)]
+pub struct UnpackedBundle {
+    pub path: PathBuf,
+}
+
+impl Drop for UnpackedBundle {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Unpack a bundle made by `git bundle create` into a temporary bare
+/// repository with the bundle's refs and HEAD. Bundles that depend on commits
+/// they do not contain (made from a range such as `main~10..main`) are refused.
+pub fn unpack_bundle(bundle: &Path) -> Result<UnpackedBundle> {
+    use std::io::BufRead;
+
+    let file = std::fs::File::open(bundle).with_context(|| format!("Cannot open bundle {:?}", bundle))?;
+    let mut reader = std::io::BufReader::new(file);
+    let mut read_line = || -> Result<String> {
+        let mut line = Vec::new();
+        reader.read_until(b'\n', &mut line)?;
+        Ok(String::from_utf8_lossy(&line).trim_end_matches('\n').to_string())
+    };
+
+    let signature = read_line()?;
+    if signature != "# v2 git bundle" && signature != "# v3 git bundle" {
+        anyhow::bail!("{:?} is not a git bundle", bundle);
+    }
+    let mut refs = Vec::new();
+    let mut prerequisites = 0;
+    loop {
+        let line = read_line()?;
+        if line.is_empty() {
+            break;
+        } else if line.starts_with('-') {
+            prerequisites += 1;
+        } else if let Some((oid, name)) = line.split_once(' ') {
+            refs.push((Oid::from_str(oid)?, name.to_string()));
+        }
+        // `@capability` lines of v3 bundles need no handling for SHA-1 repositories
+    }
+    if prerequisites > 0 {
+        anyhow::bail!(
+            "Bundle {:?} depends on {} commits it does not contain; create a complete bundle with \
+             'git bundle create <file> --all'",
+            bundle,
+            prerequisites
+        );
+    }
+
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_nanos();
+    let unpacked = UnpackedBundle {
+        path: std::env::temp_dir().join(format!("contrack-bundle-{}-{}", std::process::id(), nanos)),
+    };
+    let repo = Repository::init_bare(&unpacked.path)?;
+    {
+        let odb = repo.odb()?;
+        let mut writer = odb.packwriter()?;
+        std::io::copy(&mut reader, &mut writer).with_context(|| format!("Cannot read the pack in {:?}", bundle))?;
+        writer.commit()?;
+    }
+
+    let mut head = None;
+    for (oid, name) in &refs {
+        if name == "HEAD" {
+            head = Some(*oid);
+        } else if name.starts_with("refs/") {
+            repo.reference(name, *oid, true, "contrack: unpack bundle")?;
+        }
+    }
+    // HEAD follows the branch it names in the bundle, or the first branch
+    let branch = refs
+        .iter()
+        .filter(|(oid, name)| name.starts_with("refs/heads/") && head.is_none_or(|head| head == *oid))
+        .map(|(_, name)| name)
+        .next();
+    match (branch, head) {
+        (Some(branch), _) => repo.set_head(branch)?,
+        (None, Some(head)) => repo.set_head_detached(head)?,
+        (None, None) => anyhow::bail!("Bundle {:?} has no branches", bundle),
+    }
+    Ok(unpacked)
+}
+
+/// Every commit reachable from HEAD or any ref
+pub fn reachable_commits(repo_path: &PathBuf) -> Result<HashSet<String>> {
+    let repo = open_repo(repo_path)?;
+    let mut revwalk = repo.revwalk()?;
+    if let Ok(head) = repo.head().and_then(|h| h.peel_to_commit()) {
+        revwalk.push(head.id())?;
+    }
+    for reference in repo.references()? {
+        // Refs to trees or blobs have no history
+        if let Ok(commit) = reference?.peel_to_commit() {
+            revwalk.push(commit.id())?;
+        }
+    }
+
+    let mut hashes = HashSet::new();
+    for oid in revwalk {
+        hashes.insert(oid?.to_string());
+    }
+    Ok(hashes)
 }
 
+/// A commit by full or abbreviated hash, read even when no ref reaches it any
+/// more: the reflog keeps rewritten commits around until it expires
+pub fn find_commit(repo_path: &PathBuf, repository_url: &str, hash: &str) -> Result<Option<Commit>> {
+    let repo = open_repo(repo_path)?;
+    let Ok(commit_obj) = repo.revparse_single(hash).and_then(|object| object.peel_to_commit()) else {
+        return Ok(None);
+    };
+    let mailmap = repo.mailmap().ok();
+    Ok(Some(build_commit(&repo, &commit_obj, repository_url, mailmap.as_ref(), &ExclusionRules::load()?)?))
+}
+
+/// Amended or rebased commits and the commits that replaced them, from the
+/// reflogs of HEAD and every local branch. A finished rebase maps each commit
+/// it took off a branch to the commit it put on with the same subject line.
+pub fn reflog_rewrites(repo_path: &PathBuf) -> Result<HashMap<String, String>> {
+    let repo = open_repo(repo_path)?;
+    let mut names = vec!["HEAD".to_string()];
+    for reference in repo.references_glob("refs/heads/*")? {
+        if let Some(name) = reference?.name() {
+            names.push(name.to_string());
+        }
+    }
+
+    let mut rewrites = HashMap::new();
+    for name in names {
+        let Ok(reflog) = repo.reflog(&name) else { continue };
+        for entry in reflog.iter() {
+            let (old, new) = (entry.id_old(), entry.id_new());
+            if old.is_zero() || old == new {
+                continue;
+            }
+            // `rebase (finish)`, `rebase -i (finish)` and `pull --rebase (finish)`
+            // move the branch from its old tip to its new one
+            let message = entry.message().unwrap_or("");
+            if message.starts_with("commit (amend)") {
+                rewrites.insert(old.to_string(), new.to_string());
+            } else if message.split_once(" (finish)").is_some_and(|(command, _)| command.contains("rebase")) {
+                rewrites.extend(rebase_rewrites(&repo, old, new));
+            }
+        }
+    }
+    Ok(rewrites)
+}
+
+/// Pair the commits a rebase took off a branch with the ones it put on, by
+/// subject line; commits the reflog no longer keeps are skipped
+fn rebase_rewrites(repo: &Repository, old_tip: Oid, new_tip: Oid) -> Vec<(String, String)> {
+    let only_in = |tip: Oid, other: Oid| -> Vec<(String, String)> {
+        let Ok(mut revwalk) = repo.revwalk() else { return Vec::new() };
+        if revwalk.push(tip).is_err() || revwalk.hide(other).is_err() {
+            return Vec::new();
+        }
+        revwalk
+            .filter_map(|oid| repo.find_commit(oid.ok()?).ok())
+            .map(|commit| (commit.id().to_string(), commit.summary().unwrap_or("").to_string()))
+            .collect()
+    };
+
+    let mut added = only_in(new_tip, old_tip);
+    let mut pairs = Vec::new();
+    for (hash, subject) in only_in(old_tip, new_tip) {
+        if let Some(i) = added.iter().position(|(_, s)| *s == subject) {
+            pairs.push((hash, added.remove(i).0));
+        }
+    }
+    pairs
+}
+
+/// Author and committer times with their UTC offsets, for commits stored
+/// before they were recorded; hashes no longer in the repository are skipped
+pub fn commit_times(repo_path: &PathBuf, hashes: &[String]) -> Result<HashMap<String, CommitTimes>> {
+    let repo = open_repo(repo_path)?;
+    let mut times = HashMap::new();
+    for hash in hashes {
+        let Some(commit) = Oid::from_str(hash).ok().and_then(|oid| repo.find_commit(oid).ok()) else {
+            continue;
+        };
+        let (author, committer) = (commit.author().when(), commit.committer().when());
+        times.insert(hash.clone(), CommitTimes {
+            author_time: author.seconds(),
+            author_offset: author.offset_minutes(),
+            committer_time: committer.seconds(),
+            committer_offset: committer.offset_minutes(),
+        });
+    }
+    Ok(times)
+}
+
+/// People named in `Co-authored-by: Name <email>` trailers, canonicalized
+/// through the mailmap
+fn co_authors(message: &str, mailmap: Option<&Mailmap>) -> Vec<(String, String)> {
+    let Ok(trailers) = git2::message_trailers_strs(message) else {
+        return Vec::new();
+    };
+
+    trailers
+        .iter()
+        .filter(|(key, _)| key.eq_ignore_ascii_case("Co-authored-by"))
+        .filter_map(|(_, value)| parse_person(value))
+        .map(|(name, email)| {
+            let resolved = mailmap.and_then(|mailmap| {
+                let signature = git2::Signature::new(&name, &email, &git2::Time::new(0, 0)).ok()?;
+                let resolved = mailmap.resolve_signature(&signature).ok()?;
+                Some((resolved.name()?.to_string(), resolved.email()?.to_string()))
+            });
+            resolved.unwrap_or((name, email))
+        })
+        .collect()
+}
+
+/// Split `Name <email>` into its parts
+pub fn parse_person(value: &str) -> Option<(String, String)> {
+    let (name, rest) = value.rsplit_once('<')?;
+    let email = rest.strip_suffix('>').unwrap_or(rest).trim();
+    let name = name.trim();
+    if email.is_empty() {
+        return None;
+    }
+    let name = if name.is_empty() { email } else { name };
+    Some((name.to_string(), email.to_string()))
+}
+
+/// One `CommitFile` per delta, with line counts from its patch
+fn diff_files(diff: &git2::Diff, is_excluded: impl Fn(&str) -> bool) -> Result<Vec<CommitFile>> {
+    let path = |file: git2::DiffFile| file.path().map(|p| p.to_string_lossy().to_string());
+
+    let mut files = Vec::new();
+    for (index, delta) in diff.deltas().enumerate() {
+        let status = match delta.status() {
+            Delta::Added => FileStatus::Added,
+            Delta::Deleted => FileStatus::Deleted,
+            Delta::Renamed => FileStatus::Renamed,
+            Delta::Copied => FileStatus::Copied,
+            _ => FileStatus::Modified,
+        };
+
+        let patch = Patch::from_diff(diff, index)?;
+        let (lines_added, lines_deleted) = match &patch {
+            Some(patch) => {
+                let (_, added, deleted) = patch.line_stats()?;
+                (added as i32, deleted as i32)
+            }
+            None => (0, 0),
+        };
+        // Generating the patch loads the content, which settles the binary flag
+        let binary = patch
+            .as_ref()
+            .map(|p| p.delta().flags().is_binary())
+            .unwrap_or_else(|| delta.flags().is_binary());
+
+        let mut file = CommitFile {
+            old_path: (status != FileStatus::Added).then(|| path(delta.old_file())).flatten(),
+            new_path: (status != FileStatus::Deleted).then(|| path(delta.new_file())).flatten(),
+            status,
+            lines_added,
+            lines_deleted,
+            binary,
+            excluded: false,
+        };
+        file.excluded = is_excluded(file.path());
+        files.push(file);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Extract every commit `options` selects
+    fn extract(path: &PathBuf, options: &ExtractOptions) -> Vec<Commit> {
+        let oids = commits_to_extract(path, options).unwrap();
+        let mut commits = Vec::new();
+        extract_commits(path, "https://example.com/repo", &oids, options, |commit| {
+            commits.push(commit);
+            Ok(())
+        })
+        .unwrap();
+        commits
+    }
+
+    /// Commit an empty tree change on top of `parent`, updating `refname`
+    fn commit(repo: &Repository, refname: &str, message: &str, parents: &[&git2::Commit]) -> Oid {
+        let signature = git2::Signature::now("Ann", "ann@example.com").unwrap();
+        let tree = repo.find_tree(repo.treebuilder(None).unwrap().write().unwrap()).unwrap();
+        repo.commit(Some(refname), &signature, &signature, message, &tree, parents).unwrap()
+    }
+
+    #[test]
+    fn test_selected_branches_are_walked() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let base = commit(&repo, "HEAD", "base", &[]);
+        let base = repo.find_commit(base).unwrap();
+        let feature = commit(&repo, "refs/heads/feature/sync", "feature work", &[&base]);
+        commit(&repo, "refs/heads/other", "other work", &[&base]);
+
+        let path = dir.path().to_path_buf();
+        let selection = RefSelection {
+            branches: vec!["feature/*".to_string()],
+            ..Default::default()
+        };
+        let snapshot = snapshot_repo(&path, &selection).unwrap();
+        assert_eq!(snapshot.refs.keys().collect::<Vec<_>>(), vec!["refs/heads/feature/sync"]);
+
+        let options = ExtractOptions {
+            tips: snapshot.refs.values().cloned().collect(),
+            ..Default::default()
+        };
+        let messages: HashSet<String> = extract(&path, &options)
+            .into_iter()
+            .map(|c| c.message)
+            .collect();
+        assert_eq!(messages, HashSet::from(["base".to_string(), "feature work".to_string()]));
+
+        // Membership is worked out from the recorded tips
+        let tips = vec![
+            ("HEAD".to_string(), snapshot.head_oid.clone()),
+            ("refs/heads/feature/sync".to_string(), feature.to_string()),
+        ];
+        let membership = RefMembership::open(&path, &tips).unwrap();
+        assert_eq!(membership.refs_containing(&base.id().to_string()), vec!["HEAD", "refs/heads/feature/sync"]);
+        assert_eq!(membership.refs_containing(&feature.to_string()), vec!["refs/heads/feature/sync"]);
+    }
+
+    #[test]
+    fn test_finished_rebase_maps_old_commits_to_new_ones() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let base = commit(&repo, "refs/heads/feature", "base", &[]);
+        let base = repo.find_commit(base).unwrap();
+        let first = commit(&repo, "refs/heads/feature", "Add sync", &[&base]);
+        let first_commit = repo.find_commit(first).unwrap();
+        let second = commit(&repo, "refs/heads/feature", "Add retries", &[&first_commit]);
+
+        // Replay both commits on top of upstream work, as a rebase does
+        let upstream = commit(&repo, "refs/heads/upstream", "upstream", &[&base]);
+        let upstream = repo.find_commit(upstream).unwrap();
+        let first_new = commit(&repo, "refs/heads/scratch", "Add sync", &[&upstream]);
+        let first_new_commit = repo.find_commit(first_new).unwrap();
+        let second_new = commit(&repo, "refs/heads/scratch", "Add retries", &[&first_new_commit]);
+        repo.reference("refs/heads/feature", second_new, true, "rebase (finish): refs/heads/feature onto upstream")
+            .unwrap();
+
+        let rewrites = reflog_rewrites(&dir.path().to_path_buf()).unwrap();
+        assert_eq!(rewrites.get(&first.to_string()), Some(&first_new.to_string()));
+        assert_eq!(rewrites.get(&second.to_string()), Some(&second_new.to_string()));
+    }
+
+    /// Commit a tree holding exactly `files` on top of HEAD
+    fn commit_tree(repo: &Repository, message: &str, files: &[(&str, &[u8])]) -> Oid {
+        let mut builder = repo.treebuilder(None).unwrap();
+        for (name, content) in files {
+            let blob = repo.blob(content).unwrap();
+            builder.insert(name, blob, 0o100644).unwrap();
+        }
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+        let signature = git2::Signature::now("Ann", "ann@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).unwrap()
+    }
+
+    #[test]
+    fn test_renames_and_deletes_are_recorded_per_file() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let body: String = (0..20).map(|i| format!("line {}\n", i)).collect();
+        commit_tree(&repo, "add", &[("old.rs", body.as_bytes()), ("gone.txt", b"bye\n"), ("logo.png", b"\x89PNG\0\0")]);
+        let renamed = format!("{}line 20\n", body);
+        let oid = commit_tree(&repo, "move", &[("new.rs", renamed.as_bytes()), ("logo.png", b"\x89PNG\0\x01")]);
+
+        let commit = get_commit_details(&oid.to_string(), &dir.path().to_path_buf()).unwrap().unwrap();
+        let by_path = |path: &str| commit.files.iter().find(|f| f.path() == path).unwrap();
+
+        let moved = by_path("new.rs");
+        assert_eq!(moved.status, FileStatus::Renamed);
+        assert_eq!(moved.old_path.as_deref(), Some("old.rs"));
+        assert_eq!((moved.lines_added, moved.lines_deleted), (1, 0));
+
+        let deleted = by_path("gone.txt");
+        assert_eq!(deleted.status, FileStatus::Deleted);
+        assert_eq!(deleted.new_path, None);
+
+        assert!(by_path("logo.png").binary);
+        assert_eq!((commit.lines_added, commit.lines_deleted), (Some(1), Some(1)));
+        assert!(commit.files_changed.contains(&"gone.txt".to_string()));
+    }
+
+    #[test]
+    fn test_parallel_extraction_reads_each_commit_once() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let mut expected = HashSet::new();
+        for i in 0..25 {
+            let content = format!("{}\n", i);
+            expected.insert(commit_tree(&repo, &format!("commit {}", i), &[("n.txt", content.as_bytes())]).to_string());
+        }
+
+        let path = dir.path().to_path_buf();
+        let options = ExtractOptions { jobs: 4, ..Default::default() };
+        let hashes: Vec<String> = extract(&path, &options).into_iter().map(|c| c.hash).collect();
+        assert_eq!(hashes.len(), 25);
+        assert_eq!(hashes.into_iter().collect::<HashSet<_>>(), expected);
+
+        // A failing sink stops extraction with its error
+        let oids = commits_to_extract(&path, &options).unwrap();
+        let error = extract_commits(&path, "https://example.com/repo", &oids, &options, |_| anyhow::bail!("disk full"))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "disk full");
+    }
+
+    #[test]
+    fn test_excluded_files_are_left_out_of_meaningful_counts() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_tree(&repo, "base", &[(".gitattributes", b"schema.gen.rs linguist-generated\n")]);
+        let oid = commit_tree(&repo, "deps", &[
+            (".gitattributes", b"schema.gen.rs linguist-generated\n"),
+            ("main.rs", b"fn main() {}\n"),
+            ("Cargo.lock", b"a\nb\nc\n"),
+            ("schema.gen.rs", b"x\ny\n"),
+        ]);
+        // Attributes are read from each commit's tree, not from a later one
+        commit_tree(&repo, "unmark", &[
+            ("main.rs", b"fn main() {}\n"),
+            ("Cargo.lock", b"a\nb\nc\n"),
+            ("schema.gen.rs", b"x\ny\nz\n"),
+        ]);
+
+        let repo_path = dir.path().to_path_buf();
+        let options = ExtractOptions::default();
+        let commit = extract(&repo_path, &options)
+            .into_iter()
+            .find(|c| c.hash == oid.to_string())
+            .unwrap();
+
+        assert_eq!((commit.lines_added, commit.meaningful_lines_added), (Some(6), Some(1)));
+        let excluded: Vec<&str> = commit.files.iter().filter(|f| f.excluded).map(|f| f.path()).collect();
+        assert_eq!(excluded, vec!["Cargo.lock", "schema.gen.rs"]);
+    }
+
+    #[test]
+    fn test_co_authors_are_credited_through_the_mailmap() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        std::fs::write(dir.path().join(".mailmap"), "Bob Jones <bob@example.com> <bob@old.example.com>\n").unwrap();
+        let oid = commit(
+            &repo,
+            "HEAD",
+            "Pair on sync\n\nCo-authored-by: bob <bob@old.example.com>\nCo-authored-by: Ann <ann@example.com>\n",
+            &[],
+        );
+
+        let commit = get_commit_details(&oid.to_string(), &dir.path().to_path_buf()).unwrap().unwrap();
+        let named: Vec<(&str, AuthorRole)> = commit.authors.iter().map(|a| (a.name.as_str(), a.role)).collect();
+        assert_eq!(
+            named,
+            vec![("Ann", AuthorRole::Author), ("Bob Jones", AuthorRole::CoAuthor), ("Ann", AuthorRole::Committer)]
+        );
+        assert_eq!(commit.credited(), vec![("Ann", "ann@example.com"), ("Bob Jones", "bob@example.com")]);
+    }
+
+    #[test]
+    fn test_parse_person() {
+        assert_eq!(parse_person("Ann Lee <ann@example.com>"), Some(("Ann Lee".to_string(), "ann@example.com".to_string())));
+        assert_eq!(parse_person("<ann@example.com>"), Some(("ann@example.com".to_string(), "ann@example.com".to_string())));
+        assert_eq!(parse_person("Ann Lee"), None);
+    }
+
+    #[test]
+    fn test_commits_are_attributed_to_their_first_release() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let tag = |name: &str, oid: Oid| {
+            repo.tag_lightweight(name, &repo.find_object(oid, None).unwrap(), false).unwrap();
+        };
+
+        let first = commit_tree(&repo, "first", &[("a", b"1")]);
+        tag("v1.0", first);
+        let second = commit_tree(&repo, "second", &[("a", b"2")]);
+        tag("nightly", second);
+        let third = commit_tree(&repo, "third", &[("a", b"3")]);
+        tag("v1.1", third);
+        tag("v1.2", third);
+        let unreleased = commit_tree(&repo, "fourth", &[("a", b"4")]);
+
+        let path = dir.path().to_path_buf();
+        let pattern = crate::release::tag_pattern(&Default::default()).unwrap();
+        let tags = release_tag_tips(&path, &pattern).unwrap();
+        let names: Vec<&str> = tags.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["v1.0", "v1.1", "v1.2"]);
+
+        let released = release_tags(&path, &tags).unwrap();
+        assert_eq!(released.get(&first.to_string()).map(|s| s.as_str()), Some("v1.0"));
+        assert_eq!(released.get(&second.to_string()).map(|s| s.as_str()), Some("v1.1"));
+        assert_eq!(released.get(&third.to_string()).map(|s| s.as_str()), Some("v1.1"));
+        assert!(!released.contains_key(&unreleased.to_string()));
+
+        // Looking up single commits agrees with the full walk
+        let hashes: Vec<String> = [first, second, third, unreleased].iter().map(|oid| oid.to_string()).collect();
+        assert_eq!(releases_containing(&path, &tags, &hashes).unwrap(), released);
+    }
+
+    #[test]
+    fn test_blame_attributes_surviving_lines_and_uses_the_cache() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let first = commit_tree(&repo, "first", &[("a.txt", b"1\n2\n3\n"), ("Cargo.lock", b"x\n")]);
+        let second = commit_tree(&repo, "second", &[("a.txt", b"1\ntwo\n3\n"), ("Cargo.lock", b"y\n")]);
+
+        let path = dir.path().to_path_buf();
+        let (files, blamed) = blame_head(&path, &ExclusionRules::default(), &HashMap::new()).unwrap();
+        assert_eq!(blamed, 1);
+        let mut expected = vec![(first.to_string(), 2), (second.to_string(), 1)];
+        expected.sort();
+        assert_eq!(files[0].path, "a.txt");
+        assert_eq!(files[0].owners, expected);
+
+        let cache = HashMap::from([((files[0].path.clone(), files[0].blob_oid.clone()), files[0].owners.clone())]);
+        let (cached, blamed) = blame_head(&path, &ExclusionRules::default(), &cache).unwrap();
+        assert_eq!(blamed, 0);
+        assert_eq!(cached[0].owners, expected);
+    }
+
+    #[test]
+    fn test_author_and_committer_times_keep_their_offsets() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let tree = repo.find_tree(repo.treebuilder(None).unwrap().write().unwrap()).unwrap();
+        // Written in Tokyo in January, rebased in New York in March
+        let author = git2::Signature::new("Ann", "ann@example.com", &git2::Time::new(1_704_100_000, 540)).unwrap();
+        let committer = git2::Signature::new("Bob", "bob@example.com", &git2::Time::new(1_709_300_000, -300)).unwrap();
+        repo.commit(Some("HEAD"), &author, &committer, "rebased", &tree, &[]).unwrap();
+
+        let path = dir.path().to_path_buf();
+        let commits = extract(&path, &ExtractOptions::default());
+        let commit = &commits[0];
+        assert_eq!((commit.author_time, commit.author_offset), (Some(1_704_100_000), Some(540)));
+        assert_eq!((commit.committer_time, commit.committer_offset), (Some(1_709_300_000), Some(-300)));
+        assert_eq!(commit.date, "2024-01-01T09:06:40+00:00");
+        assert_eq!(commit.author_date(), "2024-01-01T18:06:40+09:00");
+
+        // Ranges select by author time, not by when the commit landed
+        let range = |since, until| ExtractOptions {
+            range: DateRange::parse(since, until).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(extract(&path, &range(Some("2024-01-01"), Some("2024-01-31"))).len(), 1);
+        assert!(extract(&path, &range(Some("2024-03-01"), None)).is_empty());
+    }
+
+    #[test]
+    fn test_a_revert_has_its_targets_inverse_patch_id() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_tree(&repo, "base", &[("lib.rs", b"fn a() {}\n")]);
+        let change = commit_tree(&repo, "add b", &[("lib.rs", b"fn a() {}\nfn b() {}\n")]);
+        let revert = commit_tree(&repo, "remove b", &[("lib.rs", b"fn a() {}\n")]);
+
+        let path = dir.path().to_path_buf();
+        let commits = extract(&path, &ExtractOptions::default());
+        let by_hash = |oid: Oid| commits.iter().find(|c| c.hash == oid.to_string()).unwrap();
+        let (change, revert) = (by_hash(change), by_hash(revert));
+        assert!(change.patch_id.is_some());
+        assert_eq!(revert.patch_id, change.inverse_patch_id);
+        assert_ne!(revert.patch_id, change.patch_id);
+    }
+
+    #[test]
+    fn test_cherry_picks_share_a_patch_id() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let base = commit_tree(&repo, "base", &[("lib.rs", b"fn a() {}\n"), ("notes.txt", b"v1\n")]);
+        let fix = commit_tree(&repo, "fix", &[("lib.rs", b"fn a() { fixed() }\n"), ("notes.txt", b"v1\n")]);
+
+        // The same fix applied on a release branch whose notes differ
+        let signature = git2::Signature::now("Ann", "ann@example.com").unwrap();
+        let on_release = |message: &str, lib: &[u8], parent: Oid| {
+            let mut builder = repo.treebuilder(None).unwrap();
+            builder.insert("lib.rs", repo.blob(lib).unwrap(), 0o100644).unwrap();
+            builder.insert("notes.txt", repo.blob(b"v0\n").unwrap(), 0o100644).unwrap();
+            let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+            let parent = repo.find_commit(parent).unwrap();
+            repo.commit(Some("refs/heads/release"), &signature, &signature, message, &tree, &[&parent]).unwrap()
+        };
+        let release = on_release("release notes", b"fn a() {}\n", base);
+        let picked = on_release("fix (cherry picked)", b"fn a() { fixed() }\n", release);
+
+        let path = dir.path().to_path_buf();
+        let options = ExtractOptions { tips: vec![picked.to_string()], ..Default::default() };
+        let commits = extract(&path, &options);
+        let patch_id = |oid: Oid| commits.iter().find(|c| c.hash == oid.to_string()).unwrap().patch_id.clone();
+        assert!(patch_id(fix).is_some());
+        assert_eq!(patch_id(fix), patch_id(picked));
+        assert_ne!(patch_id(fix), patch_id(release));
+    }
+
+    #[test]
+    fn test_a_patch_gives_the_same_record_as_its_commit() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_tree(&repo, "base", &[("lib.rs", b"fn a() {}\n"), ("old.txt", b"bye\n")]);
+        let oid = commit_tree(
+            &repo,
+            "Add b\n\nCo-authored-by: Bob <bob@example.com>\n",
+            &[("lib.rs", b"fn a() {}\nfn b() {}\n"), ("new.txt", b"hi\n")],
+        );
+
+        let path = dir.path().to_path_buf();
+        let extracted = get_commit_details(&oid.to_string(), &path).unwrap().unwrap();
+        let email = git2::Email::from_commit(&repo.find_commit(oid).unwrap(), &mut git2::EmailCreateOptions::new())
+            .unwrap();
+        let patches = crate::mbox::parse_mbox(std::str::from_utf8(email.as_slice()).unwrap()).unwrap();
+        let imported = commit_from_patch(&patches[0], "https://example.com/repo", None, &ExclusionRules::default()).unwrap();
+
+        assert_eq!(imported.hash, extracted.hash);
+        assert_eq!(imported.message, extracted.message);
+        assert_eq!(imported.author_time, extracted.author_time);
+        assert_eq!(imported.patch_id, extracted.patch_id);
+        assert!(imported.inverse_patch_id.is_some());
+        assert_eq!(imported.inverse_patch_id, extracted.inverse_patch_id);
+        assert_eq!((imported.lines_added, imported.lines_deleted), (extracted.lines_added, extracted.lines_deleted));
+        let paths = |c: &Commit| c.files.iter().map(|f| (f.path().to_string(), f.status)).collect::<Vec<_>>();
+        assert_eq!(paths(&imported), paths(&extracted));
+        assert!(imported.authors.iter().any(|a| a.email == "bob@example.com" && a.role == AuthorRole::CoAuthor));
+
+        let mailmap = Mailmap::from_buffer("Ann Example <ann@work.example> <ann@example.com>\n").unwrap();
+        let mapped = commit_from_patch(&patches[0], "https://example.com/repo", Some(&mailmap), &ExclusionRules::default())
+            .unwrap();
+        assert_eq!((mapped.author.as_str(), mapped.author_email.as_str()), ("Ann Example", "ann@work.example"));
+    }
+
+    #[test]
+    fn test_a_bundle_unpacks_with_its_branches() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_tree(&repo, "base", &[("lib.rs", b"fn a() {}\n")]);
+        let head = commit_tree(&repo, "add b", &[("lib.rs", b"fn a() {}\nfn b() {}\n")]);
+        let branch = repo.head().unwrap().name().unwrap().to_string();
+
+        // What 'git bundle create <file> --all' writes
+        let mut pack = repo.packbuilder().unwrap();
+        let mut walk = repo.revwalk().unwrap();
+        walk.push(head).unwrap();
+        pack.insert_walk(&mut walk).unwrap();
+        let mut buf = git2::Buf::new();
+        pack.write_buf(&mut buf).unwrap();
+        let mut bundle = format!("# v2 git bundle\n{} {}\n{} HEAD\n\n", head, branch, head).into_bytes();
+        bundle.extend_from_slice(&buf);
+        let file = dir.path().join("repo.bundle");
+        std::fs::write(&file, &bundle).unwrap();
+
+        let unpacked = unpack_bundle(&file).unwrap();
+        let snapshot = snapshot_repo(&unpacked.path, &RefSelection { all_refs: true, ..Default::default() }).unwrap();
+        assert_eq!(snapshot.head_oid, head.to_string());
+        assert_eq!(snapshot.refs.get(&branch), Some(&head.to_string()));
+        let options = ExtractOptions { tips: vec![head.to_string()], ..Default::default() };
+        assert_eq!(extract(&unpacked.path, &options).len(), 2);
+
+        let path = unpacked.path.clone();
+        drop(unpacked);
+        assert!(!path.exists());
+
+        let partial = dir.path().join("partial.bundle");
+        std::fs::write(&partial, format!("# v2 git bundle\n-{} base\n{} {}\n\n", head, head, branch)).unwrap();
+        assert!(unpack_bundle(&partial).unwrap_err().to_string().contains("does not contain"));
+    }
+
+    #[test]
+    fn test_short_ref_name() {
+        assert_eq!(short_ref_name("refs/heads/main"), "main");
+        assert_eq!(short_ref_name("refs/remotes/origin/x"), "origin/x");
+        assert_eq!(short_ref_name("HEAD"), "HEAD");
+    }
+}
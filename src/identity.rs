@@ -0,0 +1,236 @@
+use anyhow::Result;
+use std::collections::HashSet;
+
+use crate::database::{Commit, Database, Identity};
+
+/// Resolved `--author` filter: the names and emails of one person.
+/// Matching is exact and case-insensitive, so "Ann" does not match "Joanne".
+#[derive(Debug, Clone)]
+pub struct AuthorFilter {
+    /// What the user asked for, shown in generated documents
+    pub label: String,
+    names: HashSet<String>,
+    emails: HashSet<String>,
+}
+
+impl AuthorFilter {
+    /// Filter for a name or email that is not mapped to an identity
+    pub fn exact(query: &str) -> Self {
+        let label = query.trim().to_string();
+        let query = label.to_lowercase();
+        Self {
+            label,
+            names: HashSet::from([query.clone()]),
+            emails: HashSet::from([query]),
+        }
+    }
+
+    /// Filter matching every name and email of an identity
+    pub fn for_identity(identity: &Identity) -> Self {
+        let mut names = HashSet::from([identity.name.to_lowercase()]);
+        let mut emails = HashSet::new();
+        for alias in &identity.aliases {
+            if let Some(name) = &alias.name {
+                names.insert(name.to_lowercase());
+            }
+            if let Some(email) = &alias.email {
+                emails.insert(email.to_lowercase());
+            }
+        }
+        Self {
+            label: identity.name.clone(),
+            names,
+            emails,
+        }
+    }
+
+    pub fn matches(&self, name: &str, email: &str) -> bool {
+        self.names.contains(&name.to_lowercase()) || self.emails.contains(&email.to_lowercase())
+    }
+
+    /// Whether the filter matches anyone credited with the commit, including co-authors
+    pub fn matches_commit(&self, commit: &Commit) -> bool {
+        commit.credited().into_iter().any(|(name, email)| self.matches(name, email))
+    }
+}
+
+/// One line of a `.mailmap` file. The proper name/email is what a person should
+/// be shown as; the commit name/email is what appears in commits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MailmapEntry {
+    pub proper_name: Option<String>,
+    pub proper_email: Option<String>,
+    pub commit_name: Option<String>,
+    pub commit_email: Option<String>,
+}
+
+/// Parse `.mailmap` content. Supported forms:
+///
+/// ```text
+/// Proper Name <commit@email>
+/// <proper@email> <commit@email>
+/// Proper Name <proper@email> <commit@email>
+/// Proper Name <proper@email> Commit Name <commit@email>
+/// ```
+pub fn parse_mailmap(content: &str) -> Vec<MailmapEntry> {
+    let mut entries = Vec::new();
+
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        // Split into (name before email, email) pairs
+        let mut parts = Vec::new();
+        let mut rest = line;
+        while let Some(open) = rest.find('<') {
+            let Some(close) = rest[open..].find('>') else { break };
+            let name = rest[..open].trim();
+            let email = rest[open + 1..open + close].trim();
+            parts.push((
+                (!name.is_empty()).then(|| name.to_string()),
+                (!email.is_empty()).then(|| email.to_string()),
+            ));
+            rest = &rest[open + close + 1..];
+        }
+
+        let entry = match parts.as_slice() {
+            [(name, email)] => MailmapEntry {
+                proper_name: name.clone(),
+                proper_email: None,
+                commit_name: None,
+                commit_email: email.clone(),
+            },
+            [(proper_name, proper_email), (commit_name, commit_email)] => MailmapEntry {
+                proper_name: proper_name.clone(),
+                proper_email: proper_email.clone(),
+                commit_name: commit_name.clone(),
+                commit_email: commit_email.clone(),
+            },
+            _ => continue,
+        };
+        entries.push(entry);
+    }
+
+    entries
+}
+
+/// Counts reported by `import_mailmap`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MailmapImport {
+    pub identities: usize,
+    pub aliases: usize,
+}
+
+/// Store mailmap entries as identities. Each entry joins the identity that
+/// already owns its proper name or either email; otherwise a new identity is
+/// named after the proper name (or email).
+pub fn import_mailmap(db: &Database, entries: &[MailmapEntry]) -> Result<MailmapImport> {
+    let mut summary = MailmapImport::default();
+
+    for entry in entries {
+        let candidates = [&entry.proper_name, &entry.proper_email, &entry.commit_email];
+        let mut identity_id = None;
+        for candidate in candidates.into_iter().flatten() {
+            if let Some(identity) = db.find_identity(candidate)? {
+                identity_id = Some(identity.id);
+                break;
+            }
+        }
+
+        let identity_id = match identity_id {
+            Some(id) => id,
+            None => {
+                let Some(name) = candidates.into_iter().flatten().next() else { continue };
+                summary.identities += 1;
+                db.add_identity(name)?
+            }
+        };
+
+        let aliases = [
+            (entry.proper_name.as_deref(), entry.proper_email.as_deref()),
+            (entry.commit_name.as_deref(), entry.commit_email.as_deref()),
+        ];
+        for (name, email) in aliases {
+            if db.add_identity_alias(identity_id, name, email)? {
+                summary.aliases += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::IdentityAlias;
+
+    #[test]
+    fn test_parse_mailmap_forms() {
+        let entries = parse_mailmap(
+            "# comment\n\
+             Ann Lee <ann@old.example>\n\
+             <ann@work.example> <ann@home.example>\n\
+             Ann Lee <ann@work.example> <alee@old.example>\n\
+             Ann Lee <ann@work.example> A. Lee <alee@old.example> # trailing\n",
+        );
+
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].proper_name.as_deref(), Some("Ann Lee"));
+        assert_eq!(entries[0].commit_email.as_deref(), Some("ann@old.example"));
+        assert_eq!(entries[1].proper_name, None);
+        assert_eq!(entries[1].proper_email.as_deref(), Some("ann@work.example"));
+        assert_eq!(entries[1].commit_email.as_deref(), Some("ann@home.example"));
+        assert_eq!(entries[2].commit_name, None);
+        assert_eq!(entries[3].commit_name.as_deref(), Some("A. Lee"));
+    }
+
+    #[test]
+    fn test_exact_filter_does_not_match_substrings() {
+        let filter = AuthorFilter::exact("Ann");
+        assert!(filter.matches("ann", "x@example.com"));
+        assert!(!filter.matches("Joanne", "joanne@example.com"));
+    }
+
+    #[test]
+    fn test_identity_filter_matches_aliases() {
+        let identity = Identity {
+            id: 1,
+            name: "Ann Lee".to_string(),
+            aliases: vec![
+                IdentityAlias { name: Some("A. Lee".to_string()), email: None },
+                IdentityAlias { name: None, email: Some("ann@home.example".to_string()) },
+            ],
+        };
+        let filter = AuthorFilter::for_identity(&identity);
+        assert!(filter.matches("Ann Lee", "other@example.com"));
+        assert!(filter.matches("a. lee", "other@example.com"));
+        assert!(filter.matches("Someone", "ANN@home.example"));
+        assert!(!filter.matches("Ann", "ann@work.example"));
+    }
+
+    #[test]
+    fn test_import_mailmap_groups_entries_by_person() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::open_at(&dir.path().join("contributions.db")).unwrap();
+        let entries = parse_mailmap(
+            "Ann Lee <ann@work.example> <ann@home.example>\n\
+             Ann Lee <ann@work.example> Annie <annie@old.example>\n\
+             Bob Ray <bob@example.com>\n",
+        );
+
+        let summary = import_mailmap(&db, &entries).unwrap();
+        assert_eq!(summary.identities, 2);
+
+        let filter = db.resolve_author_filter("annie@old.example").unwrap();
+        assert_eq!(filter.label, "Ann Lee");
+        assert!(filter.matches("Someone", "ann@home.example"));
+        assert!(filter.matches("Annie", "unknown@example.com"));
+        assert!(!filter.matches("Bob Ray", "bob@example.com"));
+
+        // Importing again adds nothing
+        assert_eq!(import_mailmap(&db, &entries).unwrap(), MailmapImport::default());
+    }
+}
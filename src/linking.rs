@@ -0,0 +1,231 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::config::{Config, LinkingConfig};
+use crate::database::{Database, LinkSource};
+
+/// Rules by which a commit message names the contribution it belongs to
+#[derive(Debug)]
+pub struct LinkRules {
+    trailer: Option<String>,
+    patterns: Vec<Regex>,
+}
+
+impl LinkRules {
+    pub fn from_config(config: &LinkingConfig) -> Result<Self> {
+        let patterns = config
+            .patterns
+            .iter()
+            .map(|p| Regex::new(p).with_context(|| format!("Invalid linking pattern '{}' in config.toml", p)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let trailer = config.trailer.trim();
+        Ok(Self {
+            trailer: (!trailer.is_empty()).then(|| trailer.to_string()),
+            patterns,
+        })
+    }
+
+    /// Rules from config.toml, or the defaults when there is no config file
+    pub fn load() -> Result<Self> {
+        Self::from_config(&Config::load()?.linking)
+    }
+
+    /// Contribution names a message refers to, trailers first
+    pub fn contribution_names(&self, message: &str) -> Vec<(LinkSource, String)> {
+        let mut names = Vec::new();
+
+        if let Some(key) = &self.trailer {
+            if let Ok(trailers) = git2::message_trailers_strs(message) {
+                for (trailer, value) in trailers.iter() {
+                    if trailer.eq_ignore_ascii_case(key) && !value.trim().is_empty() {
+                        names.push((LinkSource::Trailer, value.trim().to_string()));
+                    }
+                }
+            }
+        }
+
+        for pattern in &self.patterns {
+            for captures in pattern.captures_iter(message) {
+                let name = captures.name("name").or_else(|| captures.get(1));
+                if let Some(name) = name {
+                    names.push((LinkSource::Regex, name.as_str().trim().to_string()));
+                }
+            }
+        }
+
+        names
+    }
+}
+
+/// Links made by `link_commits`, by source
+#[derive(Debug, Default)]
+pub struct LinkReport {
+    pub hash: usize,
+    pub trailer: usize,
+    pub regex: usize,
+    /// Names in commit messages that match no contribution
+    pub unknown_names: BTreeSet<String>,
+}
+
+impl LinkReport {
+    pub fn total(&self) -> usize {
+        self.hash + self.trailer + self.regex
+    }
+}
+
+/// Link a repository's commits. Commits listed by hash on contributions are
+/// linked first; then every commit is linked to each contribution its message
+/// names (case-insensitive), by trailer or by pattern, whatever it is already
+/// linked to.
+pub fn link_commits(db: &Database, repo_url: &str, rules: &LinkRules) -> Result<LinkReport> {
+    let mut report = LinkReport {
+        hash: db.link_commits_to_contributions(repo_url)?,
+        ..Default::default()
+    };
+
+    let contributions: HashMap<String, i64> = db
+        .get_contributions(repo_url)?
+        .into_iter()
+        .filter_map(|c| Some((c.name.to_lowercase(), c.id?)))
+        .collect();
+
+    for (hash, message) in db.get_commit_messages(repo_url)? {
+        for (source, name) in rules.contribution_names(&message) {
+            let Some(&id) = contributions.get(&name.to_lowercase()) else {
+                report.unknown_names.insert(name);
+                continue;
+            };
+            if db.link_commit(&hash, id, source)? {
+                match source {
+                    LinkSource::Trailer => report.trailer += 1,
+                    LinkSource::Regex => report.regex += 1,
+                    LinkSource::Hash => report.hash += 1,
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(patterns: &[&str]) -> LinkRules {
+        LinkRules::from_config(&LinkingConfig {
+            trailer: "Contribution".to_string(),
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_trailer_and_pattern_names() {
+        let rules = rules(&[r"\[CT-(?P<name>[^\]]+)\]"]);
+        let names = rules.contribution_names(
+            "[CT-Docs] Fix typo\n\nLonger body.\n\nContribution: Sync Engine\nSigned-off-by: Ann <ann@example.com>\n",
+        );
+        assert_eq!(
+            names,
+            vec![
+                (LinkSource::Trailer, "Sync Engine".to_string()),
+                (LinkSource::Regex, "Docs".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trailer_outside_last_paragraph_is_ignored() {
+        let names = rules(&[]).contribution_names("Subject\n\nContribution: Sync Engine\n\nMore text\n");
+        assert!(names.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_reported() {
+        let error = LinkRules::from_config(&LinkingConfig {
+            trailer: String::new(),
+            patterns: vec!["[unclosed".to_string()],
+        })
+        .unwrap_err();
+        assert!(error.to_string().contains("[unclosed"));
+    }
+
+    #[test]
+    fn test_commit_belongs_to_every_contribution_it_is_linked_to() {
+        use crate::database::{Commit, Contribution, Repository};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::open_at(&dir.path().join("contributions.db")).unwrap();
+        let repo = "https://github.com/org/repo";
+        db.add_repository(&Repository {
+            url: repo.to_string(),
+            organization: "org".to_string(),
+            name: "repo".to_string(),
+            description: None,
+            local_path: None,
+        }).unwrap();
+        for (name, key_commits) in [("Sync Engine", vec![]), ("Docs", vec!["bbbb".to_string()])] {
+            db.add_contribution(&Contribution {
+                id: None,
+                repository_url: repo.to_string(),
+                name: name.to_string(),
+                overview: String::new(),
+                description: String::new(),
+                key_commits,
+                related_commits: Vec::new(),
+                technical_details: HashMap::new(),
+                resume_bullets: Vec::new(),
+                category: "Feature".to_string(),
+                priority: 5,
+            }).unwrap();
+        }
+        for (hash, message) in [
+            ("aaaa1111", "Add watermark\n\nContribution: sync engine\n"),
+            ("bbbb2222", "Update guide\n\nContribution: Sync Engine\n"),
+            ("cccc3333", "Other\n\nContribution: Unknown Thing\n"),
+            ("dddd4444", "[CT-Docs] Document sync\n\nContribution: Docs\nContribution: Sync Engine\n"),
+        ] {
+            db.add_commit(&Commit {
+                hash: hash.to_string(),
+                repository_url: repo.to_string(),
+                author: "Ann".to_string(),
+                author_email: "ann@example.com".to_string(),
+                date: "2024-01-01T00:00:00+00:00".to_string(),
+                message: message.to_string(),
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let rules = rules(&[r"\[CT-(?P<name>[^\]]+)\]"]);
+        let report = link_commits(&db, repo, &rules).unwrap();
+        assert_eq!((report.hash, report.trailer, report.regex), (1, 4, 1));
+        assert!(report.unknown_names.contains("Unknown Thing"));
+        // Links already made are not counted again
+        assert_eq!(link_commits(&db, repo, &rules).unwrap().total(), 0);
+
+        let linked = |name: &str| -> Vec<(String, Option<String>)> {
+            let mut commits: Vec<_> = db
+                .get_commits_for_contribution(repo, name)
+                .unwrap()
+                .into_iter()
+                .map(|c| (c.hash, c.link_source))
+                .collect();
+            commits.sort();
+            commits
+        };
+        // bbbb2222 is listed by Docs and names Sync Engine in its trailer;
+        // dddd4444 names Docs both ways
+        assert_eq!(linked("Docs"), vec![
+            ("bbbb2222".to_string(), Some("hash".to_string())),
+            ("dddd4444".to_string(), Some("trailer, regex".to_string())),
+        ]);
+        assert_eq!(linked("Sync Engine"), vec![
+            ("aaaa1111".to_string(), Some("trailer".to_string())),
+            ("bbbb2222".to_string(), Some("trailer".to_string())),
+            ("dddd4444".to_string(), Some("trailer".to_string())),
+        ]);
+    }
+}
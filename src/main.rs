@@ -5,11 +5,26 @@ use std::path::PathBuf;
 mod commands;
 mod config;
 mod database;
+mod discover;
+mod exclude;
 mod git;
+mod identity;
+mod linking;
 mod markdown;
+mod mbox;
+mod migrations;
+mod portfolio;
+mod release;
+mod repair;
+mod revert;
+mod render;
+mod suggest;
+mod template;
 mod utils;
 
 use commands::*;
+use render::OutputFormat;
+use utils::DateRange;
 
 #[derive(Parser)]
 #[command(name = "contrack")]
@@ -37,6 +52,26 @@ enum Commands {
         #[arg(short, long)]
         description: Option<String>,
     },
+    /// Find git repositories under a directory and register them
+    Discover {
+        /// Directory to search recursively
+        dir: PathBuf,
+        /// Register without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Remap key and related commits that a rebase or force-push rewrote
+    Repair {
+        /// Repository URL
+        #[arg(short, long)]
+        repo_url: String,
+        /// Path to the git repository (defaults to the discovered path, then the current directory)
+        #[arg(short = 'p', long)]
+        repo_path: Option<PathBuf>,
+        /// Rewrite without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
     /// Add a new contribution
     Add {
         /// Repository URL
@@ -64,23 +99,188 @@ enum Commands {
         #[arg(short, long, default_value_t = 5)]
         priority: u8,
     },
+    /// Edit an existing contribution in place
+    Edit {
+        /// Repository URL
+        #[arg(short, long)]
+        repo_url: String,
+        /// Contribution name
+        #[arg(short, long)]
+        name: String,
+        /// New contribution name
+        #[arg(long)]
+        new_name: Option<String>,
+        /// Brief overview
+        #[arg(short, long)]
+        overview: Option<String>,
+        /// Detailed description
+        #[arg(short, long)]
+        description: Option<String>,
+        /// Category
+        #[arg(short, long)]
+        category: Option<String>,
+        /// Priority (1-10, higher is more important)
+        #[arg(short, long)]
+        priority: Option<u8>,
+        /// Key commit hashes to add (comma-separated)
+        #[arg(long)]
+        add_key_commits: Option<String>,
+        /// Key commit hashes to remove (comma-separated)
+        #[arg(long)]
+        remove_key_commits: Option<String>,
+        /// Related commit hashes to add (comma-separated)
+        #[arg(long)]
+        add_related_commits: Option<String>,
+        /// Related commit hashes to remove (comma-separated)
+        #[arg(long)]
+        remove_related_commits: Option<String>,
+    },
+    /// Remove a contribution
+    Remove {
+        /// Repository URL
+        #[arg(short, long)]
+        repo_url: String,
+        /// Contribution name
+        #[arg(short, long)]
+        name: String,
+        /// What to do with commits linked to the contribution
+        #[arg(long, value_enum, default_value_t = LinkedCommits::Detach)]
+        linked_commits: LinkedCommits,
+    },
+    /// Manage resume bullets for a contribution
+    Bullets {
+        #[command(subcommand)]
+        subcommand: BulletsCommands,
+    },
+    /// Manage technical details for a contribution
+    Details {
+        #[command(subcommand)]
+        subcommand: DetailsCommands,
+    },
     /// Update commit details from git repository
     Update {
         /// Path to git repository (defaults to current directory)
         #[arg(short, long)]
         repo_path: Option<PathBuf>,
+        /// Registered repository URL to store commits under (defaults to the origin remote)
+        #[arg(short = 'u', long)]
+        repo_url: Option<String>,
+        /// Rescan the full history instead of only commits new since the last sync
+        #[arg(long)]
+        full: bool,
+        /// Also walk local branches matching this glob, e.g. 'feature/*' (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        branches: Vec<String>,
+        /// Also walk every ref (branches, remote branches, tags)
+        #[arg(long)]
+        all_refs: bool,
+        /// Also walk every remote-tracking branch
+        #[arg(long)]
+        remote_branches: bool,
+        /// Threads computing commit diffs (0 uses one per CPU)
+        #[arg(short, long, default_value_t = 0)]
+        jobs: usize,
+        /// Blame files at HEAD to count the lines each commit still owns
+        #[arg(long)]
+        blame: bool,
+        /// Only read commits authored on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only read commits authored on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<String>,
+        /// Read history from a bundle made by 'git bundle create <file> --all' instead of a repository
+        #[arg(long, value_name = "FILE", conflicts_with = "repo_path", requires = "repo_url")]
+        bundle: Option<PathBuf>,
     },
-    /// Generate contributions markdown file
+    /// Store commits from a 'git format-patch' mailbox
+    ImportPatches {
+        /// Mailbox or patch file written by 'git format-patch'
+        mbox: PathBuf,
+        /// Repository URL to store the commits under
+        #[arg(short, long)]
+        repo_url: String,
+        /// Mailmap file for author names (defaults to the .mailmap of the repository's recorded checkout)
+        #[arg(long)]
+        mailmap: Option<PathBuf>,
+    },
+    /// Suggest contributions by grouping unassigned commits
+    Suggest {
+        /// Repository URL
+        #[arg(short, long)]
+        repo_url: String,
+        /// Path to the git repository, used to group commits by merge ancestry
+        #[arg(short = 'p', long)]
+        repo_path: Option<PathBuf>,
+        /// Group commits on the same path or scope at most this many days apart
+        #[arg(long, default_value_t = 7)]
+        window_days: i64,
+        /// Smallest group of commits to suggest
+        #[arg(long, default_value_t = 2)]
+        min_commits: usize,
+        /// Create the contribution for this suggestion number
+        #[arg(short, long)]
+        accept: Option<usize>,
+        /// Name for the accepted contribution (defaults to the suggested name)
+        #[arg(short, long, requires = "accept")]
+        name: Option<String>,
+        /// Category for the accepted contribution (defaults to the suggested category)
+        #[arg(short, long, requires = "accept")]
+        category: Option<String>,
+        /// Priority for the accepted contribution (1-10, default 5)
+        #[arg(long, requires = "accept")]
+        priority: Option<u8>,
+    },
+    /// Generate contributions document (markdown by default)
     Generate {
         /// Repository URL
         #[arg(short, long)]
         repo_url: String,
-        /// Output file path (defaults to CONTRIBUTIONS.md)
-        #[arg(short, long, default_value = "CONTRIBUTIONS.md")]
-        output: PathBuf,
-        /// Author name to filter by (optional)
+        /// Output file path (defaults to CONTRIBUTIONS.<ext> for the chosen format)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Author to filter by: an identity, or an exact name or email (optional)
         #[arg(short, long)]
         author: Option<String>,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Markdown)]
+        format: OutputFormat,
+        /// Render with a template from .contrack/templates/ instead of a built-in format
+        #[arg(short, long, conflicts_with = "format")]
+        template: Option<String>,
+        /// Only include commits authored on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only include commits authored on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<String>,
+        /// Keep reverted commits and their reverts (flagged) instead of leaving them out
+        #[arg(long)]
+        include_reverts: bool,
+    },
+    /// Generate a portfolio document across all tracked repositories
+    Portfolio {
+        /// Output file path
+        #[arg(short, long, default_value = "PORTFOLIO.md")]
+        output: PathBuf,
+        /// Only include this organization (id or name)
+        #[arg(long)]
+        org: Option<String>,
+        /// Only include work on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only include work on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<String>,
+        /// Only include this category
+        #[arg(short, long)]
+        category: Option<String>,
+        /// Only include contributions with at least this priority
+        #[arg(short, long)]
+        min_priority: Option<u8>,
+        /// Render with a template from .contrack/templates/portfolio/ instead of the built-in portfolio layout
+        #[arg(short, long)]
+        template: Option<String>,
     },
     /// Query the database
     Query {
@@ -105,16 +305,100 @@ enum Commands {
         #[command(subcommand)]
         subcommand: LoadoutCommands,
     },
+    /// Manage author identities (names and emails that belong to one person)
+    Identity {
+        #[command(subcommand)]
+        subcommand: IdentityCommands,
+    },
+    /// Manage the database schema
+    Db {
+        #[command(subcommand)]
+        subcommand: DbCommands,
+    },
     /// Output AI agent configuration prompt
     Ai,
 }
 
+#[derive(Subcommand)]
+enum BulletsCommands {
+    /// Add a resume bullet
+    Add {
+        /// Repository URL
+        #[arg(short, long)]
+        repo_url: String,
+        /// Contribution name
+        #[arg(short, long)]
+        name: String,
+        /// Bullet text
+        text: String,
+        /// Position to insert at (1-based, defaults to the end)
+        #[arg(short, long)]
+        position: Option<usize>,
+    },
+    /// Remove a resume bullet
+    Remove {
+        /// Repository URL
+        #[arg(short, long)]
+        repo_url: String,
+        /// Contribution name
+        #[arg(short, long)]
+        name: String,
+        /// Bullet number (1-based, as shown by `query contribution`)
+        number: usize,
+    },
+    /// Reorder resume bullets
+    Reorder {
+        /// Repository URL
+        #[arg(short, long)]
+        repo_url: String,
+        /// Contribution name
+        #[arg(short, long)]
+        name: String,
+        /// New order as comma-separated bullet numbers (e.g. 3,1,2)
+        order: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DetailsCommands {
+    /// Set a technical detail to a JSON value
+    Set {
+        /// Repository URL
+        #[arg(short, long)]
+        repo_url: String,
+        /// Contribution name
+        #[arg(short, long)]
+        name: String,
+        /// Detail key (suggested: technology_stack, patterns, integrations, storage, security)
+        key: String,
+        /// JSON value (e.g. '"Rust"' or '["SQLite", "Redis"]')
+        value: String,
+    },
+    /// Remove a technical detail
+    Unset {
+        /// Repository URL
+        #[arg(short, long)]
+        repo_url: String,
+        /// Contribution name
+        #[arg(short, long)]
+        name: String,
+        /// Detail key
+        key: String,
+    },
+}
+
 #[derive(Subcommand)]
 enum QueryCommands {
     /// List all contributions for a repository
     Contributions {
         /// Repository URL
         repo_url: String,
+        /// Only list contributions with commits authored on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only list contributions with commits authored on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<String>,
     },
     /// Show details for a specific contribution
     Contribution {
@@ -129,6 +413,19 @@ enum QueryCommands {
         repo_url: String,
         /// Contribution name
         name: String,
+        /// Only show commits authored on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only show commits authored on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<String>,
+    },
+    /// Show lines changed per file for a contribution
+    Files {
+        /// Repository URL
+        repo_url: String,
+        /// Contribution name
+        name: String,
     },
     /// Show database statistics
     Stats,
@@ -166,6 +463,9 @@ enum ConfigCommands {
         /// Repository description
         #[arg(short, long)]
         description: Option<String>,
+        /// Other remote URL for the repository, matched by update (repeatable)
+        #[arg(short, long = "alias")]
+        alias: Vec<String>,
     },
 }
 
@@ -197,6 +497,51 @@ enum LoadoutCommands {
     ReloadDefault,
 }
 
+#[derive(Subcommand)]
+enum IdentityCommands {
+    /// Add an identity, optionally with emails and alternate names
+    Add {
+        /// Display name for the person
+        name: String,
+        /// Email the person commits with (repeatable)
+        #[arg(short, long)]
+        email: Vec<String>,
+        /// Other name the person commits under (repeatable)
+        #[arg(short = 'n', long = "alias-name")]
+        alias_name: Vec<String>,
+    },
+    /// Add a name and/or email to an existing identity
+    Alias {
+        /// Identity name, or any of its names or emails
+        identity: String,
+        #[arg(short, long)]
+        name: Option<String>,
+        #[arg(short, long)]
+        email: Option<String>,
+    },
+    /// List identities and their aliases
+    List,
+    /// Import identities from a .mailmap file
+    ImportMailmap {
+        /// Repository to read .mailmap from (defaults to current directory)
+        #[arg(short, long, conflicts_with = "file")]
+        repo_path: Option<PathBuf>,
+        /// Path to a mailmap file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommands {
+    /// Apply pending schema migrations
+    Migrate {
+        /// Show pending migrations without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
@@ -207,6 +552,8 @@ fn main() -> Result<()> {
             name,
             description,
         } => init_command(repo_url, org, name, description),
+        Commands::Discover { dir, yes } => discover_command(dir, yes),
+        Commands::Repair { repo_url, repo_path, yes } => repair_command(repo_url, repo_path, yes),
         Commands::Add {
             repo_url,
             name,
@@ -226,16 +573,122 @@ fn main() -> Result<()> {
             category,
             priority,
         ),
-        Commands::Update { repo_path } => update_command(repo_path),
+        Commands::Edit {
+            repo_url,
+            name,
+            new_name,
+            overview,
+            description,
+            category,
+            priority,
+            add_key_commits,
+            remove_key_commits,
+            add_related_commits,
+            remove_related_commits,
+        } => edit_command(
+            repo_url,
+            name,
+            ContributionEdit {
+                new_name,
+                overview,
+                description,
+                category,
+                priority,
+                add_key_commits,
+                remove_key_commits,
+                add_related_commits,
+                remove_related_commits,
+            },
+        ),
+        Commands::Remove {
+            repo_url,
+            name,
+            linked_commits,
+        } => remove_command(repo_url, name, linked_commits),
+        Commands::Bullets { subcommand } => match subcommand {
+            BulletsCommands::Add { repo_url, name, text, position } => bullets_add_command(repo_url, name, text, position),
+            BulletsCommands::Remove { repo_url, name, number } => bullets_remove_command(repo_url, name, number),
+            BulletsCommands::Reorder { repo_url, name, order } => bullets_reorder_command(repo_url, name, order),
+        },
+        Commands::Details { subcommand } => match subcommand {
+            DetailsCommands::Set { repo_url, name, key, value } => details_set_command(repo_url, name, key, value),
+            DetailsCommands::Unset { repo_url, name, key } => details_unset_command(repo_url, name, key),
+        },
+        Commands::Update {
+            repo_path,
+            repo_url,
+            full,
+            branches,
+            all_refs,
+            remote_branches,
+            jobs,
+            blame,
+            since,
+            until,
+            bundle,
+        } => update_command(
+            repo_path,
+            repo_url,
+            full,
+            git::RefSelection { branches, all_refs, remote_branches },
+            jobs,
+            blame,
+            DateRange::parse(since.as_deref(), until.as_deref())?,
+            bundle,
+        ),
+        Commands::ImportPatches { mbox, repo_url, mailmap } => import_patches_command(mbox, repo_url, mailmap),
+        Commands::Suggest {
+            repo_url,
+            repo_path,
+            window_days,
+            min_commits,
+            accept,
+            name,
+            category,
+            priority,
+        } => suggest_command(
+            repo_url,
+            repo_path,
+            window_days,
+            min_commits,
+            accept.map(|number| AcceptSuggestion { number, name, category, priority }),
+        ),
         Commands::Generate {
             repo_url,
             output,
             author,
-        } => generate_command(repo_url, output, author),
+            format,
+            template,
+            since,
+            until,
+            include_reverts,
+        } => generate_command(
+            repo_url,
+            output,
+            author,
+            format,
+            template,
+            DateRange::parse(since.as_deref(), until.as_deref())?,
+            include_reverts,
+        ),
+        Commands::Portfolio {
+            output,
+            org,
+            since,
+            until,
+            category,
+            min_priority,
+            template,
+        } => portfolio_command(output, org, since, until, category, min_priority, template),
         Commands::Query { subcommand } => match subcommand {
-            QueryCommands::Contributions { repo_url } => query_contributions(repo_url),
+            QueryCommands::Contributions { repo_url, since, until } => {
+                query_contributions(repo_url, DateRange::parse(since.as_deref(), until.as_deref())?)
+            }
             QueryCommands::Contribution { repo_url, name } => query_contribution(repo_url, name),
-            QueryCommands::Commits { repo_url, name } => query_commits(repo_url, name),
+            QueryCommands::Commits { repo_url, name, since, until } => {
+                query_commits(repo_url, name, DateRange::parse(since.as_deref(), until.as_deref())?)
+            }
+            QueryCommands::Files { repo_url, name } => query_files(repo_url, name),
             QueryCommands::Stats => query_stats(),
         },
         Commands::List { detailed } => list_repositories(detailed),
@@ -244,7 +697,9 @@ fn main() -> Result<()> {
             ConfigCommands::Sync => config_sync_command(),
             ConfigCommands::Load => config_load_command(),
             ConfigCommands::AddOrg { id, name, description } => config_add_org_command(id, name, description),
-            ConfigCommands::AddRepo { url, org, name, description } => config_add_repo_command(url, org, name, description),
+            ConfigCommands::AddRepo { url, org, name, description, alias } => {
+                config_add_repo_command(url, org, name, description, alias)
+            }
         },
         Commands::Loadout { subcommand } => match subcommand {
             LoadoutCommands::List => loadout_list_command(),
@@ -254,6 +709,15 @@ fn main() -> Result<()> {
             LoadoutCommands::Delete { name } => loadout_delete_command(name),
             LoadoutCommands::ReloadDefault => loadout_reload_default_command(),
         },
+        Commands::Identity { subcommand } => match subcommand {
+            IdentityCommands::Add { name, email, alias_name } => identity_add_command(name, email, alias_name),
+            IdentityCommands::Alias { identity, name, email } => identity_alias_command(identity, name, email),
+            IdentityCommands::List => identity_list_command(),
+            IdentityCommands::ImportMailmap { repo_path, file } => identity_import_mailmap_command(repo_path, file),
+        },
+        Commands::Db { subcommand } => match subcommand {
+            DbCommands::Migrate { dry_run } => db_migrate_command(dry_run),
+        },
         Commands::Ai => ai_command(),
     }
 }
@@ -0,0 +1,218 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use regex::Regex;
+
+/// One patch from a `git format-patch` mailbox
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchEmail {
+    /// The hash of the commit the patch was made from
+    pub hash: String,
+    pub author: String,
+    pub author_email: String,
+    pub date: DateTime<FixedOffset>,
+    /// Subject (without the `[PATCH n/m]` prefix) and body, as a commit message
+    pub message: String,
+    /// The `diff --git` sections, without the mail signature
+    pub diff: String,
+}
+
+/// Split a `git format-patch --stdout` mailbox (or one patch file) into its
+/// patches. Messages that are not patches, such as a cover letter, are skipped.
+pub fn parse_mbox(text: &str) -> Result<Vec<PatchEmail>> {
+    let separator = Regex::new(r"^From ([0-9a-f]{40}) ").unwrap();
+
+    let mut messages: Vec<(String, Vec<&str>)> = Vec::new();
+    for line in text.lines() {
+        if let Some(captures) = separator.captures(line) {
+            messages.push((captures[1].to_string(), Vec::new()));
+        } else if let Some((_, lines)) = messages.last_mut() {
+            lines.push(line);
+        }
+    }
+
+    let mut patches = Vec::new();
+    for (hash, lines) in messages {
+        if let Some(patch) = parse_message(&hash, &lines).with_context(|| format!("Invalid patch for commit {}", hash))? {
+            patches.push(patch);
+        }
+    }
+    Ok(patches)
+}
+
+fn parse_message(hash: &str, lines: &[&str]) -> Result<Option<PatchEmail>> {
+    let blank = lines.iter().position(|l| l.is_empty()).unwrap_or(lines.len());
+    let (headers, body) = (unfold_headers(&lines[..blank]), &lines[(blank + 1).min(lines.len())..]);
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find_map(|(key, value)| key.eq_ignore_ascii_case(name).then_some(value.as_str()))
+    };
+
+    let Some(diff_start) = body.iter().position(|l| l.starts_with("diff --git ")) else {
+        return Ok(None);
+    };
+
+    let from = decode_words(header("From").context("Missing From header")?);
+    let (author, author_email) = crate::git::parse_person(&from).context("Invalid From header")?;
+    let author = author.trim_matches('"').to_string();
+    let date = DateTime::parse_from_rfc2822(header("Date").context("Missing Date header")?)
+        .context("Invalid Date header")?;
+    let subject = strip_patch_prefix(&decode_words(header("Subject").unwrap_or("")));
+
+    // The body runs up to the `---` line that opens the diffstat
+    let body_end = body[..diff_start].iter().position(|l| *l == "---").unwrap_or(diff_start);
+    let body_text = body[..body_end].join("\n");
+    let body_text = body_text.trim();
+    let message = match body_text.is_empty() {
+        true => format!("{}\n", subject),
+        false => format!("{}\n\n{}\n", subject, body_text),
+    };
+
+    // The diff ends at the `-- ` line before the git version signature
+    let diff_lines = &body[diff_start..];
+    let diff_end = diff_lines.iter().rposition(|l| *l == "-- ").unwrap_or(diff_lines.len());
+    let mut diff = diff_lines[..diff_end].join("\n");
+    diff.push('\n');
+
+    Ok(Some(PatchEmail {
+        hash: hash.to_string(),
+        author,
+        author_email,
+        date,
+        message,
+        diff,
+    }))
+}
+
+/// Header names and values, with folded continuation lines joined
+fn unfold_headers(lines: &[&str]) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in lines {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((key, value)) = line.split_once(':') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    headers
+}
+
+/// Drop leading `[PATCH v2 3/7]`-style tags from a subject
+fn strip_patch_prefix(subject: &str) -> String {
+    let mut subject = subject.trim();
+    while subject.starts_with('[') {
+        match subject.find(']') {
+            Some(end) => subject = subject[end + 1..].trim_start(),
+            None => break,
+        }
+    }
+    subject.to_string()
+}
+
+/// Decode RFC 2047 `=?UTF-8?q?...?=` words, as `format-patch` writes
+/// non-ASCII names and subjects; other encodings are left as they are
+fn decode_words(value: &str) -> String {
+    let word = Regex::new(r"(?i)=\?utf-8\?q\?([^?]*)\?=").unwrap();
+    // Whitespace between adjacent encoded words is not part of the text
+    let joined = Regex::new(r"(?i)\?=\s+=\?utf-8\?q\?").unwrap().replace_all(value, "");
+    word.replace_all(&joined, |captures: &regex::Captures| {
+        let mut bytes = Vec::new();
+        let mut chars = captures[1].bytes();
+        while let Some(byte) = chars.next() {
+            match byte {
+                b'_' => bytes.push(b' '),
+                b'=' => {
+                    let hex: Vec<u8> = chars.by_ref().take(2).collect();
+                    match std::str::from_utf8(&hex).ok().and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                        Some(decoded) => bytes.push(decoded),
+                        None => bytes.extend(std::iter::once(b'=').chain(hex)),
+                    }
+                }
+                _ => bytes.push(byte),
+            }
+        }
+        String::from_utf8_lossy(&bytes).to_string()
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MBOX: &str = "\
+From 0000000000000000000000000000000000000000 Mon Sep 17 00:00:00 2001
+From: Ann Lee <ann@example.com>
+Date: Mon, 1 Jan 2024 10:00:00 +0000
+Subject: [PATCH 0/2] Cover letter
+
+Nothing to see here.
+
+From 1111111111111111111111111111111111111111 Mon Sep 17 00:00:00 2001
+From: =?UTF-8?q?J=C3=B6rg=20M=C3=BCller?= <joerg@example.com>
+Date: Tue, 2 Jan 2024 10:00:00 +0100
+Subject: [PATCH 1/2] Add the sync engine with a rather long subject that
+ git folded onto a second line
+
+Body paragraph.
+
+Co-authored-by: Ann Lee <ann@example.com>
+---
+ sync.rs | 2 ++
+ 1 file changed, 2 insertions(+)
+
+diff --git a/sync.rs b/sync.rs
+new file mode 100644
+index 0000000..1111111
+--- /dev/null
++++ b/sync.rs
+@@ -0,0 +1,2 @@
++fn sync() {}
++-- not a signature
+--\x20
+2.43.0
+
+From 2222222222222222222222222222222222222222 Mon Sep 17 00:00:00 2001
+From: Ann Lee <ann@example.com>
+Date: Wed, 3 Jan 2024 09:30:00 -0500
+Subject: [PATCH 2/2] Fix typo
+
+---
+ README.md | 2 +-
+ 1 file changed, 1 insertion(+), 1 deletion(-)
+
+diff --git a/README.md b/README.md
+index 3333333..4444444 100644
+--- a/README.md
++++ b/README.md
+@@ -1 +1 @@
+-Helo
++Hello
+--\x20
+2.43.0
+";
+
+    #[test]
+    fn test_parse_format_patch_mailbox() {
+        let patches = parse_mbox(MBOX).unwrap();
+        assert_eq!(patches.len(), 2);
+
+        let first = &patches[0];
+        assert_eq!(first.hash, "1111111111111111111111111111111111111111");
+        assert_eq!((first.author.as_str(), first.author_email.as_str()), ("Jörg Müller", "joerg@example.com"));
+        assert_eq!(first.date.to_rfc3339(), "2024-01-02T10:00:00+01:00");
+        assert_eq!(
+            first.message,
+            "Add the sync engine with a rather long subject that git folded onto a second line\n\n\
+             Body paragraph.\n\nCo-authored-by: Ann Lee <ann@example.com>\n"
+        );
+        assert!(first.diff.starts_with("diff --git a/sync.rs b/sync.rs\n"));
+        assert!(first.diff.ends_with("+-- not a signature\n"));
+
+        assert_eq!(patches[1].message, "Fix typo\n");
+        assert_eq!(patches[1].date.offset().local_minus_utc(), -5 * 3600);
+    }
+}
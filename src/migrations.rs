@@ -0,0 +1,327 @@
+/// A versioned schema change, applied in order when the database is opened.
+/// The applied version is tracked in SQLite's `PRAGMA user_version`.
+#[derive(Debug)]
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every migration, in the order it must be applied.
+/// Never edit a migration once released; add a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Initial schema (v0.0.1)",
+        // Uses IF NOT EXISTS so databases created before versioning was
+        // introduced (user_version 0) are adopted without changes
+        sql: "
+            CREATE TABLE IF NOT EXISTS repositories (
+                repository_url TEXT PRIMARY KEY,
+                organization TEXT NOT NULL,
+                name TEXT NOT NULL,
+                description TEXT,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS contributions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                repository_url TEXT NOT NULL,
+                name TEXT NOT NULL,
+                overview TEXT,
+                description TEXT,
+                key_commits TEXT,
+                related_commits TEXT,
+                technical_details TEXT,
+                resume_bullets TEXT,
+                category TEXT,
+                priority INTEGER DEFAULT 0,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (repository_url) REFERENCES repositories(repository_url),
+                UNIQUE(repository_url, name)
+            );
+
+            CREATE TABLE IF NOT EXISTS commits (
+                commit_hash TEXT PRIMARY KEY,
+                repository_url TEXT NOT NULL,
+                contribution_id INTEGER,
+                author TEXT NOT NULL,
+                author_email TEXT,
+                date TEXT NOT NULL,
+                message TEXT,
+                files_changed TEXT,
+                lines_added INTEGER,
+                lines_deleted INTEGER,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (repository_url) REFERENCES repositories(repository_url),
+                FOREIGN KEY (contribution_id) REFERENCES contributions(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS agent_rules (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                instruction TEXT NOT NULL,
+                priority INTEGER DEFAULT 0,
+                category TEXT,
+                examples TEXT,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS prompts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                prompt_text TEXT NOT NULL,
+                description TEXT,
+                category TEXT,
+                variables TEXT,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS loadouts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                is_default INTEGER DEFAULT 0,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS loadout_prompts (
+                loadout_id INTEGER NOT NULL,
+                prompt_id INTEGER NOT NULL,
+                PRIMARY KEY (loadout_id, prompt_id),
+                FOREIGN KEY (loadout_id) REFERENCES loadouts(id) ON DELETE CASCADE,
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS loadout_rules (
+                loadout_id INTEGER NOT NULL,
+                rule_id INTEGER NOT NULL,
+                PRIMARY KEY (loadout_id, rule_id),
+                FOREIGN KEY (loadout_id) REFERENCES loadouts(id) ON DELETE CASCADE,
+                FOREIGN KEY (rule_id) REFERENCES agent_rules(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_contributions_repo ON contributions(repository_url);
+            CREATE INDEX IF NOT EXISTS idx_commits_repo ON commits(repository_url);
+            CREATE INDEX IF NOT EXISTS idx_commits_contribution ON commits(contribution_id);
+        ",
+    },
+    Migration {
+        version: 2,
+        description: "Add sync_state table for incremental updates",
+        sql: "
+            CREATE TABLE IF NOT EXISTS sync_state (
+                repository_url TEXT PRIMARY KEY,
+                head_oid TEXT NOT NULL,
+                refs TEXT,
+                synced_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (repository_url) REFERENCES repositories(repository_url)
+            );
+        ",
+    },
+    Migration {
+        version: 3,
+        description: "Add identities and identity_aliases tables",
+        sql: "
+            CREATE TABLE identities (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+
+            -- Empty strings rather than NULLs so the UNIQUE constraint applies
+            CREATE TABLE identity_aliases (
+                identity_id INTEGER NOT NULL,
+                name TEXT NOT NULL DEFAULT '' COLLATE NOCASE,
+                email TEXT NOT NULL DEFAULT '' COLLATE NOCASE,
+                PRIMARY KEY (identity_id, name, email),
+                FOREIGN KEY (identity_id) REFERENCES identities(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX idx_identity_aliases_name ON identity_aliases(name);
+            CREATE INDEX idx_identity_aliases_email ON identity_aliases(email);
+        ",
+    },
+    Migration {
+        version: 4,
+        description: "Record how each commit was linked to its contribution",
+        sql: "
+            ALTER TABLE commits ADD COLUMN link_source TEXT;
+            -- Until now every link came from key_commits or related_commits
+            UPDATE commits SET link_source = 'hash' WHERE contribution_id IS NOT NULL;
+        ",
+    },
+    Migration {
+        version: 5,
+        description: "Add ref_tips table recording the tip of each walked ref",
+        sql: "
+            CREATE TABLE ref_tips (
+                repository_url TEXT NOT NULL,
+                ref_name TEXT NOT NULL,
+                tip_hash TEXT NOT NULL,
+                PRIMARY KEY (repository_url, ref_name)
+            );
+        ",
+    },
+    Migration {
+        version: 6,
+        description: "Add commit_files table with per-file status and line counts",
+        sql: "
+            CREATE TABLE commit_files (
+                repository_url TEXT NOT NULL,
+                commit_hash TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                old_path TEXT,
+                new_path TEXT,
+                status TEXT NOT NULL,
+                lines_added INTEGER NOT NULL DEFAULT 0,
+                lines_deleted INTEGER NOT NULL DEFAULT 0,
+                is_binary INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (commit_hash, position)
+            );
+
+            CREATE INDEX idx_commit_files_new_path ON commit_files(new_path);
+            CREATE INDEX idx_commit_files_repo ON commit_files(repository_url);
+        ",
+    },
+    Migration {
+        version: 7,
+        description: "Add meaningful line counts that leave out generated, vendored and lockfile changes",
+        sql: "
+            ALTER TABLE commits ADD COLUMN meaningful_lines_added INTEGER;
+            ALTER TABLE commits ADD COLUMN meaningful_lines_deleted INTEGER;
+            ALTER TABLE commit_files ADD COLUMN is_excluded INTEGER NOT NULL DEFAULT 0;
+        ",
+    },
+    Migration {
+        version: 8,
+        description: "Add commit_authors for authors, co-authors and committers",
+        sql: "
+            CREATE TABLE commit_authors (
+                repository_url TEXT NOT NULL,
+                commit_hash TEXT NOT NULL,
+                name TEXT NOT NULL,
+                email TEXT NOT NULL,
+                role TEXT NOT NULL CHECK (role IN ('author', 'co-author', 'committer')),
+                PRIMARY KEY (commit_hash, role, name, email)
+            );
+            CREATE INDEX idx_commit_authors_email ON commit_authors(email COLLATE NOCASE);
+            CREATE INDEX idx_commit_authors_repo ON commit_authors(repository_url);
+            INSERT INTO commit_authors (repository_url, commit_hash, name, email, role)
+                SELECT repository_url, commit_hash, author, author_email, 'author' FROM commits;
+        ",
+    },
+    Migration {
+        version: 9,
+        description: "Add the release tag each commit first shipped in",
+        sql: "
+            ALTER TABLE commits ADD COLUMN released_in TEXT;
+            CREATE TABLE release_tips (
+                repository_url TEXT NOT NULL,
+                tag_name TEXT NOT NULL,
+                tip_hash TEXT NOT NULL,
+                PRIMARY KEY (repository_url, tag_name)
+            );
+        ",
+    },
+    Migration {
+        version: 10,
+        description: "Add surviving line counts and a blame cache keyed by blob",
+        sql: "
+            ALTER TABLE commits ADD COLUMN surviving_lines INTEGER;
+            CREATE TABLE blame_cache (
+                repository_url TEXT NOT NULL,
+                path TEXT NOT NULL,
+                blob_oid TEXT NOT NULL,
+                commit_hash TEXT NOT NULL,
+                lines INTEGER NOT NULL,
+                PRIMARY KEY (repository_url, path, blob_oid, commit_hash)
+            );
+        ",
+    },
+    Migration {
+        version: 11,
+        description: "Record where each repository is checked out",
+        sql: "ALTER TABLE repositories ADD COLUMN local_path TEXT;",
+    },
+    Migration {
+        version: 12,
+        description: "Store author and committer times as epoch seconds with their UTC offsets",
+        // Author times of existing commits come from their UTC date; the next
+        // update, full or not, reads both times and offsets from the repository
+        sql: "
+            ALTER TABLE commits ADD COLUMN author_time INTEGER;
+            ALTER TABLE commits ADD COLUMN author_offset INTEGER;
+            ALTER TABLE commits ADD COLUMN committer_time INTEGER;
+            ALTER TABLE commits ADD COLUMN committer_offset INTEGER;
+            UPDATE commits SET author_time = CAST(strftime('%s', date) AS INTEGER), author_offset = 0;
+            CREATE INDEX idx_commits_author_time ON commits(repository_url, author_time);
+            CREATE INDEX idx_commits_committer_time ON commits(repository_url, committer_time);
+        ",
+    },
+    Migration {
+        version: 13,
+        description: "Record patch-ids and reverts, and mark reverted commits",
+        sql: "
+            ALTER TABLE commits ADD COLUMN patch_id TEXT;
+            ALTER TABLE commits ADD COLUMN inverse_patch_id TEXT;
+            ALTER TABLE commits ADD COLUMN reverted_by TEXT;
+            CREATE TABLE commit_reverts (
+                revert_hash TEXT NOT NULL,
+                reverted_hash TEXT NOT NULL,
+                repository_url TEXT NOT NULL,
+                source TEXT NOT NULL CHECK (source IN ('message', 'patch')),
+                PRIMARY KEY (revert_hash, reverted_hash)
+            );
+            CREATE INDEX idx_commit_reverts_reverted ON commit_reverts(reverted_hash);
+        ",
+    },
+    Migration {
+        version: 14,
+        description: "Index patch-ids to group equivalent commits",
+        sql: "CREATE INDEX idx_commits_patch_id ON commits(repository_url, patch_id);",
+    },
+    Migration {
+        version: 15,
+        description: "Move key and related commits and message links into a contribution_commits table",
+        sql: "
+            -- 'hash' rows are the key and related commits a contribution lists,
+            -- as typed; the others are commits whose message names it
+            CREATE TABLE contribution_commits (
+                contribution_id INTEGER NOT NULL,
+                commit_hash TEXT NOT NULL,
+                source TEXT NOT NULL CHECK (source IN ('hash', 'trailer', 'regex')),
+                role TEXT CHECK (role IN ('key', 'related')),
+                position INTEGER,
+                PRIMARY KEY (contribution_id, commit_hash, source),
+                FOREIGN KEY (contribution_id) REFERENCES contributions(id),
+                CHECK ((source = 'hash') = (role IS NOT NULL AND position IS NOT NULL))
+            );
+            CREATE INDEX idx_contribution_commits_hash ON contribution_commits(commit_hash);
+            -- A hash listed both ways stays a key commit
+            INSERT OR IGNORE INTO contribution_commits (contribution_id, commit_hash, source, role, position)
+                SELECT c.id, trim(j.value), 'hash', 'key', j.key
+                FROM contributions c,
+                     json_each(CASE WHEN json_valid(c.key_commits) THEN c.key_commits ELSE '[]' END) j
+                WHERE trim(j.value) != '';
+            INSERT OR IGNORE INTO contribution_commits (contribution_id, commit_hash, source, role, position)
+                SELECT c.id, trim(j.value), 'hash', 'related', j.key
+                FROM contributions c,
+                     json_each(CASE WHEN json_valid(c.related_commits) THEN c.related_commits ELSE '[]' END) j
+                WHERE trim(j.value) != '';
+            INSERT OR IGNORE INTO contribution_commits (contribution_id, commit_hash, source)
+                SELECT contribution_id, commit_hash, link_source FROM commits
+                WHERE contribution_id IS NOT NULL AND link_source IN ('trailer', 'regex');
+            ALTER TABLE contributions DROP COLUMN key_commits;
+            ALTER TABLE contributions DROP COLUMN related_commits;
+        ",
+    },
+];
+
+/// The schema version a fully migrated database is at
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
@@ -0,0 +1,263 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::config::Config;
+use crate::database::{Commit, Contribution, Database, Repository};
+use crate::git;
+use crate::template::contribution_value;
+
+/// Filters for `contrack portfolio`; `None` means no restriction
+#[derive(Debug, Default)]
+pub struct PortfolioFilter {
+    /// Organization id or display name (case-insensitive)
+    pub organization: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Category (case-insensitive)
+    pub category: Option<String>,
+    pub min_priority: Option<u8>,
+}
+
+impl PortfolioFilter {
+    fn has_date_range(&self) -> bool {
+        self.since.is_some() || self.until.is_some()
+    }
+
+    fn matches_organization(&self, id: &str, name: &str) -> bool {
+        match &self.organization {
+            Some(org) => org.eq_ignore_ascii_case(id) || org.eq_ignore_ascii_case(name),
+            None => true,
+        }
+    }
+
+    fn matches_contribution(&self, contrib: &Contribution) -> bool {
+        if let Some(category) = &self.category {
+            if !category.eq_ignore_ascii_case(&contrib.category) {
+                return false;
+            }
+        }
+        self.min_priority.is_none_or(|min| contrib.priority >= min)
+    }
+
+    fn matches_commit(&self, commit: &Commit) -> bool {
+        let Ok(date) = DateTime::parse_from_rfc3339(&commit.date) else {
+            return !self.has_date_range();
+        };
+        let date = date.with_timezone(&Utc);
+        self.since.is_none_or(|since| date >= since) && self.until.is_none_or(|until| date <= until)
+    }
+}
+
+/// A repository's contributions that passed the filter
+pub struct PortfolioRepository {
+    pub repository: Repository,
+    pub contributions: Vec<(Contribution, Vec<Commit>)>,
+}
+
+/// Organization id, display name, description and repositories
+pub struct PortfolioOrganization {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub repositories: Vec<PortfolioRepository>,
+}
+
+/// Collect contributions from every tracked repository, grouped by organization
+/// and then by repository. Repositories are ordered by their most important
+/// contribution, and contributions by priority.
+pub fn collect(db: &Database, config: &Config, filter: &PortfolioFilter) -> Result<Vec<PortfolioOrganization>> {
+    let mut organizations: BTreeMap<String, PortfolioOrganization> = BTreeMap::new();
+
+    for repo in db.get_all_repositories()? {
+        // Organization comes from config.toml, falling back to the database
+        let org_id = config
+            .repositories
+            .get(&repo.url)
+            .map(|r| r.organization.clone())
+            .unwrap_or_else(|| repo.organization.clone());
+        let org_info = config.organizations.get(&org_id);
+        let org_name = org_info.map(|o| o.name.clone()).unwrap_or_else(|| org_id.clone());
+
+        if !filter.matches_organization(&org_id, &org_name) {
+            continue;
+        }
+
+        let membership = git::RefMembership::for_checkout(repo.local_path.as_deref(), &db.get_ref_tips(&repo.url)?);
+        let mut contributions = Vec::new();
+        for contrib in db.get_contributions(&repo.url)? {
+            if !filter.matches_contribution(&contrib) {
+                continue;
+            }
+            let mut commits: Vec<Commit> = db
+                .get_commits_for_contribution(&repo.url, &contrib.name)?
+                .into_iter()
+                .filter(|c| filter.matches_commit(c))
+                .collect();
+            if let Some(membership) = &membership {
+                membership.fill(&mut commits);
+            }
+            // With a date range, only contributions with work inside it are kept
+            if filter.has_date_range() && commits.is_empty() {
+                continue;
+            }
+            contributions.push((contrib, commits));
+        }
+
+        if contributions.is_empty() {
+            continue;
+        }
+        contributions.sort_by(|a, b| b.0.priority.cmp(&a.0.priority).then_with(|| a.0.name.cmp(&b.0.name)));
+
+        organizations
+            .entry(org_id.clone())
+            .or_insert_with(|| PortfolioOrganization {
+                id: org_id,
+                name: org_name,
+                description: org_info.and_then(|o| o.description.clone()),
+                repositories: Vec::new(),
+            })
+            .repositories
+            .push(PortfolioRepository { repository: repo, contributions });
+    }
+
+    let mut organizations: Vec<_> = organizations.into_values().collect();
+    organizations.sort_by_key(|o| o.name.to_lowercase());
+    for org in &mut organizations {
+        org.repositories.sort_by(|a, b| {
+            let top = |r: &PortfolioRepository| r.contributions.first().map(|(c, _)| c.priority).unwrap_or(0);
+            top(b).cmp(&top(a)).then_with(|| a.repository.name.cmp(&b.repository.name))
+        });
+    }
+
+    Ok(organizations)
+}
+
+/// First and last commit day of a contribution
+fn date_range(commits: &[Commit]) -> (Option<String>, Option<String>) {
+    let mut dates: Vec<&str> = commits.iter().filter_map(|c| c.date.get(..10)).collect();
+    dates.sort_unstable();
+    (
+        dates.first().map(|d| d.to_string()),
+        dates.last().map(|d| d.to_string()),
+    )
+}
+
+/// Build the portfolio template context:
+/// - `organizations`: `id`, `name`, `description` and `repositories`, each repository with
+///   `url`, `name`, `description` and `contributions` (as in `generate` templates, plus
+///   `start_date` and `end_date`)
+/// - `filters`: the filters that were applied
+/// - `stats`: `organizations`, `repositories`, `contributions`, `commits`, `lines_added`, `lines_deleted`
+pub fn build_context(organizations: &[PortfolioOrganization], filter: &PortfolioFilter) -> Value {
+    let mut repo_count = 0;
+    let mut contribution_count = 0;
+    let mut commit_count = 0;
+    let mut lines_added = 0i64;
+    let mut lines_deleted = 0i64;
+
+    let organization_values: Vec<Value> = organizations
+        .iter()
+        .map(|org| {
+            let repositories: Vec<Value> = org
+                .repositories
+                .iter()
+                .map(|repo| {
+                    repo_count += 1;
+                    let contributions: Vec<Value> = repo
+                        .contributions
+                        .iter()
+                        .map(|(contrib, commits)| {
+                            contribution_count += 1;
+                            commit_count += commits.len();
+                            lines_added += commits.iter().filter_map(|c| c.meaningful_lines().0).map(i64::from).sum::<i64>();
+                            lines_deleted += commits.iter().filter_map(|c| c.meaningful_lines().1).map(i64::from).sum::<i64>();
+
+                            let commit_refs: Vec<&Commit> = commits.iter().collect();
+                            let mut value = contribution_value(contrib, &commit_refs);
+                            let (start, end) = date_range(commits);
+                            value["start_date"] = json!(start);
+                            value["end_date"] = json!(end);
+                            value
+                        })
+                        .collect();
+
+                    json!({
+                        "url": repo.repository.url,
+                        "name": repo.repository.name,
+                        "description": repo.repository.description,
+                        "contributions": contributions,
+                    })
+                })
+                .collect();
+
+            json!({
+                "id": org.id,
+                "name": org.name,
+                "description": org.description,
+                "repositories": repositories,
+            })
+        })
+        .collect();
+
+    json!({
+        "organizations": organization_values,
+        "filters": {
+            "organization": filter.organization,
+            "since": filter.since.map(|d| d.format("%Y-%m-%d").to_string()),
+            "until": filter.until.map(|d| d.format("%Y-%m-%d").to_string()),
+            "category": filter.category,
+            "min_priority": filter.min_priority,
+        },
+        "stats": {
+            "organizations": organizations.len(),
+            "repositories": repo_count,
+            "contributions": contribution_count,
+            "commits": commit_count,
+            "lines_added": lines_added,
+            "lines_deleted": lines_deleted,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::parse_date;
+
+    fn commit(date: &str) -> Commit {
+        Commit {
+            hash: "abc123".to_string(),
+            repository_url: "https://github.com/org/repo".to_string(),
+            author: "Ann".to_string(),
+            author_email: "ann@example.com".to_string(),
+            date: date.to_string(),
+            message: "message".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_date_range_filter() {
+        let filter = PortfolioFilter {
+            since: Some(parse_date("2024-01-01", false).unwrap()),
+            until: Some(parse_date("2024-01-31", true).unwrap()),
+            ..Default::default()
+        };
+        assert!(filter.matches_commit(&commit("2024-01-31T23:00:00+00:00")));
+        assert!(!filter.matches_commit(&commit("2023-12-31T23:00:00+00:00")));
+        assert!(!filter.matches_commit(&commit("2024-02-01T00:00:00+00:00")));
+    }
+
+    #[test]
+    fn test_organization_filter_matches_id_or_name() {
+        let filter = PortfolioFilter {
+            organization: Some("ACME".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.matches_organization("acme", "Acme Corp"));
+        assert!(filter.matches_organization("acme-corp", "acme"));
+        assert!(!filter.matches_organization("other", "Other Inc"));
+    }
+}
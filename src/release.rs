@@ -0,0 +1,92 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::cmp::Ordering;
+
+use crate::config::{Config, ReleasesConfig};
+use crate::database::{Commit, Contribution};
+
+/// Compile the pattern that picks release tags out of all tags
+pub fn tag_pattern(config: &ReleasesConfig) -> Result<Regex> {
+    Regex::new(&config.tag_pattern)
+        .with_context(|| format!("Invalid release tag pattern '{}' in config.toml", config.tag_pattern))
+}
+
+/// The release tag pattern from config.toml, or the default when there is no config file
+pub fn load_tag_pattern() -> Result<Regex> {
+    tag_pattern(&Config::load()?.releases)
+}
+
+/// Compare tag names as versions, so that `v1.10` sorts after `v1.9`
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (chunks(a), chunks(b));
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => {
+                let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+                    (Ok(x), Ok(y)) => x.cmp(&y),
+                    _ => x.cmp(y),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+/// Runs of digits and runs of everything else
+fn chunks(tag: &str) -> impl Iterator<Item = &str> {
+    let mut rest = tag;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != first.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+/// The releases a contribution's key commits first shipped in, e.g. `v2.3` or
+/// `v2.3 – v2.5`; `None` when no key commit has been released
+pub fn released_range(contrib: &Contribution, commits: &[&Commit]) -> Option<String> {
+    let mut releases: Vec<&str> = contrib
+        .key_commits
+        .iter()
+        .filter_map(|hash| commits.iter().find(|c| c.hash.starts_with(hash.as_str())))
+        .filter_map(|c| c.released_in.as_deref())
+        .collect();
+    releases.sort_by(|a, b| compare_versions(a, b));
+
+    let (first, last) = (releases.first()?, releases.last()?);
+    if first == last {
+        Some(first.to_string())
+    } else {
+        Some(format!("{} – {}", first, last))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_versions() {
+        let mut tags = vec!["v1.10.0", "v1.9.2", "v2.0", "v1.9.10", "v1.9"];
+        tags.sort_by(|a, b| compare_versions(a, b));
+        assert_eq!(tags, vec!["v1.9", "v1.9.2", "v1.9.10", "v1.10.0", "v2.0"]);
+    }
+
+    #[test]
+    fn test_default_pattern_picks_release_tags() {
+        let pattern = tag_pattern(&ReleasesConfig::default()).unwrap();
+        assert!(pattern.is_match("v2.3"));
+        assert!(pattern.is_match("1.0.0"));
+        assert!(!pattern.is_match("nightly-2024-01-01"));
+        assert!(!pattern.is_match("v2.3-rc1"));
+    }
+}
@@ -0,0 +1,476 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::database::{Commit, Contribution, LineTotals, Repository};
+use crate::identity::AuthorFilter;
+use crate::markdown;
+use crate::release::released_range;
+use crate::utils::short_hash;
+
+/// Data every output format renders from
+pub struct RenderInput<'a> {
+    pub repo_url: &'a str,
+    pub repository: Option<&'a Repository>,
+    pub contributions: &'a [(Contribution, Vec<Commit>)],
+    pub author_filter: Option<&'a AuthorFilter>,
+}
+
+/// An output format for `generate`. New formats implement this trait and are
+/// registered in `OutputFormat::renderer`.
+pub trait Renderer {
+    fn render(&self, input: &RenderInput) -> Result<String>;
+
+    /// File extension used for the default output path
+    fn extension(&self) -> &'static str;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Markdown,
+    Json,
+    Html,
+    /// JSON Resume (https://jsonresume.org) with contributions as projects
+    Jsonresume,
+}
+
+impl OutputFormat {
+    pub fn renderer(self) -> Box<dyn Renderer> {
+        match self {
+            OutputFormat::Markdown => Box::new(MarkdownRenderer),
+            OutputFormat::Json => Box::new(JsonRenderer),
+            OutputFormat::Html => Box::new(HtmlRenderer),
+            OutputFormat::Jsonresume => Box::new(JsonResumeRenderer),
+        }
+    }
+}
+
+/// Contributions sorted by priority, with commits filtered by author.
+/// Contributions without a matching commit are dropped when filtering by author.
+fn visible_contributions<'a>(input: &RenderInput<'a>) -> Vec<(&'a Contribution, Vec<&'a Commit>)> {
+    let mut visible: Vec<_> = input
+        .contributions
+        .iter()
+        .map(|(contrib, commits)| {
+            let commits: Vec<&Commit> = match input.author_filter {
+                Some(filter) => commits.iter().filter(|c| filter.matches_commit(c)).collect(),
+                None => commits.iter().collect(),
+            };
+            (contrib, commits)
+        })
+        .filter(|(_, commits)| input.author_filter.is_none() || !commits.is_empty())
+        .collect();
+
+    visible.sort_by(|a, b| b.0.priority.cmp(&a.0.priority).then_with(|| a.0.name.cmp(&b.0.name)));
+    visible
+}
+
+/// " · also as <code>…</code>" listing the other commits that make the same change
+fn also_as(commit: &Commit, commits: &[&Commit]) -> String {
+    let Some(id) = &commit.patch_id else { return String::new() };
+    let others: Vec<String> = commits
+        .iter()
+        .filter(|c| c.hash != commit.hash && c.patch_id.as_ref() == Some(id))
+        .map(|c| format!("<code>{}</code>", short_hash(&c.hash)))
+        .collect();
+    match others.is_empty() {
+        true => String::new(),
+        false => format!(" · also as {}", others.join(", ")),
+    }
+}
+
+/// Key commits of a contribution, resolved against its commits where possible
+fn key_commits<'a>(contrib: &'a Contribution, commits: &[&'a Commit]) -> Vec<(&'a str, Option<&'a Commit>)> {
+    contrib
+        .key_commits
+        .iter()
+        .map(|hash| (hash.as_str(), commits.iter().copied().find(|c| c.hash.starts_with(hash.as_str()))))
+        .collect()
+}
+
+fn first_line(message: &str) -> &str {
+    message.lines().next().unwrap_or("")
+}
+
+pub struct MarkdownRenderer;
+
+impl Renderer for MarkdownRenderer {
+    fn render(&self, input: &RenderInput) -> Result<String> {
+        markdown::generate_markdown(input)
+    }
+
+    fn extension(&self) -> &'static str {
+        "md"
+    }
+}
+
+pub struct JsonRenderer;
+
+impl Renderer for JsonRenderer {
+    fn render(&self, input: &RenderInput) -> Result<String> {
+        let mut contributions = Vec::new();
+        for (contrib, commits) in visible_contributions(input) {
+            let mut value = serde_json::to_value(contrib)?;
+            if let Some(range) = released_range(contrib, &commits) {
+                value["released_in"] = json!(range);
+            }
+            value["commits"] = serde_json::to_value(&commits)?;
+            contributions.push(value);
+        }
+
+        let document = json!({
+            "repository": input.repo_url,
+            "author_filter": input.author_filter.map(|f| &f.label),
+            "contributions": contributions,
+        });
+
+        Ok(serde_json::to_string_pretty(&document)? + "\n")
+    }
+
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+}
+
+/// `+A -D`, with surviving lines when blame has run
+fn line_summary(totals: LineTotals) -> String {
+    let mut summary = format!("+{} -{}", totals.lines_added, totals.lines_deleted);
+    if let Some(lines) = totals.surviving_lines {
+        summary.push_str(&format!(" · {} lines still in production", lines));
+    }
+    summary
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+pub struct HtmlRenderer;
+
+impl Renderer for HtmlRenderer {
+    fn render(&self, input: &RenderInput) -> Result<String> {
+        let mut output = String::new();
+        let repo_url = escape_html(input.repo_url);
+
+        output.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n");
+        output.push_str("<meta charset=\"utf-8\">\n");
+        output.push_str("<title>Contributions</title>\n");
+        output.push_str("</head>\n<body>\n");
+        output.push_str("<h1>Contributions</h1>\n");
+        output.push_str(&format!(
+            "<p><strong>Repository:</strong> <a href=\"{0}\">{0}</a></p>\n",
+            repo_url
+        ));
+
+        let visible = visible_contributions(input);
+        for (contrib, commits) in &visible {
+            output.push_str("<section class=\"contribution\">\n");
+            output.push_str(&format!("<h2>{}</h2>\n", escape_html(&contrib.name)));
+            output.push_str(&format!(
+                "<p><strong>Category:</strong> {} | <strong>Priority:</strong> {}{}</p>\n",
+                escape_html(&contrib.category),
+                contrib.priority,
+                match released_range(contrib, commits) {
+                    Some(range) => format!(" | <strong>First released in:</strong> {}", escape_html(&range)),
+                    None => String::new(),
+                }
+            ));
+            output.push_str(&format!("<p><strong>Changes:</strong> {}</p>\n", line_summary(LineTotals::of(commits.iter().copied()))));
+            output.push_str(&format!("<p>{}</p>\n", escape_html(&contrib.overview)));
+            output.push_str(&format!("<p>{}</p>\n", escape_html(&contrib.description)));
+
+            if !contrib.key_commits.is_empty() {
+                output.push_str("<h3>Key Commits</h3>\n<ul>\n");
+                for (hash, commit) in key_commits(contrib, commits) {
+                    match commit {
+                        Some(commit) => output.push_str(&format!(
+                            "<li><code>{}</code> {}{} <em>({}, {})</em>{}{}</li>\n",
+                            short_hash(&commit.hash),
+                            escape_html(first_line(&commit.message)),
+                            if commit.is_reverted() { " <em>(reverted)</em>" } else { "" },
+                            escape_html(&commit.author),
+                            escape_html(&commit.date),
+                            match commit.meaningful_lines() {
+                                (Some(added), Some(deleted)) => format!(" +{} -{}", added, deleted),
+                                _ => String::new(),
+                            },
+                            also_as(commit, commits)
+                        )),
+                        None => output.push_str(&format!("<li><code>{}</code></li>\n", escape_html(hash))),
+                    }
+                }
+                output.push_str("</ul>\n");
+            }
+
+            if !contrib.technical_details.is_empty() {
+                let mut details: Vec<_> = contrib.technical_details.iter().collect();
+                details.sort_by(|a, b| a.0.cmp(b.0));
+                output.push_str("<h3>Technical Details</h3>\n<dl>\n");
+                for (key, value) in details {
+                    output.push_str(&format!(
+                        "<dt>{}</dt><dd>{}</dd>\n",
+                        escape_html(key),
+                        escape_html(&value.to_string())
+                    ));
+                }
+                output.push_str("</dl>\n");
+            }
+
+            if !contrib.resume_bullets.is_empty() {
+                output.push_str("<h3>Resume Bullet Points</h3>\n<ul>\n");
+                for bullet in &contrib.resume_bullets {
+                    output.push_str(&format!("<li>{}</li>\n", escape_html(bullet)));
+                }
+                output.push_str("</ul>\n");
+            }
+
+            output.push_str("</section>\n");
+        }
+
+        output.push_str(&format!(
+            "<footer>\n<p>Total contributions documented: {}</p>\n",
+            visible.len()
+        ));
+        let totals = LineTotals::of(visible.iter().flat_map(|(_, commits)| commits.iter().copied()));
+        output.push_str(&format!(
+            "<p>Lines changed: +{} -{} (excluding generated, vendored and lockfile changes){}</p>\n",
+            totals.lines_added,
+            totals.lines_deleted,
+            match totals.surviving_lines {
+                Some(lines) => format!(" · {} lines still in production", lines),
+                None => String::new(),
+            }
+        ));
+        if let Some(filter) = input.author_filter {
+            output.push_str(&format!("<p><em>Filtered by author: {}</em></p>\n", escape_html(&filter.label)));
+        }
+        output.push_str("<p><em>This document was generated by contrack.</em></p>\n</footer>\n");
+        output.push_str("</body>\n</html>\n");
+
+        Ok(output)
+    }
+
+    fn extension(&self) -> &'static str {
+        "html"
+    }
+}
+
+pub struct JsonResumeRenderer;
+
+impl JsonResumeRenderer {
+    /// Keywords come from the `technology_stack` technical detail
+    fn keywords(contrib: &Contribution) -> Vec<Value> {
+        match contrib.technical_details.get("technology_stack") {
+            Some(Value::Array(items)) => items.clone(),
+            Some(Value::String(item)) => vec![Value::String(item.clone())],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Renderer for JsonResumeRenderer {
+    fn render(&self, input: &RenderInput) -> Result<String> {
+        let mut projects = Vec::new();
+        for (contrib, commits) in visible_contributions(input) {
+            let mut project = json!({
+                "name": contrib.name,
+                "description": contrib.overview,
+                "highlights": contrib.resume_bullets,
+                "keywords": Self::keywords(contrib),
+                "url": input.repo_url,
+                "type": contrib.category,
+            });
+
+            // Dates are the day of the earliest and latest commit
+            let mut dates: Vec<&str> = commits.iter().filter_map(|c| c.date.get(..10)).collect();
+            dates.sort_unstable();
+            if let (Some(start), Some(end)) = (dates.first(), dates.last()) {
+                project["startDate"] = json!(start);
+                project["endDate"] = json!(end);
+            }
+
+            projects.push(project);
+        }
+
+        let mut document = json!({
+            "$schema": "https://raw.githubusercontent.com/jsonresume/resume-schema/v1.0.0/schema.json",
+            "projects": projects,
+            "meta": {
+                "canonical": input.repo_url,
+                "version": "v1.0.0",
+            },
+        });
+        if let Some(filter) = input.author_filter {
+            document["basics"] = json!({ "name": filter.label });
+        }
+
+        Ok(serde_json::to_string_pretty(&document)? + "\n")
+    }
+
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    const REPO: &str = "https://github.com/org/repo";
+
+    fn commit(hash: &str, author: &str, date: &str, message: &str) -> Commit {
+        Commit {
+            hash: hash.to_string(),
+            repository_url: REPO.to_string(),
+            contribution_id: Some(1),
+            author: author.to_string(),
+            author_email: format!("{}@example.com", author.to_lowercase()),
+            date: date.to_string(),
+            message: message.to_string(),
+            files_changed: vec!["src/lib.rs".to_string()],
+            lines_added: Some(10),
+            lines_deleted: Some(2),
+            ..Default::default()
+        }
+    }
+
+    fn fixture() -> Vec<(Contribution, Vec<Commit>)> {
+        let mut details = HashMap::new();
+        details.insert("technology_stack".to_string(), json!(["Rust", "SQLite"]));
+        details.insert("storage".to_string(), json!("SQLite <embedded>"));
+
+        let sync = Contribution {
+            id: Some(1),
+            repository_url: REPO.to_string(),
+            name: "Sync Engine".to_string(),
+            overview: "Incremental sync & storage".to_string(),
+            description: "Keeps the database in step with git.".to_string(),
+            key_commits: vec!["aaaaaaaa".to_string(), "deadbeef".to_string()],
+            related_commits: vec!["bbbbbbbb".to_string()],
+            technical_details: details,
+            resume_bullets: vec!["Cut sync time from minutes to seconds".to_string()],
+            category: "Core Feature".to_string(),
+            priority: 9,
+        };
+        // The watermark commit also touched a lockfile, left out of meaningful counts
+        let mut watermark = commit("aaaaaaaa11111111", "Ann", "2024-03-02T10:00:00+00:00", "Add sync watermark\n\nDetails");
+        watermark.meaningful_lines_added = Some(4);
+        watermark.meaningful_lines_deleted = Some(2);
+        watermark.released_in = Some("v1.2.0".to_string());
+        watermark.surviving_lines = Some(3);
+        let sync_commits = vec![
+            watermark,
+            commit("bbbbbbbb22222222", "Bob", "2024-03-01T09:00:00+00:00", "Refactor walker"),
+        ];
+
+        let docs = Contribution {
+            id: Some(2),
+            repository_url: REPO.to_string(),
+            name: "Docs".to_string(),
+            overview: "User guide".to_string(),
+            description: "Wrote the user guide.".to_string(),
+            key_commits: vec!["cccccccc".to_string()],
+            related_commits: Vec::new(),
+            technical_details: HashMap::new(),
+            resume_bullets: Vec::new(),
+            category: "Feature".to_string(),
+            priority: 4,
+        };
+        let docs_commits = vec![commit("cccccccc33333333", "Bob", "2024-04-01T12:00:00+00:00", "Write guide")];
+
+        vec![(sync, sync_commits), (docs, docs_commits)]
+    }
+
+    /// Compare against `tests/golden/<name>`; set UPDATE_GOLDEN=1 to rewrite the file
+    fn assert_golden(name: &str, actual: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, actual).unwrap();
+        }
+        let expected = std::fs::read_to_string(&path)
+            .unwrap_or_else(|_| panic!("Missing golden file {:?}; run with UPDATE_GOLDEN=1", path));
+        assert_eq!(actual, expected, "Output does not match golden file {:?}", path);
+    }
+
+    fn render(format: OutputFormat, author_filter: Option<&str>) -> String {
+        let contributions = fixture();
+        let author_filter = author_filter.map(AuthorFilter::exact);
+        let input = RenderInput {
+            repo_url: REPO,
+            repository: None,
+            contributions: &contributions,
+            author_filter: author_filter.as_ref(),
+        };
+        format.renderer().render(&input).unwrap()
+    }
+
+    #[test]
+    fn test_markdown_golden() {
+        assert_golden("contributions.md", &render(OutputFormat::Markdown, None));
+    }
+
+    #[test]
+    fn test_json_golden() {
+        assert_golden("contributions.json", &render(OutputFormat::Json, None));
+    }
+
+    #[test]
+    fn test_html_golden() {
+        assert_golden("contributions.html", &render(OutputFormat::Html, None));
+    }
+
+    #[test]
+    fn test_jsonresume_golden() {
+        assert_golden("contributions.resume.json", &render(OutputFormat::Jsonresume, Some("Ann")));
+    }
+
+    #[test]
+    fn test_author_filter_drops_contributions_without_commits() {
+        let output: Value = serde_json::from_str(&render(OutputFormat::Json, Some("Ann"))).unwrap();
+        let contributions = output["contributions"].as_array().unwrap();
+        assert_eq!(contributions.len(), 1);
+        assert_eq!(contributions[0]["name"], "Sync Engine");
+        assert_eq!(contributions[0]["commits"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_author_filter_credits_co_authors() {
+        use crate::database::{AuthorRole, CommitAuthor};
+
+        let mut contributions = fixture();
+        let guide = &mut contributions[1].1[0];
+        guide.authors = vec![
+            CommitAuthor { name: "Bob".to_string(), email: "bob@example.com".to_string(), role: AuthorRole::Author },
+            CommitAuthor { name: "Ann".to_string(), email: "ann@example.com".to_string(), role: AuthorRole::CoAuthor },
+        ];
+        let author_filter = AuthorFilter::exact("ann@example.com");
+        let input = RenderInput {
+            repo_url: REPO,
+            repository: None,
+            contributions: &contributions,
+            author_filter: Some(&author_filter),
+        };
+
+        let output: Value = serde_json::from_str(&JsonRenderer.render(&input).unwrap()).unwrap();
+        let names: Vec<&str> = output["contributions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["Sync Engine", "Docs"]);
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::database::Commit;
+
+/// Smallest word overlap for two messages by the same author to count as the
+/// same commit reworded
+const SIMILAR_MESSAGE: f64 = 0.6;
+
+/// Why a commit is taken to be the rewritten form of a missing one, strongest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchReason {
+    /// The reflog records the missing commit being amended or rebased into it
+    Reflog,
+    /// Same patch-id: the diff survived the rebase unchanged
+    PatchId,
+    /// Same author and subject line
+    AuthorAndSubject,
+    /// Same author and a largely overlapping message
+    SimilarMessage,
+}
+
+impl MatchReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchReason::Reflog => "amended or rebased, per reflog",
+            MatchReason::PatchId => "same patch-id",
+            MatchReason::AuthorAndSubject => "same author and subject",
+            MatchReason::SimilarMessage => "same author, similar message",
+        }
+    }
+}
+
+/// A listed commit that no longer exists and the commit proposed in its place
+#[derive(Debug, Clone)]
+pub struct Remap {
+    pub contribution_id: i64,
+    pub contribution: String,
+    /// `key` or `related`
+    pub role: &'static str,
+    pub old: String,
+    pub new: String,
+    pub reason: MatchReason,
+}
+
+impl Remap {
+    /// The replacement hash, abbreviated like the one it replaces
+    pub fn replacement(&self) -> &str {
+        &self.new[..self.old.len().clamp(7, self.new.len())]
+    }
+}
+
+/// The commit that `old_hash` was most likely rewritten into. `old` is what is
+/// still known about the missing commit (from the database or the reflog),
+/// `rewrites` maps amended commits to their replacements and `candidates` are
+/// the commits that still exist.
+pub fn find_rewrite(
+    old_hash: &str,
+    old: Option<&Commit>,
+    candidates: &[&Commit],
+    rewrites: &HashMap<String, String>,
+) -> Option<(String, MatchReason)> {
+    let existing: HashSet<&str> = candidates.iter().map(|c| c.hash.as_str()).collect();
+    if let Some(new) = follow_rewrites(old_hash, rewrites).filter(|new| existing.contains(new.as_str())) {
+        return Some((new, MatchReason::Reflog));
+    }
+
+    let old = old?;
+    candidates
+        .iter()
+        .filter(|c| c.hash != old.hash)
+        .filter_map(|c| {
+            let same_author = c.author_email.eq_ignore_ascii_case(&old.author_email);
+            let similarity = message_similarity(&old.message, &c.message);
+            let reason = if c.patch_id.is_some() && c.patch_id == old.patch_id {
+                MatchReason::PatchId
+            } else if same_author && subject(&c.message) == subject(&old.message) {
+                MatchReason::AuthorAndSubject
+            } else if same_author && similarity >= SIMILAR_MESSAGE {
+                MatchReason::SimilarMessage
+            } else {
+                return None;
+            };
+            let distance = match (c.author_time, old.author_time) {
+                (Some(a), Some(b)) => (a - b).abs(),
+                _ => i64::MAX,
+            };
+            Some((reason, !same_author, -similarity, distance, c))
+        })
+        .min_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)).then(a.2.total_cmp(&b.2)).then(a.3.cmp(&b.3)))
+        .map(|(reason, _, _, _, c)| (c.hash.clone(), reason))
+}
+
+/// Follow a chain of amends from a (possibly abbreviated) hash to the last one
+fn follow_rewrites(hash: &str, rewrites: &HashMap<String, String>) -> Option<String> {
+    let mut current = rewrites.iter().find(|(old, _)| old.starts_with(hash))?.1.clone();
+    for _ in 0..rewrites.len() {
+        match rewrites.get(&current) {
+            Some(next) => current = next.clone(),
+            None => break,
+        }
+    }
+    Some(current)
+}
+
+fn subject(message: &str) -> &str {
+    message.lines().next().unwrap_or("").trim()
+}
+
+/// Overlap of the words of two messages, from 0 (none shared) to 1 (same words)
+pub fn message_similarity(a: &str, b: &str) -> f64 {
+    let words = |text: &str| -> HashSet<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_lowercase())
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(hash: &str, email: &str, message: &str, patch_id: Option<&str>) -> Commit {
+        Commit {
+            hash: hash.to_string(),
+            repository_url: "https://github.com/org/repo".to_string(),
+            author: "Ann".to_string(),
+            author_email: email.to_string(),
+            date: "2024-01-01T00:00:00+00:00".to_string(),
+            message: message.to_string(),
+            patch_id: patch_id.map(|p| p.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rewrites_are_matched_strongest_reason_first() {
+        let old = commit("aaaa1111", "ann@example.com", "Add sync engine\n\nFirst cut.", Some("p1"));
+        let reworded = commit("bbbb2222", "ann@example.com", "Add sync engine\n\nReworded body.", Some("p2"));
+        let rebased = commit("cccc3333", "bob@example.com", "Sync engine", Some("p1"));
+        let other = commit("dddd4444", "ann@example.com", "Fix typo in docs", Some("p3"));
+        let candidates = vec![&reworded, &rebased, &other];
+
+        let none = HashMap::new();
+        assert_eq!(
+            find_rewrite("aaaa", Some(&old), &candidates, &none),
+            Some(("cccc3333".to_string(), MatchReason::PatchId))
+        );
+        assert_eq!(
+            find_rewrite("aaaa", Some(&old), &[&reworded, &other], &none),
+            Some(("bbbb2222".to_string(), MatchReason::AuthorAndSubject))
+        );
+        assert_eq!(find_rewrite("aaaa", Some(&old), &[&other], &none), None);
+
+        // An amend recorded in the reflog wins, even when nothing else is known
+        let rewrites = HashMap::from([
+            ("aaaa1111".to_string(), "eeee5555".to_string()),
+            ("eeee5555".to_string(), "dddd4444".to_string()),
+        ]);
+        assert_eq!(
+            find_rewrite("aaaa", None, &candidates, &rewrites),
+            Some(("dddd4444".to_string(), MatchReason::Reflog))
+        );
+    }
+
+    #[test]
+    fn test_message_similarity() {
+        assert_eq!(message_similarity("Add sync engine", "add Sync engine"), 1.0);
+        assert!(message_similarity("Add sync engine with retries", "Add sync engine") >= SIMILAR_MESSAGE);
+        assert!(message_similarity("Add sync engine", "Fix typo in docs") < SIMILAR_MESSAGE);
+    }
+}
@@ -0,0 +1,152 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+use crate::database::{PatchInfo, Revert, RevertSource};
+
+/// Find reverts among a repository's commits. A commit reverts the one named
+/// by a `This reverts commit <hash>` line in its message; failing that, it
+/// reverts the latest earlier commit whose inverse diff has the same patch-id.
+pub fn find_reverts(commits: &[PatchInfo]) -> Vec<Revert> {
+    let message = Regex::new(r"(?m)^This reverts commit ([0-9a-f]{7,40})").unwrap();
+
+    let mut by_inverse: HashMap<&str, Vec<&PatchInfo>> = HashMap::new();
+    for commit in commits {
+        if let Some(inverse) = &commit.inverse_patch_id {
+            by_inverse.entry(inverse.as_str()).or_default().push(commit);
+        }
+    }
+
+    let mut reverts = Vec::new();
+    for commit in commits {
+        let named = message.captures_iter(&commit.message).find_map(|captures| {
+            let prefix = &captures[1];
+            let mut matches = commits.iter().filter(|c| c.hash != commit.hash && c.hash.starts_with(prefix));
+            match (matches.next(), matches.next()) {
+                (Some(target), None) => Some(target),
+                _ => None,
+            }
+        });
+        if let Some(target) = named {
+            reverts.push(Revert {
+                revert_hash: commit.hash.clone(),
+                reverted_hash: target.hash.clone(),
+                source: RevertSource::Message,
+            });
+            continue;
+        }
+
+        let (Some(patch_id), Some(time)) = (&commit.patch_id, commit.author_time) else {
+            continue;
+        };
+        let target = by_inverse
+            .get(patch_id.as_str())
+            .into_iter()
+            .flatten()
+            .filter(|c| c.hash != commit.hash && c.author_time.is_some_and(|t| t < time))
+            .max_by_key(|c| c.author_time);
+        if let Some(target) = target {
+            reverts.push(Revert {
+                revert_hash: commit.hash.clone(),
+                reverted_hash: target.hash.clone(),
+                source: RevertSource::Patch,
+            });
+        }
+    }
+    reverts
+}
+
+/// Reverted commits and the revert that undid each, counting only reverts that
+/// still stand: a revert that was itself reverted no longer undoes its target.
+pub fn standing_reverts(reverts: &[Revert]) -> HashMap<String, String> {
+    let mut reverted_by: HashMap<&str, Vec<&str>> = HashMap::new();
+    for revert in reverts {
+        reverted_by.entry(revert.reverted_hash.as_str()).or_default().push(revert.revert_hash.as_str());
+    }
+
+    let mut standing = HashMap::new();
+    for revert in reverts {
+        if stands(&revert.revert_hash, &reverted_by, &mut HashMap::new(), &mut HashSet::new()) {
+            standing.insert(revert.reverted_hash.clone(), revert.revert_hash.clone());
+        }
+    }
+    standing
+}
+
+/// Whether a commit still stands: none of its reverts stand. Cycles, which
+/// only malformed messages can produce, count as not standing.
+fn stands<'a>(
+    hash: &'a str,
+    reverted_by: &HashMap<&'a str, Vec<&'a str>>,
+    memo: &mut HashMap<&'a str, bool>,
+    visiting: &mut HashSet<&'a str>,
+) -> bool {
+    if let Some(&known) = memo.get(hash) {
+        return known;
+    }
+    if !visiting.insert(hash) {
+        return false;
+    }
+    let result = reverted_by
+        .get(hash)
+        .is_none_or(|reverts| reverts.iter().all(|revert| !stands(revert, reverted_by, memo, visiting)));
+    visiting.remove(hash);
+    memo.insert(hash, result);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(hash: &str, message: &str, time: i64, patch: &str, inverse: &str) -> PatchInfo {
+        PatchInfo {
+            hash: hash.to_string(),
+            message: message.to_string(),
+            author_time: Some(time),
+            patch_id: Some(patch.to_string()),
+            inverse_patch_id: Some(inverse.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_reverts_by_message_and_inverse_patch() {
+        let commits = vec![
+            info("aaaa1111", "Add cache", 1, "p-a", "i-a"),
+            info("bbbb2222", "Revert \"Add cache\"\n\nThis reverts commit aaaa1111.\n", 2, "i-a", "p-a"),
+            info("cccc3333", "Add limits", 3, "p-c", "i-c"),
+            info("dddd4444", "Drop limits again", 4, "i-c", "p-c"),
+        ];
+        let reverts = find_reverts(&commits);
+        assert_eq!(
+            reverts,
+            vec![
+                Revert {
+                    revert_hash: "bbbb2222".to_string(),
+                    reverted_hash: "aaaa1111".to_string(),
+                    source: RevertSource::Message,
+                },
+                Revert {
+                    revert_hash: "dddd4444".to_string(),
+                    reverted_hash: "cccc3333".to_string(),
+                    source: RevertSource::Patch,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reverted_revert_restores_the_original() {
+        let commits = vec![
+            info("aaaa1111", "Add cache", 1, "p-a", "i-a"),
+            info("bbbb2222", "Revert \"Add cache\"", 2, "i-a", "p-a"),
+            info("cccc3333", "Reapply \"Add cache\"", 3, "p-a", "i-a"),
+        ];
+        let reverts = find_reverts(&commits);
+        assert_eq!(reverts.len(), 2);
+
+        let standing = standing_reverts(&reverts);
+        assert_eq!(standing.len(), 1);
+        assert_eq!(standing.get("bbbb2222").map(|s| s.as_str()), Some("cccc3333"));
+        assert!(!standing.contains_key("aaaa1111"));
+    }
+}
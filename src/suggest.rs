@@ -0,0 +1,371 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+
+use crate::database::Commit;
+
+/// Knobs for `contrack suggest`
+#[derive(Debug, Clone)]
+pub struct SuggestOptions {
+    /// Commits sharing a path area or scope are grouped when no further apart than this
+    pub window: Duration,
+    /// Clusters smaller than this are not suggested
+    pub min_commits: usize,
+    /// Maximum number of key commits per suggestion
+    pub key_commits: usize,
+}
+
+impl Default for SuggestOptions {
+    fn default() -> Self {
+        Self {
+            window: Duration::days(7),
+            min_commits: 2,
+            key_commits: 3,
+        }
+    }
+}
+
+/// A candidate contribution built from a cluster of unassigned commits
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub name: String,
+    pub overview: String,
+    pub description: String,
+    pub category: String,
+    /// Largest commits by lines changed
+    pub key_commits: Vec<String>,
+    pub related_commits: Vec<String>,
+    /// First commit day, used to order suggestions of equal size
+    pub start_date: String,
+}
+
+impl Suggestion {
+    pub fn commit_count(&self) -> usize {
+        self.key_commits.len() + self.related_commits.len()
+    }
+}
+
+/// Union-find over commit indices
+struct Clusters {
+    parent: Vec<usize>,
+}
+
+impl Clusters {
+    fn new(len: usize) -> Self {
+        Self { parent: (0..len).collect() }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        // Path compression
+        let mut i = i;
+        while self.parent[i] != root {
+            let next = self.parent[i];
+            self.parent[i] = root;
+            i = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[b.max(a)] = a.min(b);
+        }
+    }
+}
+
+/// Split a conventional-commit subject like `feat(api)!: add x` into type and scope
+fn conventional(subject: &str) -> Option<(String, Option<String>)> {
+    let (head, _) = subject.split_once(':')?;
+    let head = head.trim_end_matches('!');
+    let (kind, scope) = match head.split_once('(') {
+        Some((kind, rest)) => (kind, Some(rest.strip_suffix(')')?.trim().to_lowercase())),
+        None => (head, None),
+    };
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some((kind.to_lowercase(), scope.filter(|s| !s.is_empty())))
+}
+
+/// Directory area of a path: its first two directories, or its only directory.
+/// Files at the repository root have no area.
+fn path_area(path: &str) -> Option<String> {
+    let dirs: Vec<&str> = path.split('/').collect();
+    let dirs = &dirs[..dirs.len().saturating_sub(1)];
+    match dirs {
+        [] => None,
+        [first] => Some(first.to_string()),
+        [first, second, ..] => Some(format!("{}/{}", first, second)),
+    }
+}
+
+/// The area most of a commit's files fall in
+fn primary_area(commit: &Commit) -> Option<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for area in commit.files_changed.iter().filter_map(|f| path_area(f)) {
+        *counts.entry(area).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+        .map(|(area, _)| area)
+}
+
+/// Turn a branch, scope or path into a contribution name: `feature/sync-engine` → `Sync Engine`
+fn humanize(label: &str) -> String {
+    let label = label.trim_end_matches('/');
+    let label = label.rsplit('/').next().unwrap_or(label);
+    label
+        .split(['-', '_', ' ', '.'])
+        .filter(|w| !w.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn category_for(kind: Option<&str>) -> &'static str {
+    match kind {
+        Some("feat") => "Feature",
+        Some("fix") => "Bug Fix",
+        Some("perf") => "Performance",
+        Some("build") | Some("ci") | Some("chore") => "Infrastructure",
+        Some("refactor") => "Feature Enhancement",
+        _ => "Feature",
+    }
+}
+
+fn parse_date(commit: &Commit) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&commit.date).ok().map(|d| d.with_timezone(&Utc))
+}
+
+fn subject(commit: &Commit) -> &str {
+    commit.message.lines().next().unwrap_or("").trim()
+}
+
+/// Group unassigned commits into candidate contributions. Commits are joined when:
+/// - they came in through the same merge (`merge_groups`, from `git::merge_groups`)
+/// - they share a conventional-commit scope within `options.window` of each other
+/// - their files mostly fall in the same directory area within `options.window`
+///
+/// Suggestions are ordered by size, largest first.
+pub fn suggest(
+    commits: &[Commit],
+    merge_groups: &[(Option<String>, Vec<String>)],
+    options: &SuggestOptions,
+) -> Vec<Suggestion> {
+    let mut clusters = Clusters::new(commits.len());
+    let index: HashMap<&str, usize> = commits.iter().enumerate().map(|(i, c)| (c.hash.as_str(), i)).collect();
+
+    // Merge ancestry
+    let mut branch_names: HashMap<usize, String> = HashMap::new();
+    for (branch, hashes) in merge_groups {
+        let members: Vec<usize> = hashes.iter().filter_map(|h| index.get(h.as_str()).copied()).collect();
+        for pair in members.windows(2) {
+            clusters.union(pair[0], pair[1]);
+        }
+        if let (Some(branch), Some(&first)) = (branch, members.first()) {
+            branch_names.entry(first).or_insert_with(|| branch.clone());
+        }
+    }
+
+    // Scope and path area, chained through time
+    let mut order: Vec<usize> = (0..commits.len()).collect();
+    order.sort_by_key(|&i| parse_date(&commits[i]));
+    let mut last_by_key: HashMap<String, (usize, Option<DateTime<Utc>>)> = HashMap::new();
+    for &i in &order {
+        let date = parse_date(&commits[i]);
+        let scope = conventional(subject(&commits[i])).and_then(|(_, scope)| scope);
+        let keys = [
+            scope.map(|s| format!("scope:{}", s)),
+            primary_area(&commits[i]).map(|a| format!("area:{}", a)),
+        ];
+        for key in keys.into_iter().flatten() {
+            if let Some(&(previous, previous_date)) = last_by_key.get(&key) {
+                let close = match (previous_date, date) {
+                    (Some(a), Some(b)) => b - a <= options.window,
+                    _ => false,
+                };
+                if close {
+                    clusters.union(previous, i);
+                }
+            }
+            last_by_key.insert(key, (i, date));
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for &i in &order {
+        let root = clusters.find(i);
+        groups.entry(root).or_default().push(i);
+    }
+
+    let mut suggestions: Vec<Suggestion> = groups
+        .into_values()
+        .filter(|members| members.len() >= options.min_commits)
+        .map(|members| build_suggestion(commits, &members, &branch_names, options))
+        .collect();
+
+    suggestions.sort_by(|a, b| {
+        b.commit_count()
+            .cmp(&a.commit_count())
+            .then_with(|| a.start_date.cmp(&b.start_date))
+            // Keep numbering stable between runs so `--accept` picks what was listed
+            .then_with(|| a.key_commits.cmp(&b.key_commits))
+    });
+    suggestions
+}
+
+fn build_suggestion(
+    commits: &[Commit],
+    members: &[usize],
+    branch_names: &HashMap<usize, String>,
+    options: &SuggestOptions,
+) -> Suggestion {
+    let mut by_size = members.to_vec();
+    let churn = |i: usize| {
+        let (added, deleted) = commits[i].meaningful_lines();
+        added.unwrap_or(0) + deleted.unwrap_or(0)
+    };
+    by_size.sort_by_key(|&i| std::cmp::Reverse(churn(i)));
+
+    // Most common conventional type and scope, and most common area
+    let mut kinds: HashMap<String, usize> = HashMap::new();
+    let mut scopes: HashMap<String, usize> = HashMap::new();
+    let mut areas: HashMap<String, usize> = HashMap::new();
+    for &i in members {
+        if let Some((kind, scope)) = conventional(subject(&commits[i])) {
+            *kinds.entry(kind).or_default() += 1;
+            if let Some(scope) = scope {
+                *scopes.entry(scope).or_default() += 1;
+            }
+        }
+        if let Some(area) = primary_area(&commits[i]) {
+            *areas.entry(area).or_default() += 1;
+        }
+    }
+    let most_common = |counts: &HashMap<String, usize>| {
+        counts
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(k, _)| k.clone())
+    };
+    let kind = most_common(&kinds);
+    let scope = most_common(&scopes);
+    let area = most_common(&areas);
+
+    let branch = members.iter().find_map(|i| branch_names.get(i));
+    let top = &commits[by_size[0]];
+    let top_subject = subject(top);
+    let name = branch
+        .map(|b| humanize(b))
+        .or_else(|| scope.as_deref().map(humanize))
+        .or_else(|| area.as_deref().map(humanize))
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| top_subject.to_string());
+
+    // Overview is the largest commit's subject without its conventional prefix
+    let overview = match conventional(top_subject) {
+        Some(_) => top_subject.split_once(':').map(|(_, s)| s.trim()).unwrap_or(top_subject),
+        None => top_subject,
+    };
+
+    let dates: Vec<&str> = members.iter().filter_map(|&i| commits[i].date.get(..10)).collect();
+    let start_date = dates.iter().min().map(|d| d.to_string()).unwrap_or_default();
+    let end_date = dates.iter().max().map(|d| d.to_string()).unwrap_or_default();
+
+    let mut description = format!("{} commits from {} to {}", members.len(), start_date, end_date);
+    if let Some(area) = &area {
+        description.push_str(&format!(", mostly in {}", area));
+    }
+
+    let hashes: Vec<String> = by_size.iter().map(|&i| commits[i].hash.clone()).collect();
+    let split = options.key_commits.min(hashes.len());
+
+    Suggestion {
+        name,
+        overview: overview.to_string(),
+        description,
+        category: category_for(kind.as_deref()).to_string(),
+        key_commits: hashes[..split].to_vec(),
+        related_commits: hashes[split..].to_vec(),
+        start_date,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(hash: &str, date: &str, message: &str, files: &[&str], lines: i32) -> Commit {
+        Commit {
+            hash: hash.to_string(),
+            repository_url: "https://github.com/org/repo".to_string(),
+            author: "Ann".to_string(),
+            author_email: "ann@example.com".to_string(),
+            date: format!("{}T12:00:00+00:00", date),
+            message: message.to_string(),
+            files_changed: files.iter().map(|f| f.to_string()).collect(),
+            lines_added: Some(lines),
+            lines_deleted: Some(0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_conventional_and_humanize() {
+        assert_eq!(conventional("feat(api)!: add x"), Some(("feat".to_string(), Some("api".to_string()))));
+        assert_eq!(conventional("fix: y"), Some(("fix".to_string(), None)));
+        assert_eq!(conventional("Update README: typo"), None);
+        assert_eq!(humanize("feature/sync-engine"), "Sync Engine");
+        assert_eq!(path_area("src/render/html.rs"), Some("src/render".to_string()));
+        assert_eq!(path_area("Cargo.toml"), None);
+    }
+
+    #[test]
+    fn test_groups_by_area_within_window() {
+        let commits = vec![
+            commit("a1", "2024-01-01", "Add parser", &["src/parser/mod.rs"], 10),
+            commit("a2", "2024-01-03", "Parse comments", &["src/parser/lexer.rs"], 50),
+            commit("b1", "2024-01-02", "feat(ui): add menu", &["web/menu.ts"], 5),
+            commit("b2", "2024-01-04", "fix(ui): menu focus", &["web/focus.ts"], 3),
+            // Same area as a1/a2 but a month later
+            commit("a3", "2024-02-10", "Parser cleanup", &["src/parser/mod.rs"], 2),
+        ];
+        let suggestions = suggest(&commits, &[], &SuggestOptions::default());
+
+        assert_eq!(suggestions.len(), 2);
+        let parser = suggestions.iter().find(|s| s.name == "Parser").unwrap();
+        assert_eq!(parser.key_commits, vec!["a2", "a1"]);
+        let ui = suggestions.iter().find(|s| s.name == "Ui").unwrap();
+        assert_eq!(ui.category, "Feature");
+        assert_eq!(ui.overview, "add menu");
+    }
+
+    #[test]
+    fn test_merge_groups_join_commits_and_name_cluster() {
+        let commits = vec![
+            commit("c1", "2024-01-01", "Start sync", &["src/sync.rs"], 10),
+            commit("c2", "2024-03-01", "Finish docs", &["docs/sync.md"], 10),
+            commit("m1", "2024-03-02", "Merge branch 'feature/sync-engine'", &[], 0),
+        ];
+        let groups = vec![(
+            Some("feature/sync-engine".to_string()),
+            vec!["m1".to_string(), "c2".to_string(), "c1".to_string()],
+        )];
+        let suggestions = suggest(&commits, &groups, &SuggestOptions::default());
+
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].name, "Sync Engine");
+        assert_eq!(suggestions[0].commit_count(), 3);
+    }
+}
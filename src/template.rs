@@ -0,0 +1,470 @@
+use anyhow::{Context as _, Result};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tera::{Context, Tera};
+
+use crate::database::{distinct_changes, Commit, Contribution, LineTotals};
+use crate::identity::AuthorFilter;
+use crate::release::released_range;
+use crate::render::{RenderInput, Renderer};
+use crate::utils::short_hash;
+
+/// The built-in template; it produces the standard CONTRIBUTIONS.md layout
+pub const DEFAULT_TEMPLATE: &str = include_str!("templates/default.md.tera");
+
+/// The built-in template for `contrack portfolio`
+pub const PORTFOLIO_TEMPLATE: &str = include_str!("templates/portfolio.md.tera");
+
+/// Name of the built-in template, which a user template of the same name overrides
+pub const DEFAULT_TEMPLATE_NAME: &str = "default";
+
+const TEMPLATE_SUFFIX: &str = ".tera";
+
+/// Subdirectory of each template directory holding `portfolio` templates
+const PORTFOLIO_TEMPLATE_DIR: &str = "portfolio";
+
+/// A Tera template used as an output format for `generate`
+pub struct TemplateRenderer {
+    name: String,
+    source: String,
+    extension: &'static str,
+}
+
+impl TemplateRenderer {
+    pub fn builtin() -> Self {
+        Self {
+            name: "default.md".to_string(),
+            source: DEFAULT_TEMPLATE.to_string(),
+            extension: "md",
+        }
+    }
+
+    pub fn portfolio() -> Self {
+        Self {
+            name: "portfolio.md".to_string(),
+            source: PORTFOLIO_TEMPLATE.to_string(),
+            extension: "md",
+        }
+    }
+
+    /// Render against an already-built context, such as the portfolio context
+    pub fn render_value(&self, context: Value) -> Result<String> {
+        render_with_context(&self.name, &self.source, context)
+    }
+
+    /// Load a template by name from the template directories.
+    /// `default` falls back to the built-in template when no user template overrides it.
+    pub fn load(name: &str) -> Result<Self> {
+        Self::load_from(name, None)
+    }
+
+    /// Load a portfolio template by name. Portfolio templates receive a different
+    /// context, so they live apart in the `portfolio/` subdirectory of each template
+    /// directory; `default` falls back to the built-in portfolio layout.
+    pub fn load_portfolio(name: &str) -> Result<Self> {
+        Self::load_from(name, Some(PORTFOLIO_TEMPLATE_DIR))
+    }
+
+    fn load_from(name: &str, subdir: Option<&str>) -> Result<Self> {
+        for dir in template_dirs() {
+            let dir = match subdir {
+                Some(subdir) => dir.join(subdir),
+                None => dir,
+            };
+            if let Some(path) = find_template(&dir, name)? {
+                let source = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read template {:?}", path))?;
+                let file_name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| name.to_string());
+                // Register without the .tera suffix so Tera autoescapes .html templates
+                let name = file_name.strip_suffix(TEMPLATE_SUFFIX).unwrap_or(&file_name).to_string();
+                return Ok(Self {
+                    extension: extension_for(&file_name),
+                    name,
+                    source,
+                });
+            }
+        }
+
+        if name == DEFAULT_TEMPLATE_NAME {
+            return Ok(match subdir {
+                Some(_) => Self::portfolio(),
+                None => Self::builtin(),
+            });
+        }
+
+        let available = available_templates(subdir)?;
+        Err(anyhow::anyhow!(
+            "{} '{}' not found. Available templates: {}",
+            match subdir {
+                Some(_) => "Portfolio template",
+                None => "Template",
+            },
+            name,
+            available.into_iter().collect::<Vec<_>>().join(", ")
+        ))
+    }
+}
+
+impl Renderer for TemplateRenderer {
+    fn render(&self, input: &RenderInput) -> Result<String> {
+        render_template(&self.name, &self.source, input)
+    }
+
+    fn extension(&self) -> &'static str {
+        self.extension
+    }
+}
+
+/// Directories searched for user templates, in order of precedence
+fn template_dirs() -> Vec<PathBuf> {
+    use crate::utils::{get_config_dir, get_contrack_dir};
+
+    let mut dirs = Vec::new();
+    if let Some(contrack_dir) = get_contrack_dir() {
+        dirs.push(contrack_dir.join("templates"));
+    }
+    if let Ok(config_dir) = get_config_dir() {
+        dirs.push(config_dir.join("templates"));
+    }
+    dirs
+}
+
+/// Split a template file name into its name and output extension:
+/// `report.html.tera` is `report` rendering to `.html`, and `report.tera` and
+/// `my.report.tera` (no known inner extension) are `report` and `my.report`
+/// rendering markdown
+fn split_template_file(file_name: &str) -> Option<(&str, &'static str)> {
+    let stem = file_name.strip_suffix(TEMPLATE_SUFFIX)?;
+    let known = stem.rsplit_once('.').and_then(|(name, ext)| {
+        let ext = match ext {
+            "html" | "htm" => "html",
+            "json" => "json",
+            "txt" => "txt",
+            "xml" => "xml",
+            "csv" => "csv",
+            "tex" => "tex",
+            "md" => "md",
+            _ => return None,
+        };
+        Some((name, ext))
+    });
+    Some(known.unwrap_or((stem, "md")))
+}
+
+/// Template name for a file (see `split_template_file`)
+fn template_name(file_name: &str) -> Option<&str> {
+    split_template_file(file_name).map(|(name, _)| name)
+}
+
+/// Output extension for a template file (see `split_template_file`)
+fn extension_for(file_name: &str) -> &'static str {
+    split_template_file(file_name).map_or("md", |(_, ext)| ext)
+}
+
+/// The template called `name` in `dir`. Two files with the same name, such as
+/// `report.tera` and `report.html.tera`, are an error rather than a guess.
+fn find_template(dir: &Path, name: &str) -> Result<Option<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+
+    let mut matches = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else { continue };
+        if template_name(file_name) == Some(name) {
+            matches.push(path);
+        }
+    }
+    matches.sort();
+    if matches.len() > 1 {
+        let files: Vec<String> = matches.iter().map(|p| format!("{:?}", p)).collect();
+        anyhow::bail!("Template '{}' is defined more than once ({}); keep one", name, files.join(", "));
+    }
+    Ok(matches.pop())
+}
+
+/// Names of all templates in `subdir` of the template directories (the
+/// `generate` templates when `None`), built-in and user-defined
+fn available_templates(subdir: Option<&str>) -> Result<BTreeSet<String>> {
+    let mut names = BTreeSet::new();
+    names.insert(DEFAULT_TEMPLATE_NAME.to_string());
+
+    for dir in template_dirs() {
+        let dir = match subdir {
+            Some(subdir) => dir.join(subdir),
+            None => dir,
+        };
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in std::fs::read_dir(&dir).with_context(|| format!("Failed to read {:?}", dir))? {
+            let path = entry?.path();
+            if let Some(name) = path.file_name().and_then(|n| n.to_str()).and_then(template_name) {
+                names.insert(name.to_string());
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Render a template source against the report data.
+/// Autoescaping follows Tera's rules, so it is on for `.html` template names.
+pub fn render_template(name: &str, source: &str, input: &RenderInput) -> Result<String> {
+    render_with_context(name, source, build_context(input))
+}
+
+fn render_with_context(name: &str, source: &str, context: Value) -> Result<String> {
+    let mut tera = Tera::default();
+    tera.add_raw_template(name, source)
+        .with_context(|| format!("Failed to parse template '{}'", name))?;
+
+    let context = Context::from_value(context)
+        .context("Failed to build template context")?;
+
+    tera.render(name, &context)
+        .map_err(|e| anyhow::anyhow!("Failed to render template '{}': {}", name, error_chain(&e)))
+}
+
+/// Tera reports the useful detail in the error's source chain
+fn error_chain(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        message.push_str(": ");
+        message.push_str(&e.to_string());
+        source = e.source();
+    }
+    message
+}
+
+/// Template value for a commit. `lines_added`/`lines_deleted` leave out
+/// excluded files; `raw_lines_added`/`raw_lines_deleted` count everything.
+pub fn commit_value(commit: &Commit) -> Value {
+    let (lines_added, lines_deleted) = commit.meaningful_lines();
+    json!({
+        "hash": commit.hash,
+        "short_hash": short_hash(&commit.hash),
+        "author": commit.author,
+        "author_email": commit.author_email,
+        "date": commit.date,
+        "author_date": commit.author_date(),
+        "committer_date": commit.committer_date(),
+        "subject": commit.message.lines().next().unwrap_or(""),
+        "message": commit.message,
+        "files_changed": commit.files_changed,
+        "lines_added": lines_added,
+        "lines_deleted": lines_deleted,
+        "raw_lines_added": commit.lines_added,
+        "raw_lines_deleted": commit.lines_deleted,
+        "refs": commit.refs,
+        "files": commit.files,
+        "authors": commit.authors,
+        "released_in": commit.released_in,
+        "surviving_lines": commit.surviving_lines,
+        "reverts": commit.reverts,
+        "reverted_by": commit.reverted_by,
+        "reverted": commit.is_reverted(),
+        "role": commit.role.map(|role| role.as_str()),
+    })
+}
+
+pub fn contribution_value(contrib: &Contribution, commits: &[&Commit]) -> Value {
+    let key_commits: Vec<Value> = contrib
+        .key_commits
+        .iter()
+        .map(|hash| {
+            let commit = commits.iter().find(|c| c.hash.starts_with(hash.as_str()));
+            // The same change under other hashes, e.g. cherry-picks to release branches
+            let equivalents: Vec<Value> = commit
+                .and_then(|commit| commit.patch_id.as_ref().map(|id| (commit, id)))
+                .map(|(commit, id)| {
+                    commits
+                        .iter()
+                        .filter(|c| c.hash != commit.hash && c.patch_id.as_ref() == Some(id))
+                        .map(|c| commit_value(c))
+                        .collect()
+                })
+                .unwrap_or_default();
+            json!({ "hash": hash, "commit": commit.map(|c| commit_value(c)), "equivalents": equivalents })
+        })
+        .collect();
+
+    let totals = LineTotals::of(commits.iter().copied());
+
+    let mut details: Vec<_> = contrib.technical_details.iter().collect();
+    details.sort_by(|a, b| a.0.cmp(b.0));
+    let technical_details: Vec<Value> = details
+        .into_iter()
+        .map(|(key, value)| json!({ "key": key, "value": value }))
+        .collect();
+
+    json!({
+        "id": contrib.id,
+        "name": contrib.name,
+        "overview": contrib.overview,
+        "description": contrib.description,
+        "category": contrib.category,
+        "priority": contrib.priority,
+        "key_commits": key_commits,
+        "related_commits": contrib.related_commits,
+        "technical_details": technical_details,
+        "resume_bullets": contrib.resume_bullets,
+        "released_in": released_range(contrib, commits),
+        "lines_added": totals.lines_added,
+        "lines_deleted": totals.lines_deleted,
+        "surviving_lines": totals.surviving_lines,
+        "commits": commits.iter().map(|c| commit_value(c)).collect::<Vec<_>>(),
+    })
+}
+
+/// Build the data every template receives:
+/// - `repository`: url, organization, name, description
+/// - `contributions`: contributions by priority, each with `commits` and resolved `key_commits`
+/// - `categories`: contributions grouped by category, highest priority first
+/// - `commits`: every commit shown in the document
+/// - `stats`: totals across the document
+/// - `author_filter`: the author filter, if any
+pub fn build_context(input: &RenderInput) -> Value {
+    fn filter_commits<'c>(commits: &'c [Commit], author_filter: Option<&AuthorFilter>) -> Vec<&'c Commit> {
+        match author_filter {
+            Some(filter) => commits.iter().filter(|c| filter.matches_commit(c)).collect(),
+            None => commits.iter().collect(),
+        }
+    }
+    let is_visible = |commits: &[&Commit]| input.author_filter.is_none() || !commits.is_empty();
+
+    // Group by category
+    let mut by_category: HashMap<&str, Vec<&(Contribution, Vec<Commit>)>> = HashMap::new();
+    for contrib in input.contributions {
+        by_category.entry(contrib.0.category.as_str()).or_default().push(contrib);
+    }
+
+    // Sort categories by the priority of their most important contribution
+    let mut categories: Vec<_> = by_category.into_iter().collect();
+    categories.sort_by(|a, b| b.0.cmp(a.0));
+    categories.sort_by_key(|(_, contribs)| contribs.iter().map(|(c, _)| c.priority).max().unwrap_or(0));
+    categories.reverse();
+
+    let mut category_values = Vec::new();
+    for (category, contribs) in categories {
+        // Sort contributions by priority
+        let mut sorted = contribs.clone();
+        sorted.sort_by_key(|(c, _)| c.priority);
+        sorted.reverse();
+
+        let visible: Vec<Value> = sorted
+            .into_iter()
+            .filter_map(|(contrib, commits)| {
+                let commits = filter_commits(commits, input.author_filter);
+                is_visible(&commits).then(|| contribution_value(contrib, &commits))
+            })
+            .collect();
+
+        category_values.push(json!({
+            "name": category,
+            "count": contribs.len(),
+            "contributions": visible,
+        }));
+    }
+
+    let mut contributions = Vec::new();
+    let mut all_commits = Vec::new();
+    for (contrib, commits) in input.contributions {
+        let commits = filter_commits(commits, input.author_filter);
+        if is_visible(&commits) {
+            contributions.push(contribution_value(contrib, &commits));
+            all_commits.extend(commits);
+        }
+    }
+    // A commit listed by several contributions is counted once
+    let mut seen = HashSet::new();
+    all_commits.retain(|c| seen.insert(c.hash.as_str()));
+
+    let authors: BTreeSet<&str> = all_commits
+        .iter()
+        .flat_map(|c| c.credited().into_iter().map(|(name, _)| name))
+        .collect();
+    let totals = LineTotals::of(all_commits.iter().copied());
+    let distinct = distinct_changes(all_commits.iter().copied());
+    let stats = json!({
+        "total_contributions": input.contributions.len(),
+        "documented_contributions": contributions.len(),
+        "total_commits": all_commits.len(),
+        "total_changes": totals.changes,
+        "lines_added": totals.lines_added,
+        "lines_deleted": totals.lines_deleted,
+        "surviving_lines": totals.surviving_lines,
+        "raw_lines_added": distinct.iter().filter_map(|c| c.lines_added).map(i64::from).sum::<i64>(),
+        "raw_lines_deleted": distinct.iter().filter_map(|c| c.lines_deleted).map(i64::from).sum::<i64>(),
+        "authors": authors,
+    });
+
+    let repository = match input.repository {
+        Some(repo) => json!({
+            "url": repo.url,
+            "organization": repo.organization,
+            "name": repo.name,
+            "description": repo.description,
+        }),
+        None => json!({ "url": input.repo_url }),
+    };
+
+    json!({
+        "repository": repository,
+        "contributions": contributions,
+        "categories": category_values,
+        "commits": all_commits.iter().map(|c| commit_value(c)).collect::<Vec<_>>(),
+        "stats": stats,
+        "author_filter": input.author_filter.map(|f| &f.label),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_name_and_extension() {
+        assert_eq!(template_name("report.tera"), Some("report"));
+        assert_eq!(template_name("report.html.tera"), Some("report"));
+        assert_eq!(template_name("report.html"), None);
+        assert_eq!(template_name("my.report.tera"), Some("my.report"));
+        assert_eq!(template_name("my.report.html.tera"), Some("my.report"));
+        assert_eq!(extension_for("report.tera"), "md");
+        assert_eq!(extension_for("report.html.tera"), "html");
+        assert_eq!(extension_for("my.report.tera"), "md");
+    }
+
+    #[test]
+    fn test_duplicate_template_names_are_rejected() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("brief.tera"), "").unwrap();
+        std::fs::write(dir.path().join("my.brief.tera"), "").unwrap();
+        assert_eq!(find_template(dir.path(), "brief").unwrap(), Some(dir.path().join("brief.tera")));
+
+        std::fs::write(dir.path().join("brief.html.tera"), "").unwrap();
+        assert!(find_template(dir.path(), "brief").unwrap_err().to_string().contains("more than once"));
+    }
+
+    #[test]
+    fn test_render_user_template() {
+        let contributions = Vec::new();
+        let input = RenderInput {
+            repo_url: "https://github.com/org/repo",
+            repository: None,
+            contributions: &contributions,
+            author_filter: None,
+        };
+        let output = render_template(
+            "summary.txt",
+            "{{ repository.url }}: {{ stats.total_contributions }}",
+            &input,
+        ).unwrap();
+        assert_eq!(output, "https://github.com/org/repo: 0");
+    }
+}
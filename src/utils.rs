@@ -1,4 +1,5 @@
 use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
 use directories::ProjectDirs;
 use std::path::PathBuf;
 
@@ -48,7 +49,6 @@ pub fn get_database_path() -> Result<PathBuf> {
 }
 
 /// Get the path to the application config directory
-#[allow(dead_code)]
 pub fn get_config_dir() -> Result<PathBuf> {
     let project_dirs = ProjectDirs::from("com", "contrack", "contrack")
         .context("Failed to determine application config directory")?;
@@ -80,12 +80,171 @@ pub fn get_config_path() -> Result<PathBuf> {
     Ok(config_dir.join("config.toml"))
 }
 
+/// Parse a date filter given as `YYYY-MM-DD` or RFC 3339.
+/// A bare date means the start of that day (UTC), or its end when `end_of_day` is set.
+pub fn parse_date(value: &str, end_of_day: bool) -> Result<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .with_context(|| format!("Invalid date '{}': expected YYYY-MM-DD or RFC 3339", value))?;
+    let time = if end_of_day {
+        date.and_hms_opt(23, 59, 59)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+    Ok(time.context("Invalid time of day")?.and_utc())
+}
+
+/// Inclusive range of epoch seconds from `--since`/`--until` dates; an unset
+/// bound is open
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+impl DateRange {
+    pub fn parse(since: Option<&str>, until: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            since: since.map(|d| parse_date(d, false)).transpose()?.map(|d| d.timestamp()),
+            until: until.map(|d| parse_date(d, true)).transpose()?.map(|d| d.timestamp()),
+        })
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        self.since.is_some() || self.until.is_some()
+    }
+
+    pub fn contains(&self, time: i64) -> bool {
+        self.since.is_none_or(|since| time >= since) && self.until.is_none_or(|until| time <= until)
+    }
+}
+
+/// The first eight characters of a commit hash, or all of a shorter one
+pub fn short_hash(hash: &str) -> &str {
+    hash.get(..8).unwrap_or(hash)
+}
+
+/// Canonical form of a git remote URL, so the different spellings of one
+/// repository compare equal. SSH (including scp-style `git@host:org/repo`),
+/// `git://` and `http://` remotes become `https://host/org/repo`, lowercased,
+/// without a `.git` suffix, user info or trailing slash. Anything else, such
+/// as a local path, is returned without its trailing slash.
+pub fn normalize_repo_url(url: &str) -> String {
+    let url = url.trim();
+
+    let (host, path) = if let Some((scheme, rest)) = url.split_once("://") {
+        let scheme = scheme.to_lowercase();
+        if !matches!(scheme.as_str(), "http" | "https" | "ssh" | "git" | "git+ssh" | "ssh+git") {
+            return url.trim_end_matches('/').to_string();
+        }
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        // An SSH port says nothing about where the web URL lives
+        let host = if scheme.contains("ssh") || scheme == "git" {
+            host.split(':').next().unwrap_or(host)
+        } else {
+            host
+        };
+        (host, path)
+    } else {
+        // scp-style: [user@]host:path, where the host part has no slash
+        match url.split_once(':') {
+            Some((authority, path)) if !authority.contains('/') && authority.len() > 1 && !path.starts_with('\\') => {
+                (authority.rsplit('@').next().unwrap_or(authority), path)
+            }
+            _ => return url.trim_end_matches('/').to_string(),
+        }
+    };
+
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path).trim_end_matches('/');
+    format!("https://{}/{}", host.to_lowercase(), path.to_lowercase())
+}
+
+/// Organization (owner, including any subgroups) and repository name from a
+/// remote URL, keeping their case, e.g. `("Org", "Repo")` for `git@github.com:Org/Repo.git`
+pub fn repo_owner_and_name(url: &str) -> Option<(String, String)> {
+    let url = url.trim().trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?.1,
+        None => url.split_once(':')?.1,
+    };
+
+    let mut segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let name = segments.pop()?;
+    if segments.is_empty() {
+        return None;
+    }
+    Some((segments.join("/"), name.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_date_range_bounds_are_inclusive_days() {
+        let range = DateRange::parse(Some("2024-03-01"), Some("2024-03-31")).unwrap();
+        assert!(range.contains(parse_date("2024-03-01T00:00:00Z", false).unwrap().timestamp()));
+        assert!(range.contains(parse_date("2024-03-31T23:59:59Z", false).unwrap().timestamp()));
+        assert!(!range.contains(parse_date("2024-04-01T00:00:00Z", false).unwrap().timestamp()));
+        assert!(!DateRange::parse(None, None).unwrap().is_bounded());
+    }
+
+    #[test]
+    fn test_short_hash() {
+        assert_eq!(short_hash("0123456789abcdef"), "01234567");
+        assert_eq!(short_hash("abc123"), "abc123");
+    }
+
+    #[test]
+    fn test_repo_owner_and_name() {
+        let parts = |url| repo_owner_and_name(url).map(|(o, n)| o + "/" + &n);
+        assert_eq!(parts("git@github.com:Org/Repo.git").as_deref(), Some("Org/Repo"));
+        assert_eq!(parts("https://gitlab.com/group/sub/app/").as_deref(), Some("group/sub/app"));
+        assert_eq!(parts("ssh://git@host:2222/team/tool").as_deref(), Some("team/tool"));
+        assert_eq!(parts("https://github.com/lonely"), None);
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let start = parse_date("2024-03-01", false).unwrap();
+        assert_eq!(start.to_rfc3339(), "2024-03-01T00:00:00+00:00");
+        let end = parse_date("2024-03-01", true).unwrap();
+        assert_eq!(end.to_rfc3339(), "2024-03-01T23:59:59+00:00");
+        let exact = parse_date("2024-03-01T10:00:00+02:00", false).unwrap();
+        assert_eq!(exact.to_rfc3339(), "2024-03-01T08:00:00+00:00");
+        assert!(parse_date("March 1st", false).is_err());
+    }
+
+    #[test]
+    fn test_normalize_repo_url() {
+        let canonical = "https://github.com/org/repo";
+        for url in [
+            "https://github.com/org/repo",
+            "https://github.com/org/repo/",
+            "https://github.com/org/repo.git",
+            "https://GitHub.com/Org/Repo",
+            "http://user@github.com/org/repo.git",
+            "git@github.com:org/repo.git",
+            "git@github.com:/org/repo",
+            "ssh://git@github.com/org/repo.git",
+            "ssh://git@github.com:22/org/repo",
+            "git://github.com/org/repo.git",
+        ] {
+            assert_eq!(normalize_repo_url(url), canonical, "{}", url);
+        }
+        assert_eq!(normalize_repo_url("https://git.example.com:8443/a/b"), "https://git.example.com:8443/a/b");
+        assert_eq!(normalize_repo_url("/srv/git/repo/"), "/srv/git/repo");
+        assert_eq!(normalize_repo_url("C:\\repos\\app"), "C:\\repos\\app");
+    }
+
     #[test]
     fn test_database_path() {
         let path = get_database_path().unwrap();